@@ -1,3 +1,7 @@
 fn main() {
-    bare_test_macros::build_test_setup!();
+    // The bare-test link setup only applies to the bare-metal board tests;
+    // host builds run the simulator tests with the standard harness.
+    if std::env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("none") {
+        bare_test_macros::build_test_setup!();
+    }
 }
@@ -6,44 +6,54 @@
 //! - Idle state verification
 //! - Timeout handling for idle operations
 
-use crate::{PowerError, registers::PmuRegs, variants::RockchipDomainInfo};
+use crate::{
+    PowerError,
+    registers::{RegisterAccess, write_domain_bits},
+    time::{TimeoutStep, Waiter},
+    variants::{RockchipDomainInfo, RockchipPmuInfo},
+};
 use mbarrier::mb;
 
-/// Idle request timeout (in iterations)
-const IDLE_REQUEST_TIMEOUT: u32 = 10000;
-/// Idle acknowledgment timeout (in iterations)
-const IDLE_ACK_TIMEOUT: u32 = 10000;
-
-/// ACK register offset from idle register base
-const IDLE_ACK_OFFSET: usize = 0x0c;
-
 /// Bus idle controller
 pub struct BusIdleControl {
+    req_offset: u32,
     idle_offset: u32,
+    ack_offset: u32,
 }
 
 impl BusIdleControl {
     /// Create a new bus idle controller
     ///
     /// # Arguments
-    /// * `idle_offset` - Base offset for idle control registers
-    pub fn new(idle_offset: u32) -> Self {
-        Self { idle_offset }
+    /// * `info` - Chip-specific PMU information with the idle register offsets
+    pub fn new(info: &RockchipPmuInfo) -> Self {
+        Self {
+            req_offset: info.req_offset,
+            idle_offset: info.idle_offset,
+            ack_offset: info.ack_offset,
+        }
     }
 
     /// Request bus idle state
     ///
+    /// If the acknowledgment times out, the request is re-issued according to
+    /// the domain's [`IdleRetry`](crate::time::IdleRetry) settings before
+    /// giving up.
+    ///
     /// # Arguments
     /// * `reg` - PMU register accessor
+    /// * `waiter` - Clock, timeouts and retry settings of the domain transition
     /// * `domain_info` - Domain information containing idle control masks
     /// * `idle` - True to request idle, false to cancel idle request
     ///
     /// # Returns
     /// * `Ok(())` if successful
-    /// * `Err(PowerError)` if domain has no idle control or operation fails
-    pub fn request_idle(
+    /// * `Err(PowerError::IdleAckTimeout)` if every attempt timed out
+    /// * `Err(PowerError)` if the operation fails otherwise
+    pub async fn request_idle<R: RegisterAccess>(
         &self,
-        reg: &mut PmuRegs,
+        reg: &R,
+        waiter: &Waiter<'_>,
         domain_info: &RockchipDomainInfo,
         idle: bool,
     ) -> Result<(), PowerError> {
@@ -52,22 +62,50 @@ impl BusIdleControl {
             return Ok(());
         }
 
-        // Set idle request bit
-        let current = reg.read_u32(self.idle_offset as usize);
-        let new_value = if idle {
-            current | (domain_info.req_mask as u32)
-        } else {
-            current & !(domain_info.req_mask as u32)
-        };
-        reg.write_u32(self.idle_offset as usize, new_value);
+        let retry = waiter.idle_retry();
+        let mut attempt = 0;
+        loop {
+            if attempt > 0 {
+                if retry.toggle_request {
+                    self.write_request(reg, domain_info, !idle)?;
+                }
+                waiter.delay(retry.delay_before(attempt)).await;
+            }
 
-        mb();
+            // Set idle request bit
+            self.write_request(reg, domain_info, idle)?;
 
-        // Wait for acknowledgment
-        self.wait_idle_ack(reg, domain_info, idle)?;
+            // Wait for acknowledgment
+            match self.wait_idle_ack(reg, waiter, domain_info, idle).await {
+                Err(PowerError::IdleAckTimeout) if attempt < retry.retries => attempt += 1,
+                result => break result?,
+            }
+        }
 
         // Verify idle state
-        self.verify_idle_state(reg, domain_info, idle)?;
+        self.verify_idle_state(reg, waiter, domain_info, idle)
+            .await?;
+
+        Ok(())
+    }
+
+    /// Write the idle request bit of a domain
+    fn write_request<R: RegisterAccess>(
+        &self,
+        reg: &R,
+        domain_info: &RockchipDomainInfo,
+        idle: bool,
+    ) -> Result<(), PowerError> {
+        let req_offset = (self.req_offset + domain_info.req_offset) as usize;
+        write_domain_bits(
+            reg,
+            req_offset,
+            domain_info.req_mask,
+            domain_info.req_w_mask,
+            idle,
+        )?;
+
+        mb();
 
         Ok(())
     }
@@ -76,15 +114,17 @@ impl BusIdleControl {
     ///
     /// # Arguments
     /// * `reg` - PMU register accessor
+    /// * `waiter` - Clock and timeouts of the domain transition
     /// * `domain_info` - Domain information containing ACK mask
     /// * `expected` - Expected ACK state
     ///
     /// # Returns
     /// * `Ok(())` if ACK received within timeout
     /// * `Err(PowerError::IdleAckTimeout)` if timeout occurs
-    fn wait_idle_ack(
+    async fn wait_idle_ack<R: RegisterAccess>(
         &self,
-        reg: &PmuRegs,
+        reg: &R,
+        waiter: &Waiter<'_>,
         domain_info: &RockchipDomainInfo,
         expected: bool,
     ) -> Result<(), PowerError> {
@@ -93,33 +133,36 @@ impl BusIdleControl {
             return Ok(());
         }
 
-        let ack_offset = self.idle_offset as usize + IDLE_ACK_OFFSET;
-
-        for _ in 0..IDLE_ACK_TIMEOUT {
-            let val = reg.read_u32(ack_offset);
-            let ack_set = (val & (domain_info.ack_mask as u32)) == (domain_info.ack_mask as u32);
-
-            if ack_set == expected {
-                return Ok(());
-            }
+        let acked = waiter
+            .poll(TimeoutStep::IdleAck, || {
+                let val = reg.read_u32(self.ack_offset as usize)?;
+                let ack_set =
+                    (val & (domain_info.ack_mask as u32)) == (domain_info.ack_mask as u32);
+                Ok(ack_set == expected)
+            })
+            .await?;
+
+        if !acked {
+            return Err(PowerError::IdleAckTimeout);
         }
-
-        Err(PowerError::IdleAckTimeout)
+        Ok(())
     }
 
     /// Verify idle state matches expectation
     ///
     /// # Arguments
     /// * `reg` - PMU register accessor
+    /// * `waiter` - Clock and timeouts of the domain transition
     /// * `domain_info` - Domain information containing idle mask
     /// * `expected` - Expected idle state
     ///
     /// # Returns
     /// * `Ok(())` if state matches expectation within timeout
     /// * `Err(PowerError::IdleRequestTimeout)` if timeout occurs
-    fn verify_idle_state(
+    async fn verify_idle_state<R: RegisterAccess>(
         &self,
-        reg: &PmuRegs,
+        reg: &R,
+        waiter: &Waiter<'_>,
         domain_info: &RockchipDomainInfo,
         expected: bool,
     ) -> Result<(), PowerError> {
@@ -128,15 +171,18 @@ impl BusIdleControl {
             return Ok(());
         }
 
-        for _ in 0..IDLE_REQUEST_TIMEOUT {
-            let val = reg.read_u32(self.idle_offset as usize);
-            let is_idle = (val & (domain_info.idle_mask as u32)) == (domain_info.idle_mask as u32);
-
-            if is_idle == expected {
-                return Ok(());
-            }
+        let reached = waiter
+            .poll(TimeoutStep::IdleState, || {
+                let val = reg.read_u32(self.idle_offset as usize)?;
+                let is_idle =
+                    (val & (domain_info.idle_mask as u32)) == (domain_info.idle_mask as u32);
+                Ok(is_idle == expected)
+            })
+            .await?;
+
+        if !reached {
+            return Err(PowerError::IdleRequestTimeout);
         }
-
-        Err(PowerError::IdleRequestTimeout)
+        Ok(())
     }
 }
@@ -0,0 +1,409 @@
+//! PMU interrupt handling
+//!
+//! With an [`IrqLayout`] configured through
+//! [`RockchipPM::with_irq`](crate::RockchipPM::with_irq), interrupt sources
+//! can be enabled, masked and acknowledged, and async transitions stop asking
+//! to be polled again right away: a suspended wait is resumed when the
+//! interrupt handler calls [`IrqHandle::handle_irq`] (or
+//! [`wake_transitions`](IrqHandle::wake_transitions)), or by the time source
+//! once its budget has run out
+//! ([`TimeSource::wake_at`](crate::time::TimeSource::wake_at)). A
+//! [`PowerTransition`](crate::PowerTransition) is simply polled after the
+//! handler has run.
+//!
+//! [`RockchipPM`](crate::RockchipPM) is not `Sync` and must not be touched
+//! from interrupt context. The handler uses an [`IrqHandle`] instead, taken
+//! from [`RockchipPM::irq_handle`](crate::RockchipPM::irq_handle): it owns its
+//! own register accessor and shares only the waker slots with the driver, so
+//! it can be stored in a static and called while the driver is in use.
+//!
+//! The interrupt registers are not part of the register tables this crate
+//! shares with the Linux `pm_domains.c` driver, and no layout is built in for
+//! any chip: board code passes the offsets and source bits from its TRM. The
+//! offsets are relative to the same base as every other PMU register, so the
+//! interrupt registers must lie inside the mapped window. Blocking transitions ([`power_domain_on`](crate::RockchipPM::power_domain_on)
+//! etc.) keep polling either way.
+//!
+//! Suspended waits hold their waker in one of [`MAX_IRQ_WAITERS`] fixed
+//! slots, so the interrupt handler neither allocates nor takes a lock and
+//! may safely preempt a wait that is registering.
+
+use alloc::sync::Arc;
+use core::{
+    cell::UnsafeCell,
+    sync::atomic::{AtomicBool, AtomicU8, Ordering},
+    task::Waker,
+};
+
+use crate::{PmuRegs, PowerResult, RegisterAccess};
+
+/// Maximum number of async waits suspended on the PMU interrupt at a time
+///
+/// Further waits don't suspend but keep asking to be polled again, as they
+/// do without interrupts.
+pub const MAX_IRQ_WAITERS: usize = 16;
+
+/// Location of the PMU interrupt control registers
+///
+/// Offsets are relative to the PMU base, like every other register access.
+/// They come from the TRM of the board's chip; the crate ships none.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IrqLayout {
+    /// Hi-word write-enable register selecting the enabled sources
+    pub enable_offset: usize,
+    /// True if a set bit in the enable register masks the source instead of
+    /// enabling it
+    pub masked_when_set: bool,
+    /// Pending sources, cleared by writing 1 to their bits
+    pub status_offset: usize,
+}
+
+impl IrqLayout {
+    pub(crate) fn enable<R: RegisterAccess + ?Sized>(
+        &self,
+        reg: &R,
+        sources: u32,
+    ) -> PowerResult<()> {
+        let value = if self.masked_when_set { 0 } else { sources };
+        reg.write_hiword(self.enable_offset, sources, value)
+    }
+
+    pub(crate) fn mask<R: RegisterAccess + ?Sized>(
+        &self,
+        reg: &R,
+        sources: u32,
+    ) -> PowerResult<()> {
+        let value = if self.masked_when_set { sources } else { 0 };
+        reg.write_hiword(self.enable_offset, sources, value)
+    }
+
+    pub(crate) fn pending<R: RegisterAccess + ?Sized>(&self, reg: &R) -> PowerResult<u32> {
+        reg.read_u32(self.status_offset)
+    }
+
+    pub(crate) fn ack<R: RegisterAccess + ?Sized>(&self, reg: &R, sources: u32) -> PowerResult<()> {
+        reg.write_u32(self.status_offset, sources)
+    }
+}
+
+/// Interrupt side of a [`RockchipPM`](crate::RockchipPM)
+///
+/// Holds a register accessor of its own, the [`IrqLayout`] and the waker
+/// slots shared with the driver. It is `Send` and `Sync` whenever the
+/// register backend is, as [`PmuRegs`] is.
+///
+/// Every method is safe to call from interrupt context, including while the
+/// driver runs a transition: they only access the interrupt registers and
+/// the lock-free waker slots, and never allocate.
+#[derive(Clone)]
+pub struct IrqHandle<R: RegisterAccess = PmuRegs> {
+    reg: R,
+    layout: IrqLayout,
+    notifier: Arc<IrqNotifier>,
+}
+
+impl<R: RegisterAccess> IrqHandle<R> {
+    pub(crate) fn new(reg: R, layout: IrqLayout, notifier: Arc<IrqNotifier>) -> Self {
+        Self {
+            reg,
+            layout,
+            notifier,
+        }
+    }
+
+    /// Get the interrupt registers
+    pub fn layout(&self) -> &IrqLayout {
+        &self.layout
+    }
+
+    /// Enable the PMU interrupt `sources`
+    ///
+    /// # Arguments
+    /// * `sources` - Source bits of the enable register (bits 0-15)
+    pub fn enable_irq(&self, sources: u32) -> PowerResult<()> {
+        self.layout.enable(&self.reg, sources)
+    }
+
+    /// Mask the PMU interrupt `sources`
+    ///
+    /// # Arguments
+    /// * `sources` - Source bits of the enable register (bits 0-15)
+    pub fn mask_irq(&self, sources: u32) -> PowerResult<()> {
+        self.layout.mask(&self.reg, sources)
+    }
+
+    /// Get the pending PMU interrupt sources
+    pub fn pending_irq(&self) -> PowerResult<u32> {
+        self.layout.pending(&self.reg)
+    }
+
+    /// Acknowledge the PMU interrupt `sources`
+    pub fn ack_irq(&self, sources: u32) -> PowerResult<()> {
+        self.layout.ack(&self.reg, sources)
+    }
+
+    /// PMU interrupt handler entry point
+    ///
+    /// Acknowledges every pending source and wakes the transitions waiting
+    /// for a PMU event, so they re-check their status registers. Step-wise
+    /// transitions are not woken; poll them after this returns.
+    ///
+    /// # Returns
+    /// The sources that were pending
+    pub fn handle_irq(&self) -> PowerResult<u32> {
+        let pending = self.pending_irq()?;
+        if pending != 0 {
+            self.ack_irq(pending)?;
+        }
+        self.wake_transitions();
+        Ok(pending)
+    }
+
+    /// Wake every async transition waiting for a PMU interrupt
+    ///
+    /// Call this when a transition may have completed without raising an
+    /// interrupt.
+    pub fn wake_transitions(&self) {
+        self.notifier.notify();
+    }
+
+    /// Get the number of async transitions waiting for a PMU interrupt
+    pub fn waiting_transitions(&self) -> usize {
+        self.notifier.waiting()
+    }
+}
+
+/// Wakers of the waits suspended until the next PMU event
+///
+/// [`notify`](Self::notify) runs in interrupt context and may preempt a
+/// wait while it registers, so the wakers live in a fixed array of slots
+/// guarded by atomics instead of a lock or an allocation.
+pub(crate) struct IrqNotifier {
+    slots: [WakerSlot; MAX_IRQ_WAITERS],
+}
+
+impl Default for IrqNotifier {
+    fn default() -> Self {
+        Self {
+            slots: core::array::from_fn(|_| WakerSlot::default()),
+        }
+    }
+}
+
+impl IrqNotifier {
+    /// Reserve a slot for one suspended wait
+    ///
+    /// # Returns
+    /// `None` if [`MAX_IRQ_WAITERS`] waits are already suspended
+    pub fn claim(&self) -> Option<IrqSlot<'_>> {
+        self.slots
+            .iter()
+            .find(|slot| {
+                slot.claimed
+                    .compare_exchange(false, true, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+            })
+            .map(|slot| IrqSlot { slot })
+    }
+
+    /// Wake every registered wait
+    pub fn notify(&self) {
+        for slot in &self.slots {
+            slot.wake();
+        }
+    }
+
+    /// Get the number of registered waits
+    pub fn waiting(&self) -> usize {
+        self.slots
+            .iter()
+            .filter(|slot| slot.armed.load(Ordering::Acquire))
+            .count()
+    }
+}
+
+/// A slot of the [`IrqNotifier`], released when dropped
+pub(crate) struct IrqSlot<'a> {
+    slot: &'a WakerSlot,
+}
+
+impl IrqSlot<'_> {
+    /// Wake `waker` on the next [`IrqNotifier::notify`]
+    pub fn register(&self, waker: &Waker) {
+        self.slot.register(waker);
+    }
+}
+
+impl Drop for IrqSlot<'_> {
+    fn drop(&mut self) {
+        self.slot.clear();
+        self.slot.claimed.store(false, Ordering::Release);
+    }
+}
+
+/// No access to the waker in progress
+const IDLE: u8 = 0;
+/// The owning wait is storing or clearing its waker
+const REGISTERING: u8 = 0b01;
+/// An interrupt is taking the waker
+const WAKING: u8 = 0b10;
+
+/// Storage for one waker, shared between a wait and the interrupt handler
+///
+/// Whoever moves `state` away from [`IDLE`] owns `waker` until it puts it
+/// back. An interrupt arriving while the wait registers only sets
+/// [`WAKING`], and the wait delivers the wakeup itself when it is done.
+#[derive(Default)]
+struct WakerSlot {
+    /// Reserved by a suspended wait
+    claimed: AtomicBool,
+    /// Holding a waker that hasn't been woken yet
+    armed: AtomicBool,
+    state: AtomicU8,
+    waker: UnsafeCell<Option<Waker>>,
+}
+
+// SAFETY: `waker` is only accessed by whoever moved `state` away from IDLE
+unsafe impl Sync for WakerSlot {}
+
+impl WakerSlot {
+    fn register(&self, waker: &Waker) {
+        if self
+            .state
+            .compare_exchange(IDLE, REGISTERING, Ordering::Acquire, Ordering::Acquire)
+            .is_err()
+        {
+            // An interrupt is being handled right now
+            waker.wake_by_ref();
+            return;
+        }
+
+        // SAFETY: REGISTERING gives exclusive access to the waker
+        let previous = unsafe { (*self.waker.get()).replace(waker.clone()) };
+        self.armed.store(true, Ordering::Release);
+
+        if self
+            .state
+            .compare_exchange(REGISTERING, IDLE, Ordering::AcqRel, Ordering::Acquire)
+            .is_err()
+        {
+            // Notified while registering: deliver the wakeup ourselves
+            // SAFETY: the interrupt left the waker to us
+            let waker = unsafe { (*self.waker.get()).take() };
+            self.armed.store(false, Ordering::Release);
+            self.state.store(IDLE, Ordering::Release);
+            if let Some(waker) = waker {
+                waker.wake();
+            }
+        }
+        drop(previous);
+    }
+
+    fn wake(&self) {
+        if self.state.fetch_or(WAKING, Ordering::AcqRel) != IDLE {
+            // The wait is registering and will wake itself
+            return;
+        }
+
+        // SAFETY: WAKING gives exclusive access to the waker
+        let waker = unsafe { (*self.waker.get()).take() };
+        self.armed.store(false, Ordering::Release);
+        self.state.fetch_and(!WAKING, Ordering::Release);
+        if let Some(waker) = waker {
+            waker.wake();
+        }
+    }
+
+    fn clear(&self) {
+        if self
+            .state
+            .compare_exchange(IDLE, REGISTERING, Ordering::Acquire, Ordering::Acquire)
+            .is_err()
+        {
+            // An interrupt is taking the waker right now
+            return;
+        }
+
+        // SAFETY: REGISTERING gives exclusive access to the waker
+        let waker = unsafe { (*self.waker.get()).take() };
+        self.armed.store(false, Ordering::Release);
+        // A notification arriving meanwhile has nobody left to wake
+        self.state.store(IDLE, Ordering::Release);
+        drop(waker);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use core::{
+        cell::Cell,
+        task::{RawWaker, RawWakerVTable},
+    };
+
+    /// Waker target that can raise the interrupt while it is being cloned
+    struct Probe {
+        notifier: IrqNotifier,
+        irq_on_clone: Cell<bool>,
+        woken: Cell<usize>,
+    }
+
+    static VTABLE: RawWakerVTable = RawWakerVTable::new(
+        |data| {
+            let probe = unsafe { &*(data as *const Probe) };
+            if probe.irq_on_clone.replace(false) {
+                probe.notifier.notify();
+            }
+            RawWaker::new(data, &VTABLE)
+        },
+        wake,
+        wake,
+        |_| {},
+    );
+
+    fn wake(data: *const ()) {
+        let probe = unsafe { &*(data as *const Probe) };
+        probe.woken.set(probe.woken.get() + 1);
+    }
+
+    #[test]
+    fn test_irq_during_register() {
+        let probe = Probe {
+            notifier: IrqNotifier::default(),
+            irq_on_clone: Cell::new(false),
+            woken: Cell::new(0),
+        };
+        let waker =
+            unsafe { Waker::from_raw(RawWaker::new(&probe as *const Probe as *const (), &VTABLE)) };
+
+        // The interrupt fires while the waker is being stored
+        let slot = probe.notifier.claim().unwrap();
+        probe.irq_on_clone.set(true);
+        slot.register(&waker);
+        assert_eq!(probe.woken.get(), 1);
+        assert_eq!(probe.notifier.waiting(), 0);
+
+        // A regular registration waits for the next interrupt
+        slot.register(&waker);
+        assert_eq!(probe.notifier.waiting(), 1);
+        probe.notifier.notify();
+        assert_eq!(probe.woken.get(), 2);
+        assert_eq!(probe.notifier.waiting(), 0);
+
+        // Released slots drop their waker without waking it
+        slot.register(&waker);
+        drop(slot);
+        assert_eq!(probe.notifier.waiting(), 0);
+        probe.notifier.notify();
+        assert_eq!(probe.woken.get(), 2);
+    }
+
+    #[test]
+    fn test_irq_slots_are_bounded() {
+        let notifier = IrqNotifier::default();
+        let slots: [_; MAX_IRQ_WAITERS] = core::array::from_fn(|_| notifier.claim().unwrap());
+        assert!(notifier.claim().is_none());
+        drop(slots);
+        assert!(notifier.claim().is_some());
+    }
+}
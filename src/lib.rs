@@ -6,23 +6,44 @@
 #![no_std]
 
 extern crate alloc;
+#[cfg(feature = "std")]
+extern crate std;
 
 use rdif_base::DriverGeneric;
 
-use crate::{power_sequencer::PowerSequencer, registers::PmuRegs, variants::RockchipPmuInfo};
+use crate::{power_sequencer::PowerSequencer, variants::RockchipPmuInfo};
 use core::ptr::NonNull;
 
 // Make dependency_manager public for testing
 pub mod dependency_manager;
 mod idle_control;
+pub mod irq;
 mod memory_control;
 mod power_sequencer;
 mod qos_control;
-mod registers;
+mod qos_snapshot;
+pub mod registers;
+#[cfg(any(test, feature = "sim"))]
+pub mod sim;
+pub mod stats;
+pub mod time;
 mod variants;
 
-// Re-export PowerDomain type
-pub use variants::PowerDomain;
+// Re-export PowerDomain and QoS port types
+pub use variants::{PowerDomain, QosPort};
+
+// Re-export sequencer step identifiers and step-wise transitions
+pub use power_sequencer::{PowerStep, PowerTransition};
+
+// Re-export QoS port mapping
+pub use qos_control::{
+    QOS_PORT_SIZE, QosMapper, QosMode, QosProfile, QosProfileEntry, QosSettings, QosWindow,
+};
+
+// Re-export register access backends
+#[cfg(all(feature = "std", unix))]
+pub use registers::{MmapQosWindow, MmapRegion, MmapRegs};
+pub use registers::{PmuRegs, RegisterAccess};
 
 // Re-export chip-specific power domain constants as modules
 pub use variants::rk3568 as RK3568;
@@ -58,28 +79,560 @@ pub enum PowerError {
     QoSError,
     /// Invalid QoS configuration
     InvalidQoSConfig,
+    /// Write mask does not fit the lower half of a hi-word write-enable register
+    InvalidWriteMask,
+    /// Register offset outside the mapped PMU window
+    RegisterOutOfRange,
+    /// PMU interrupt registers not configured
+    IrqNotConfigured,
+    /// Power domain must be powered for the operation
+    DomainNotPowered,
+    /// QoS snapshot is truncated, corrupted, or for another format version or chip
+    InvalidQoSSnapshot,
 }
 
 pub type PowerResult<T> = Result<T, PowerError>;
 
-pub struct RockchipPM {
-    _board: RkBoard,
-    reg: PmuRegs,
+/// Rockchip PMU driver
+///
+/// Generic over the register access backend `R`, which defaults to the
+/// memory-mapped [`PmuRegs`].
+pub struct RockchipPM<R: RegisterAccess = PmuRegs> {
+    board: RkBoard,
+    reg: R,
     info: RockchipPmuInfo,
     dep_manager: dependency_manager::DependencyManager,
-    /// QoS state storage for persistence across power cycles
-    qos_states: alloc::collections::BTreeMap<PowerDomain, qos_control::QoSControl>,
+    /// QoS state saved at power off, restored at the next power on
+    qos_states: qos_control::QoSStates,
+    /// Mapping of the QoS port physical addresses
+    qos_mapper: Option<qos_control::CachedQosMapper>,
+    /// QoS profile currently applied
+    qos_profile: Option<qos_control::ActiveQosProfile>,
+    /// Set once a QoS save skipped for lack of a mapper has been reported
+    qos_unmapped_warned: core::cell::Cell<bool>,
+    /// Clock for the power sequence timeouts
+    time: alloc::boxed::Box<dyn time::TimeSource + Send>,
+    /// Budgets of the power sequence poll loops
+    timeouts: time::TimeoutPolicy,
+    /// Retry settings for timed out bus idle requests
+    idle_retry: time::IdleRetryPolicy,
+    /// Transition latency samples, when instrumentation is enabled
+    latency: Option<core::cell::RefCell<stats::LatencyStats>>,
+    /// PMU interrupt registers and the transitions waiting for an interrupt,
+    /// shared with the [`irq::IrqHandle`]s
+    irq: Option<(irq::IrqLayout, alloc::sync::Arc<irq::IrqNotifier>)>,
 }
 
 impl RockchipPM {
-    pub fn new(base: NonNull<u8>, board: RkBoard) -> Self {
+    /// Create a driver for the memory-mapped PMU at `base`
+    ///
+    /// Every register offset used by the chip's power domain table is checked
+    /// against the mapped window, so a bad table entry or a short mapping is
+    /// reported here instead of turning into a wild access later.
+    ///
+    /// # Arguments
+    /// * `base` - Virtual address of the mapped PMU (syscon) window
+    /// * `size` - Size of the mapped window in bytes, e.g. from the device tree
+    /// * `board` - Chip variant whose power domain table is used
+    ///
+    /// # Returns
+    /// * `Err(PowerError::RegisterOutOfRange)` if a table offset lies outside
+    ///   the window
+    pub fn new(base: NonNull<u8>, size: usize, board: RkBoard) -> PowerResult<Self> {
+        let reg = PmuRegs::new(base, size);
+        check_register_window(&reg, board)?;
+        Ok(Self::with_regs(reg, board))
+    }
+}
+
+#[cfg(all(feature = "std", unix))]
+impl RockchipPM<MmapRegs> {
+    /// Create a driver for a PMU window mapped through a file descriptor
+    ///
+    /// Like [`RockchipPM::new`], every register offset of the chip table is
+    /// checked against the mapped window.
+    ///
+    /// # Arguments
+    /// * `region` - PMU window mapped from `/dev/mem`, a UIO device or a
+    ///   register image file
+    /// * `board` - Chip variant whose power domain table is used
+    pub fn from_mmap(region: MmapRegion, board: RkBoard) -> PowerResult<Self> {
+        let reg = MmapRegs::new(region);
+        check_register_window(reg.pmu_regs(), board)?;
+        Ok(Self::with_regs(reg, board))
+    }
+}
+
+/// Check that every register used by the `board` table lies inside `reg`
+fn check_register_window(reg: &PmuRegs, board: RkBoard) -> PowerResult<()> {
+    let info = RockchipPmuInfo::new(board);
+    if !info.register_offsets().all(|offset| reg.contains(offset)) {
+        return Err(PowerError::RegisterOutOfRange);
+    }
+    Ok(())
+}
+
+impl<R: RegisterAccess> RockchipPM<R> {
+    /// Create a driver on top of a custom register access backend
+    ///
+    /// # Arguments
+    /// * `reg` - Register backend, addressed with offsets relative to the PMU base
+    /// * `board` - Chip variant whose power domain table is used
+    pub fn with_regs(reg: R, board: RkBoard) -> Self {
         Self {
-            _board: board,
+            board,
             info: RockchipPmuInfo::new(board),
-            reg: PmuRegs::new(base),
+            reg,
             dep_manager: dependency_manager::DependencyManager::new(),
-            qos_states: alloc::collections::BTreeMap::new(),
+            qos_states: Default::default(),
+            qos_mapper: None,
+            qos_profile: None,
+            qos_unmapped_warned: Default::default(),
+            time: time::default_time_source(),
+            timeouts: time::TimeoutPolicy::for_board(board),
+            idle_retry: time::IdleRetryPolicy::default(),
+            latency: None,
+            irq: None,
+        }
+    }
+
+    /// Use `time` to measure the power sequence timeouts
+    ///
+    /// By default the ARM generic timer is used on aarch64, the OS clock with
+    /// the `std` feature, and a [`PollCounter`](time::PollCounter) otherwise.
+    ///
+    /// # Arguments
+    /// * `time` - Monotonic microsecond clock
+    pub fn with_time_source(mut self, time: impl time::TimeSource + Send + 'static) -> Self {
+        self.time = alloc::boxed::Box::new(time);
+        self
+    }
+
+    /// Use `timeouts` for the power sequence poll loops
+    ///
+    /// Replaces the chip default from [`TimeoutPolicy::for_board`](time::TimeoutPolicy::for_board).
+    pub fn with_timeouts(mut self, timeouts: time::TimeoutPolicy) -> Self {
+        self.timeouts = timeouts;
+        self
+    }
+
+    /// Get the timeout policy
+    pub fn timeouts(&self) -> &time::TimeoutPolicy {
+        &self.timeouts
+    }
+
+    /// Get the timeout policy for modification
+    pub fn timeouts_mut(&mut self) -> &mut time::TimeoutPolicy {
+        &mut self.timeouts
+    }
+
+    /// Retry bus idle requests whose acknowledgment timed out
+    ///
+    /// By default a timed out acknowledgment fails the transition with
+    /// [`PowerError::IdleAckTimeout`] right away.
+    pub fn with_idle_retry(mut self, idle_retry: time::IdleRetryPolicy) -> Self {
+        self.idle_retry = idle_retry;
+        self
+    }
+
+    /// Get the bus idle retry policy for modification
+    pub fn idle_retry_mut(&mut self) -> &mut time::IdleRetryPolicy {
+        &mut self.idle_retry
+    }
+
+    /// Measure the latency of every power transition and step
+    ///
+    /// Steps are timestamped with the driver's [`TimeSource`](time::TimeSource),
+    /// which costs two clock reads per step.
+    pub fn with_latency_stats(mut self) -> Self {
+        self.set_latency_stats(true);
+        self
+    }
+
+    /// Enable or disable latency measurement
+    ///
+    /// Disabling drops the samples collected so far.
+    pub fn set_latency_stats(&mut self, enabled: bool) {
+        match (enabled, &self.latency) {
+            (true, None) => self.latency = Some(Default::default()),
+            (false, _) => self.latency = None,
+            _ => {}
+        }
+    }
+
+    /// Get a snapshot of the latency statistics
+    ///
+    /// # Returns
+    /// `None` if latency measurement is disabled
+    pub fn latency_stats(&self) -> Option<stats::LatencyStats> {
+        self.latency.as_ref().map(|stats| stats.borrow().clone())
+    }
+
+    /// Drop the latency samples collected so far
+    pub fn clear_latency_stats(&self) {
+        if let Some(stats) = &self.latency {
+            stats.borrow_mut().clear();
+        }
+    }
+
+    /// Drive async transitions from PMU interrupts
+    ///
+    /// Once configured, a suspended async wait is resumed by
+    /// [`IrqHandle::handle_irq`](irq::IrqHandle::handle_irq),
+    /// [`IrqHandle::wake_transitions`](irq::IrqHandle::wake_transitions) or
+    /// its timeout instead of asking to be polled again right away. Up to
+    /// [`irq::MAX_IRQ_WAITERS`] waits suspend at a time; any further ones keep
+    /// polling. Take the handle for the interrupt handler from
+    /// [`irq_handle`](Self::irq_handle).
+    ///
+    /// # Timeouts
+    /// A wait only suspends if the time source can wake it when its budget
+    /// runs out (see [`TimeSource::wake_at`](time::TimeSource::wake_at)), so
+    /// an interrupt that never comes (the domain hangs, the source is masked
+    /// or the layout is wrong) still ends in a timeout. With a clock that has
+    /// no timer, async waits keep polling as without interrupts. Blocking
+    /// calls and [`PowerTransition`]s polled by the caller are not affected.
+    ///
+    /// # Arguments
+    /// * `layout` - Interrupt registers, with the offsets from the TRM
+    pub fn with_irq(mut self, layout: irq::IrqLayout) -> Self {
+        self.irq = Some((layout, Default::default()));
+        self
+    }
+
+    /// Get a handle for the PMU interrupt handler
+    ///
+    /// The driver itself is not `Sync` and must not be used from interrupt
+    /// context; the handle is, with a clone of the register accessor.
+    ///
+    /// # Returns
+    /// * `Err(PowerError::IrqNotConfigured)` without [`with_irq`](Self::with_irq)
+    pub fn irq_handle(&self) -> PowerResult<irq::IrqHandle<R>>
+    where
+        R: Clone,
+    {
+        let (layout, notifier) = self.irq.as_ref().ok_or(PowerError::IrqNotConfigured)?;
+        Ok(irq::IrqHandle::new(
+            self.reg.clone(),
+            *layout,
+            notifier.clone(),
+        ))
+    }
+
+    /// Enable the PMU interrupt `sources`
+    ///
+    /// # Arguments
+    /// * `sources` - Source bits of the enable register (bits 0-15)
+    ///
+    /// # Returns
+    /// * `Err(PowerError::IrqNotConfigured)` without [`with_irq`](Self::with_irq)
+    pub fn enable_irq(&self, sources: u32) -> PowerResult<()> {
+        self.irq_layout()?.enable(&self.reg, sources)
+    }
+
+    /// Mask the PMU interrupt `sources`
+    ///
+    /// # Arguments
+    /// * `sources` - Source bits of the enable register (bits 0-15)
+    ///
+    /// # Returns
+    /// * `Err(PowerError::IrqNotConfigured)` without [`with_irq`](Self::with_irq)
+    pub fn mask_irq(&self, sources: u32) -> PowerResult<()> {
+        self.irq_layout()?.mask(&self.reg, sources)
+    }
+
+    /// Get the pending PMU interrupt sources
+    pub fn pending_irq(&self) -> PowerResult<u32> {
+        self.irq_layout()?.pending(&self.reg)
+    }
+
+    /// Acknowledge the PMU interrupt `sources`
+    pub fn ack_irq(&self, sources: u32) -> PowerResult<()> {
+        self.irq_layout()?.ack(&self.reg, sources)
+    }
+
+    /// Get the number of async transitions waiting for a PMU interrupt
+    pub fn waiting_transitions(&self) -> usize {
+        self.irq
+            .as_ref()
+            .map_or(0, |(_, notifier)| notifier.waiting())
+    }
+
+    fn irq_layout(&self) -> PowerResult<&irq::IrqLayout> {
+        self.irq
+            .as_ref()
+            .map(|(layout, _)| layout)
+            .ok_or(PowerError::IrqNotConfigured)
+    }
+
+    /// Get the register access backend
+    pub fn regs(&self) -> &R {
+        &self.reg
+    }
+
+    /// Reach the QoS ports through `mapper`
+    ///
+    /// QoS ports are listed by physical address, which is rarely mapped 1:1.
+    /// Until a mapper is set, QoS registers are not saved or restored, and the
+    /// first power off of a domain with QoS ports logs a warning. If the
+    /// mapper cannot map a port of a domain, powering it off fails with
+    /// [`PowerError::QoSError`] before any register is touched.
+    ///
+    /// Each port is mapped on first use and the address is kept for the
+    /// lifetime of the driver; the mapper is not called again for it.
+    ///
+    /// # Arguments
+    /// * `mapper` - Translation from physical to virtual addresses, e.g. a
+    ///   closure `|phys_addr, size| Some(iomap(phys_addr, size))`
+    pub fn with_qos_mapper(mut self, mapper: impl QosMapper + Send + 'static) -> Self {
+        self.qos_mapper = Some(qos_control::CachedQosMapper::new(alloc::boxed::Box::new(
+            mapper,
+        )));
+        self
+    }
+
+    /// Reach the QoS ports through a pre-mapped window
+    ///
+    /// # Arguments
+    /// * `base` - Virtual address the window is mapped at
+    /// * `phys_base` - Physical address of the start of the window
+    /// * `size` - Size of the window in bytes
+    pub fn with_qos_window(self, base: NonNull<u8>, phys_base: u64, size: usize) -> Self {
+        self.with_qos_mapper(QosWindow::new(base, phys_base, size))
+    }
+
+    /// Get the QoS ports whose registers are saved and restored with a domain
+    ///
+    /// # Returns
+    /// * `Err(PowerError::DomainNotFound)` if the domain is not in the chip table
+    pub fn qos_ports(&self, domain: PowerDomain) -> PowerResult<&'static [QosPort]> {
+        self.info
+            .domains
+            .get(&domain)
+            .map(|domain_info| domain_info.qos_ports)
+            .ok_or(PowerError::DomainNotFound)
+    }
+
+    /// Read the QoS settings of a port
+    ///
+    /// While the domain is powered the registers are read; while it is off
+    /// the settings saved at power off are returned, which are the ones
+    /// restored at the next power on.
+    ///
+    /// # Arguments
+    /// * `domain` - Power domain owning the port
+    /// * `port` - Index of the port in [`qos_ports`](Self::qos_ports)
+    ///
+    /// # Returns
+    /// * `Ok(QosSettings)` if successful
+    /// * `Err(PowerError::InvalidQoSConfig)` if the domain has no such port
+    /// * `Err(PowerError::QoSError)` if the port can't be mapped, or the domain
+    ///   is off without saved state
+    pub fn qos_settings(&self, domain: PowerDomain, port: usize) -> PowerResult<QosSettings> {
+        match self.qos_port_base(domain, port)? {
+            Some(base) => Ok(qos_control::read_settings(base)),
+            None => self
+                .qos_states
+                .borrow()
+                .get(&domain)
+                .and_then(|state| state.settings(port))
+                .ok_or(PowerError::QoSError),
+        }
+    }
+
+    /// Program the QoS settings of a port
+    ///
+    /// While the domain is powered the registers are written and saved again
+    /// at the next power off like any other value. While it is off the saved
+    /// state is updated instead, so the settings take effect at the next
+    /// power on.
+    ///
+    /// # Arguments
+    /// * `domain` - Power domain owning the port
+    /// * `port` - Index of the port in [`qos_ports`](Self::qos_ports)
+    /// * `settings` - New settings of the port
+    ///
+    /// # Returns
+    /// * `Ok(())` if successful
+    /// * `Err(PowerError::InvalidQoSConfig)` if the domain has no such port or
+    ///   a field is out of range
+    /// * `Err(PowerError::QoSError)` if the port can't be mapped, or the domain
+    ///   is off without saved state
+    pub fn set_qos_settings(
+        &mut self,
+        domain: PowerDomain,
+        port: usize,
+        settings: &QosSettings,
+    ) -> PowerResult<()> {
+        match self.qos_port_base(domain, port)? {
+            Some(base) => qos_control::write_settings(base, settings),
+            None => self
+                .qos_states
+                .get_mut()
+                .get_mut(&domain)
+                .ok_or(PowerError::QoSError)?
+                .set_settings(port, settings),
+        }
+    }
+
+    /// Apply a QoS profile
+    ///
+    /// Every port of the profile is programmed, and ports of a previously
+    /// applied profile that this one doesn't cover go back to the settings
+    /// they had before. Nothing is written unless all ports can be: each
+    /// domain named by the profile must be powered, each port mapped and
+    /// each setting in range. Ports going back to their old settings are
+    /// handled like in [`revert_qos_profile`](Self::revert_qos_profile), so
+    /// their domains may be off. The settings survive power cycles like any
+    /// other QoS value.
+    ///
+    /// # Arguments
+    /// * `profile` - Profile to apply
+    ///
+    /// # Returns
+    /// * `Ok(())` if successful
+    /// * `Err(PowerError::DomainNotPowered)` if a domain named by the profile
+    ///   is off
+    /// * `Err(PowerError::InvalidQoSConfig)` if a port or setting is invalid
+    /// * `Err(PowerError::QoSError)` if a port can't be mapped, or a domain
+    ///   going back to its old settings is off without saved state
+    pub fn apply_qos_profile(&mut self, profile: &QosProfile) -> PowerResult<()> {
+        let mut baseline = self
+            .qos_profile
+            .as_ref()
+            .map(|active| active.baseline.clone())
+            .unwrap_or_default();
+        let mut writes = baseline.clone();
+        let mut named = alloc::collections::BTreeSet::new();
+        for entry in &profile.entries {
+            entry.settings.validate()?;
+            writes.insert((entry.domain, entry.port), entry.settings);
+            named.insert((entry.domain, entry.port));
+        }
+
+        let targets = writes
+            .keys()
+            .map(|&(domain, port)| {
+                if !named.contains(&(domain, port)) {
+                    return self.qos_write_target(domain, port);
+                }
+                let base = self
+                    .qos_port_base(domain, port)?
+                    .ok_or(PowerError::DomainNotPowered)?;
+                Ok(Some(base))
+            })
+            .collect::<PowerResult<alloc::vec::Vec<_>>>()?;
+        for (&key, target) in writes.keys().zip(&targets) {
+            if let Some(base) = *target {
+                baseline
+                    .entry(key)
+                    .or_insert_with(|| qos_control::read_settings(base));
+            }
+        }
+        Self::write_qos_targets(self.qos_states.get_mut(), &writes, targets)?;
+
+        self.qos_profile = Some(qos_control::ActiveQosProfile {
+            name: profile.name,
+            baseline,
+        });
+        Ok(())
+    }
+
+    /// Revert the applied QoS profile
+    ///
+    /// Every port touched by the profile goes back to the settings it had
+    /// before the first profile was applied. Ports of powered domains are
+    /// written; for domains that are off the saved state is updated, as with
+    /// [`set_qos_settings`](Self::set_qos_settings). Nothing is written
+    /// unless all ports can be. Does nothing if no profile is applied.
+    ///
+    /// # Returns
+    /// * `Ok(())` if successful
+    /// * `Err(PowerError::QoSError)` if a port can't be mapped, or a domain is
+    ///   off without saved state, leaving the profile applied
+    pub fn revert_qos_profile(&mut self) -> PowerResult<()> {
+        let Some(active) = &self.qos_profile else {
+            return Ok(());
+        };
+
+        let targets = active
+            .baseline
+            .keys()
+            .map(|&(domain, port)| self.qos_write_target(domain, port))
+            .collect::<PowerResult<alloc::vec::Vec<_>>>()?;
+        Self::write_qos_targets(self.qos_states.get_mut(), &active.baseline, targets)?;
+
+        self.qos_profile = None;
+        Ok(())
+    }
+
+    /// Get the name of the applied QoS profile
+    pub fn active_qos_profile(&self) -> Option<&'static str> {
+        self.qos_profile.as_ref().map(|active| active.name)
+    }
+
+    /// Find where new settings of a QoS port go
+    ///
+    /// # Returns
+    /// * `Ok(Some(base))` with the mapped port if the domain is powered
+    /// * `Ok(None)` if the domain is off, for its saved state
+    /// * `Err(PowerError::QoSError)` if the port can't be mapped, or the
+    ///   domain is off without saved state
+    fn qos_write_target(
+        &self,
+        domain: PowerDomain,
+        port: usize,
+    ) -> PowerResult<Option<NonNull<u8>>> {
+        let base = self.qos_port_base(domain, port)?;
+        let saved = || {
+            let states = self.qos_states.borrow();
+            states
+                .get(&domain)
+                .and_then(|state| state.settings(port))
+                .is_some()
+        };
+        if base.is_none() && !saved() {
+            return Err(PowerError::QoSError);
         }
+        Ok(base)
+    }
+
+    /// Write QoS settings to the targets found by
+    /// [`qos_write_target`](Self::qos_write_target)
+    fn write_qos_targets(
+        states: &mut alloc::collections::BTreeMap<PowerDomain, qos_control::QoSControl>,
+        writes: &alloc::collections::BTreeMap<(PowerDomain, usize), QosSettings>,
+        targets: alloc::vec::Vec<Option<NonNull<u8>>>,
+    ) -> PowerResult<()> {
+        for ((&(domain, port), settings), target) in writes.iter().zip(targets) {
+            match target {
+                Some(base) => qos_control::write_settings(base, settings)?,
+                None => states
+                    .get_mut(&domain)
+                    .ok_or(PowerError::QoSError)?
+                    .set_settings(port, settings)?,
+            }
+        }
+        Ok(())
+    }
+
+    /// Map a QoS port of a powered domain
+    ///
+    /// # Returns
+    /// * `Ok(None)` if the domain is off and its registers are inaccessible
+    fn qos_port_base(&self, domain: PowerDomain, port: usize) -> PowerResult<Option<NonNull<u8>>> {
+        let port = self
+            .qos_ports(domain)?
+            .get(port)
+            .ok_or(PowerError::InvalidQoSConfig)?;
+        let mapper = self.qos_mapper.as_ref().ok_or(PowerError::QoSError)?;
+        if !self.is_domain_on(&domain)? {
+            return Ok(None);
+        }
+        mapper
+            .map(port.phys_addr, QOS_PORT_SIZE)
+            .map(Some)
+            .ok_or(PowerError::QoSError)
     }
 
     /// Check if QoS state exists for a domain
@@ -90,34 +643,160 @@ impl RockchipPM {
     /// # Returns
     /// true if QoS state has been saved for this domain
     pub fn has_qos_state(&self, domain: PowerDomain) -> bool {
-        self.qos_states.contains_key(&domain)
+        self.qos_states.borrow().contains_key(&domain)
     }
 
     /// Clear QoS state for a domain
     ///
+    /// The next power on of the domain leaves its QoS registers at their
+    /// reset values.
+    ///
     /// # Arguments
     /// * `domain` - Power domain to clear state for
     pub fn clear_qos_state(&mut self, domain: PowerDomain) {
-        self.qos_states.remove(&domain);
+        self.qos_states.get_mut().remove(&domain);
     }
 
     /// Clear all QoS states
     pub fn clear_all_qos_states(&mut self) {
-        self.qos_states.clear();
+        self.qos_states.get_mut().clear();
+    }
+
+    /// Export the saved QoS state of all domains
+    ///
+    /// The blob starts with the magic `RKQS`, the format version and the chip,
+    /// lists the five QoS registers of every port per domain together with a
+    /// fingerprint of the domain's port table, and ends with a CRC-32 of its
+    /// contents. It can be handed to another instance of the driver, e.g.
+    /// across a warm reboot, with [`import_qos_state`](Self::import_qos_state).
+    ///
+    /// # Returns
+    /// * `Ok(snapshot)`, which lists no domains if nothing was saved
+    /// * `Err(PowerError::InvalidQoSSnapshot)` if the saved state doesn't fit
+    ///   the snapshot format
+    pub fn export_qos_state(&self) -> PowerResult<alloc::vec::Vec<u8>> {
+        let states = self.qos_states.borrow();
+        let domains = states
+            .iter()
+            .filter_map(|(&domain, state)| {
+                let ports = self.qos_ports(domain).ok()?;
+                Some((domain, ports, state.saved_regs()?))
+            })
+            .collect::<alloc::vec::Vec<_>>();
+        qos_snapshot::encode(self.board, domains.into_iter())
+    }
+
+    /// Import QoS state exported by [`export_qos_state`](Self::export_qos_state)
+    ///
+    /// The imported state replaces the saved state of the domains in the
+    /// snapshot. Ports of powered domains are programmed right away, like
+    /// with [`set_qos_settings`](Self::set_qos_settings); the other domains
+    /// are restored at their next power on. Nothing is imported unless the
+    /// whole snapshot is valid.
+    ///
+    /// # Arguments
+    /// * `snapshot` - Blob written by `export_qos_state`
+    ///
+    /// # Returns
+    /// * `Ok(count)` with the number of imported domains
+    /// * `Err(PowerError::InvalidQoSSnapshot)` if the blob is invalid, taken on
+    ///   another chip, or doesn't match the chip's QoS port tables
+    /// * `Err(PowerError::QoSError)` if a port can't be mapped
+    pub fn import_qos_state(&mut self, snapshot: &[u8]) -> PowerResult<usize> {
+        let domains =
+            qos_snapshot::decode(self.board, snapshot, |domain| self.qos_ports(domain).ok())?;
+        let mapper = self.qos_mapper.as_ref().ok_or(PowerError::QoSError)?;
+
+        let mut states = alloc::vec::Vec::with_capacity(domains.len());
+        for (domain, saved_regs) in domains {
+            let qos_bases = qos_control::map_ports(self.qos_ports(domain)?, mapper)?;
+            let state = qos_control::QoSControl::with_saved(qos_bases, saved_regs)
+                .ok_or(PowerError::InvalidQoSSnapshot)?;
+            states.push((domain, state, self.is_domain_on(&domain)?));
+        }
+
+        let count = states.len();
+        for (domain, state, powered) in states {
+            if powered {
+                state.restore()?;
+            }
+            self.qos_states.get_mut().insert(domain, state);
+        }
+        Ok(count)
     }
 
     /// Power on the specified power domain
     pub fn power_domain_on(&mut self, domain: PowerDomain) -> PowerResult<()> {
-        let mut sequencer = PowerSequencer::new(&mut self.reg, &self.info);
+        let mut sequencer = self.sequencer();
         sequencer.power_on_sequence(domain)
     }
 
     /// Power off the specified power domain
     pub fn power_domain_off(&mut self, domain: PowerDomain) -> PowerResult<()> {
-        let mut sequencer = PowerSequencer::new(&mut self.reg, &self.info);
+        let mut sequencer = self.sequencer();
         sequencer.power_off_sequence(domain)
     }
 
+    /// Power on the specified power domain without blocking the executor
+    ///
+    /// Runs the same sequence as [`power_domain_on`](Self::power_domain_on),
+    /// but every status poll that is not yet satisfied yields to the executor,
+    /// so other tasks keep running while the domain powers up. Only shared
+    /// access is needed, so transitions of unrelated domains can be awaited
+    /// concurrently. Timeouts are still measured with the driver's
+    /// [`TimeSource`](time::TimeSource) and keep running while the task is
+    /// suspended.
+    ///
+    /// The future is not `Send`, since the register backend and clock are
+    /// borrowed without a `Sync` bound.
+    pub async fn power_domain_on_async(&self, domain: PowerDomain) -> PowerResult<()> {
+        let mut sequencer = self.sequencer();
+        sequencer.power_on_sequence_async(domain).await
+    }
+
+    /// Power off the specified power domain without blocking the executor
+    ///
+    /// Async counterpart of [`power_domain_off`](Self::power_domain_off), see
+    /// [`power_domain_on_async`](Self::power_domain_on_async).
+    pub async fn power_domain_off_async(&self, domain: PowerDomain) -> PowerResult<()> {
+        let mut sequencer = self.sequencer();
+        sequencer.power_off_sequence_async(domain).await
+    }
+
+    /// Start powering on a domain, to be driven with [`PowerTransition::poll`]
+    ///
+    /// # Arguments
+    /// * `domain` - Power domain to enable
+    ///
+    /// # Returns
+    /// * `Err(PowerError::DomainNotFound)` if the domain is not in the chip table
+    pub fn start_power_on(&self, domain: PowerDomain) -> PowerResult<PowerTransition<'_>> {
+        self.start_transition(domain, true)
+    }
+
+    /// Start powering off a domain, to be driven with [`PowerTransition::poll`]
+    ///
+    /// # Arguments
+    /// * `domain` - Power domain to disable
+    ///
+    /// # Returns
+    /// * `Err(PowerError::DomainNotFound)` if the domain is not in the chip table
+    pub fn start_power_off(&self, domain: PowerDomain) -> PowerResult<PowerTransition<'_>> {
+        self.start_transition(domain, false)
+    }
+
+    fn start_transition(
+        &self,
+        domain: PowerDomain,
+        power_on: bool,
+    ) -> PowerResult<PowerTransition<'_>> {
+        if !self.info.domains.contains_key(&domain) {
+            return Err(PowerError::DomainNotFound);
+        }
+        let sequencer = self.sequencer();
+        Ok(PowerTransition::new(sequencer, domain, power_on))
+    }
+
     /// Power on domain with dependency checking
     ///
     /// This method checks that all parent dependencies are satisfied before
@@ -141,7 +820,7 @@ impl RockchipPM {
         self.dep_manager.can_power_on(domain, domain_info)?;
 
         // Execute power on
-        let mut sequencer = PowerSequencer::new(&mut self.reg, &self.info);
+        let mut sequencer = self.sequencer();
         sequencer.power_on_sequence(domain)?;
 
         // Mark as active
@@ -173,7 +852,7 @@ impl RockchipPM {
         self.dep_manager.can_power_off(domain, domain_info)?;
 
         // Execute power off
-        let mut sequencer = PowerSequencer::new(&mut self.reg, &self.info);
+        let mut sequencer = self.sequencer();
         sequencer.power_off_sequence(domain)?;
 
         // Mark as inactive
@@ -193,6 +872,18 @@ impl RockchipPM {
         self.dep_manager.get_active_domains()
     }
 
+    /// Create a sequencer with the driver's clock, timeouts and statistics
+    fn sequencer(&self) -> PowerSequencer<'_, R> {
+        let sequencer = PowerSequencer::new(&self.reg, &self.info, &*self.time, &self.timeouts)
+            .with_idle_retry(&self.idle_retry)
+            .with_stats(self.latency.as_ref())
+            .with_irq(self.irq.as_ref().map(|(_, notifier)| &**notifier));
+        match &self.qos_mapper {
+            Some(mapper) => sequencer.with_qos(&self.qos_states, mapper),
+            None => sequencer.with_qos_unmapped(&self.qos_unmapped_warned),
+        }
+    }
+
     /// Check if power domain is on
     pub fn is_domain_on(&self, domain: &PowerDomain) -> PowerResult<bool> {
         let domain_info = self
@@ -203,7 +894,7 @@ impl RockchipPM {
 
         if domain_info.repair_status_mask != 0 {
             // Use repair status register
-            let val = self.reg.read_u32(self.info.repair_status_offset as usize);
+            let val = self.reg.read_u32(self.info.repair_status_offset as usize)?;
             // 1'b1: power on, 1'b0: power off
             return Ok((val & (domain_info.repair_status_mask as u32)) != 0);
         }
@@ -213,7 +904,7 @@ impl RockchipPM {
             return Ok(!self.is_domain_idle(domain)?);
         }
 
-        let val = self.reg.read_u32(self.info.status_offset as usize);
+        let val = self.reg.read_u32(self.info.status_offset as usize)?;
         // 1'b0: power on, 1'b1: power off
         Ok((val & (domain_info.status_mask as u32)) == 0)
     }
@@ -226,12 +917,77 @@ impl RockchipPM {
             .get(domain)
             .ok_or(PowerError::DomainNotFound)?;
 
-        let val = self.reg.read_u32(self.info.idle_offset as usize);
+        let val = self.reg.read_u32(self.info.idle_offset as usize)?;
         Ok((val & (domain_info.idle_mask as u32)) == (domain_info.idle_mask as u32))
     }
+
+    /// Wait until a domain reaches a power state
+    ///
+    /// Polls [`is_domain_on`](Self::is_domain_on) with the driver's
+    /// [`TimeSource`](time::TimeSource), for domains whose state is changed
+    /// outside this driver, e.g. by firmware or another core.
+    ///
+    /// # Arguments
+    /// * `domain` - Power domain to watch
+    /// * `expected_on` - Power state to wait for
+    /// * `timeout_us` - Budget in microseconds
+    ///
+    /// # Returns
+    /// * `Ok(())` once the domain is in the expected state
+    /// * `Err(PowerError::Timeout)` if it is not within `timeout_us`
+    /// * `Err(PowerError)` if the domain is unknown or a register read fails
+    pub fn wait_domain_state(
+        &self,
+        domain: PowerDomain,
+        expected_on: bool,
+        timeout_us: u64,
+    ) -> PowerResult<()> {
+        self.wait_for(
+            timeout_us,
+            || Ok(self.is_domain_on(&domain)? == expected_on),
+        )
+    }
+
+    /// Wait until a domain reaches a bus idle state
+    ///
+    /// Polls [`is_domain_idle`](Self::is_domain_idle), see
+    /// [`wait_domain_state`](Self::wait_domain_state).
+    ///
+    /// # Arguments
+    /// * `domain` - Power domain to watch
+    /// * `expected_idle` - Idle state to wait for
+    /// * `timeout_us` - Budget in microseconds
+    ///
+    /// # Returns
+    /// * `Ok(())` once the domain is in the expected state
+    /// * `Err(PowerError::Timeout)` if it is not within `timeout_us`
+    /// * `Err(PowerError)` if the domain is unknown or a register read fails
+    pub fn wait_domain_idle(
+        &self,
+        domain: PowerDomain,
+        expected_idle: bool,
+        timeout_us: u64,
+    ) -> PowerResult<()> {
+        self.wait_for(timeout_us, || {
+            Ok(self.is_domain_idle(&domain)? == expected_idle)
+        })
+    }
+
+    /// Poll `done` for up to `timeout_us` microseconds
+    fn wait_for(
+        &self,
+        timeout_us: u64,
+        done: impl FnMut() -> PowerResult<bool>,
+    ) -> PowerResult<()> {
+        let reached = time::block_on(time::poll_until(&*self.time, timeout_us, None, done))?;
+        if !reached {
+            return Err(PowerError::Timeout);
+        }
+        Ok(())
+    }
 }
 
-impl DriverGeneric for RockchipPM {
+impl<R: RegisterAccess + Send + 'static> DriverGeneric for RockchipPM<R> {
     fn open(&mut self) -> Result<(), rdif_base::KError> {
         Ok(())
     }
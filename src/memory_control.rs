@@ -5,12 +5,14 @@
 //! - Memory power state verification
 //! - Timeout handling for memory operations
 
-use crate::{PowerError, registers::PmuRegs, variants::RockchipDomainInfo};
+use crate::{
+    PowerError,
+    registers::{RegisterAccess, write_domain_bits},
+    time::{TimeoutStep, Waiter},
+    variants::RockchipDomainInfo,
+};
 use mbarrier::mb;
 
-/// Memory power control timeout (in iterations)
-const MEMORY_POWER_TIMEOUT: u32 = 10000;
-
 /// Memory power controller
 pub struct MemoryPowerControl {
     mem_pwr_offset: u32,
@@ -35,9 +37,9 @@ impl MemoryPowerControl {
     /// # Returns
     /// * `Ok(())` if successful
     /// * `Err(PowerError)` if domain has no memory control or operation fails
-    pub fn set_memory_power(
+    pub fn set_memory_power<R: RegisterAccess>(
         &self,
-        reg: &mut PmuRegs,
+        reg: &R,
         domain_info: &RockchipDomainInfo,
         power_on: bool,
     ) -> Result<(), PowerError> {
@@ -48,24 +50,14 @@ impl MemoryPowerControl {
 
         let mem_offset = self.mem_pwr_offset + domain_info.mem_offset;
 
-        if domain_info.mem_w_mask != 0 {
-            // Use write enable mask method
-            let value = if power_on {
-                domain_info.mem_w_mask
-            } else {
-                domain_info.mem_mask | domain_info.mem_w_mask
-            };
-            reg.write_u32(mem_offset as usize, value as u32);
-        } else {
-            // Use read-modify-write method
-            let current = reg.read_u32(mem_offset as usize);
-            let new_value = if power_on {
-                current & !(domain_info.mem_mask as u32)
-            } else {
-                current | (domain_info.mem_mask as u32)
-            };
-            reg.write_u32(mem_offset as usize, new_value);
-        }
+        // Memory gate bit set means memory powered off
+        write_domain_bits(
+            reg,
+            mem_offset as usize,
+            domain_info.mem_mask,
+            domain_info.mem_w_mask,
+            !power_on,
+        )?;
 
         mb();
 
@@ -76,6 +68,7 @@ impl MemoryPowerControl {
     ///
     /// # Arguments
     /// * `reg` - PMU register accessor
+    /// * `waiter` - Clock and timeouts of the domain transition
     /// * `domain_info` - Domain information containing status masks
     /// * `expected_on` - Expected power state (true = on, false = off)
     /// * `repair_status_offset` - Offset for repair status register
@@ -83,9 +76,10 @@ impl MemoryPowerControl {
     /// # Returns
     /// * `Ok(())` if state matches expectation within timeout
     /// * `Err(PowerError::MemoryPowerTimeout)` if timeout occurs
-    pub fn wait_memory_stable(
+    pub async fn wait_memory_stable<R: RegisterAccess>(
         &self,
-        reg: &PmuRegs,
+        reg: &R,
+        waiter: &Waiter<'_>,
         domain_info: &RockchipDomainInfo,
         expected_on: bool,
         repair_status_offset: u32,
@@ -95,15 +89,69 @@ impl MemoryPowerControl {
             return Ok(());
         }
 
-        for _ in 0..MEMORY_POWER_TIMEOUT {
-            let val = reg.read_u32(repair_status_offset as usize);
-            let is_on = (val & (domain_info.repair_status_mask as u32)) != 0;
+        let stable = waiter
+            .poll(TimeoutStep::MemoryStable, || {
+                let val = reg.read_u32(repair_status_offset as usize)?;
+                let is_on = (val & (domain_info.repair_status_mask as u32)) != 0;
+                Ok(is_on == expected_on)
+            })
+            .await?;
 
-            if is_on == expected_on {
-                return Ok(());
-            }
+        if !stable {
+            return Err(PowerError::MemoryPowerTimeout);
         }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        RK3588,
+        sim::{Fault, FaultInjector, Rk3588Pmu},
+        time::{IdleRetryPolicy, PollCounter, TimeoutPolicy, block_on},
+        variants::rk3588,
+    };
+
+    #[test]
+    fn test_memory_power_timeout() {
+        let info = rk3588::pmu_info();
+        let mut domain = info.domains[&RK3588::NPUTOP].clone();
+        domain.mem_mask = domain.mem_status_mask;
+        domain.mem_w_mask = domain.mem_mask << 16;
+
+        // Repair status keeps reporting the memory as powered
+        let regs = FaultInjector::new(Rk3588Pmu::new());
+        regs.inject(Fault::Stuck {
+            offset: info.repair_status_offset as usize,
+            mask: domain.repair_status_mask as u32,
+            value: u32::MAX,
+        });
+
+        let control = MemoryPowerControl::new(info.mem_pwr_offset);
+        control.set_memory_power(&regs, &domain, false).unwrap();
+        assert_eq!(
+            block_on(control.wait_memory_stable(
+                &regs,
+                &Waiter {
+                    time: &PollCounter::new(),
+                    policy: &TimeoutPolicy::default(),
+                    retry: &IdleRetryPolicy::default(),
+                    irq: None,
+                    domain: RK3588::NPUTOP,
+                },
+                &domain,
+                false,
+                info.repair_status_offset
+            )),
+            Err(PowerError::MemoryPowerTimeout)
+        );
 
-        Err(PowerError::MemoryPowerTimeout)
+        // The memory power-down request stays latched
+        let mem = regs
+            .inner()
+            .peek((info.mem_pwr_offset + domain.mem_offset) as usize);
+        assert_eq!(mem & domain.mem_mask as u32, domain.mem_mask as u32);
     }
 }
@@ -4,41 +4,137 @@
 //! coordinating memory power, bus idle requests, and main power control.
 
 use crate::{
-    PowerDomain, PowerError, idle_control::BusIdleControl, memory_control::MemoryPowerControl,
-    qos_control::QoSControl, registers::PmuRegs, variants::RockchipPmuInfo,
+    PowerDomain, PowerError,
+    idle_control::BusIdleControl,
+    irq::IrqNotifier,
+    memory_control::MemoryPowerControl,
+    qos_control::{self, QoSControl, QoSStates, QosMapper},
+    registers::{RegisterAccess, write_domain_bits},
+    stats::LatencyStats,
+    time::{IdleRetryPolicy, TimeSource, TimeoutPolicy, TimeoutStep, Waiter, block_on},
+    variants::RockchipPmuInfo,
+};
+use alloc::{boxed::Box, rc::Rc};
+use core::{
+    cell::{Cell, RefCell},
+    pin::Pin,
+    task::{Context, Poll, Waker},
 };
-use alloc::vec::Vec;
-use core::ptr::NonNull;
 use mbarrier::mb;
 
-/// Repair operation timeout (in iterations)
-const REPAIR_TIMEOUT: u32 = 10000;
-/// Power state stabilization timeout (in iterations)
-const POWER_STABLE_TIMEOUT: u32 = 10000;
+/// Individual steps of a power domain transition
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum PowerStep {
+    /// Ungate the domain clocks for the transition, and gate them again after
+    ClockUngate,
+    /// Save the QoS registers before power off
+    QosSave,
+    /// Switch domain memory power and wait for it to settle
+    MemoryPower,
+    /// Request or cancel bus idle and wait for the handshake
+    IdleRequest,
+    /// Write the power gate control register
+    PowerWrite,
+    /// Wait for memory repair to complete after power on
+    RepairWait,
+    /// Wait for the power status to match the requested state
+    PowerStable,
+    /// Restore the QoS registers after power on
+    QosRestore,
+}
+
+/// Idle retry settings of a sequencer without a retry policy
+static NO_IDLE_RETRY: IdleRetryPolicy = IdleRetryPolicy::new(crate::time::IdleRetry::NONE);
 
 /// Power sequencer that coordinates complete power domain transitions
-pub struct PowerSequencer<'a> {
-    reg: &'a mut PmuRegs,
+pub struct PowerSequencer<'a, R: RegisterAccess> {
+    reg: &'a R,
     info: &'a RockchipPmuInfo,
+    time: &'a dyn TimeSource,
+    timeouts: &'a TimeoutPolicy,
+    idle_retry: &'a IdleRetryPolicy,
+    irq: Option<&'a IrqNotifier>,
+    /// Saved QoS state per domain and the mapping of the QoS ports; QoS
+    /// save/restore is skipped without them
+    qos: Option<(&'a QoSStates, &'a dyn QosMapper)>,
+    /// Set once the missing QoS mapping has been reported
+    qos_unmapped_warned: Option<&'a Cell<bool>>,
     memory_control: MemoryPowerControl,
     idle_control: BusIdleControl,
+    /// Step in progress, shared with a [`PowerTransition`] driving the sequence
+    step: Rc<Cell<Option<PowerStep>>>,
+    /// Latency samples, when instrumentation is enabled
+    stats: Option<&'a RefCell<LatencyStats>>,
+    /// Start time of the transition and of the step in progress
+    started_us: Cell<(u64, u64)>,
 }
 
-impl<'a> PowerSequencer<'a> {
+impl<'a, R: RegisterAccess> PowerSequencer<'a, R> {
     /// Create a new power sequencer
     ///
     /// # Arguments
     /// * `reg` - PMU register accessor
     /// * `info` - Chip-specific PMU information
-    pub fn new(reg: &'a mut PmuRegs, info: &'a RockchipPmuInfo) -> Self {
+    /// * `time` - Clock for the poll loop timeouts
+    /// * `timeouts` - Poll loop budgets
+    pub fn new(
+        reg: &'a R,
+        info: &'a RockchipPmuInfo,
+        time: &'a dyn TimeSource,
+        timeouts: &'a TimeoutPolicy,
+    ) -> Self {
         Self {
             memory_control: MemoryPowerControl::new(info.mem_pwr_offset),
-            idle_control: BusIdleControl::new(info.idle_offset),
+            idle_control: BusIdleControl::new(info),
             reg,
             info,
+            time,
+            timeouts,
+            idle_retry: &NO_IDLE_RETRY,
+            irq: None,
+            qos: None,
+            qos_unmapped_warned: None,
+            step: Rc::new(Cell::new(None)),
+            stats: None,
+            started_us: Cell::new((0, 0)),
         }
     }
 
+    /// Retry timed out bus idle requests according to `idle_retry`
+    pub fn with_idle_retry(mut self, idle_retry: &'a IdleRetryPolicy) -> Self {
+        self.idle_retry = idle_retry;
+        self
+    }
+
+    /// Keep the QoS state saved before power off in `qos_states`, and restore
+    /// it after power on
+    ///
+    /// # Arguments
+    /// * `qos_states` - Saved QoS state per domain
+    /// * `mapper` - Mapping of the QoS port physical addresses
+    pub(crate) fn with_qos(mut self, qos_states: &'a QoSStates, mapper: &'a dyn QosMapper) -> Self {
+        self.qos = Some((qos_states, mapper));
+        self
+    }
+
+    /// Report skipped QoS saves without a mapping, once per `warned` flag
+    pub(crate) fn with_qos_unmapped(mut self, warned: &'a Cell<bool>) -> Self {
+        self.qos_unmapped_warned = Some(warned);
+        self
+    }
+
+    /// Suspend waits until `irq` is notified instead of re-polling right away
+    pub(crate) fn with_irq(mut self, irq: Option<&'a IrqNotifier>) -> Self {
+        self.irq = irq;
+        self
+    }
+
+    /// Record the latency of every step into `stats`
+    pub fn with_stats(mut self, stats: Option<&'a RefCell<LatencyStats>>) -> Self {
+        self.stats = stats;
+        self
+    }
+
     /// Execute complete power-on sequence for a domain
     ///
     /// Sequence:
@@ -47,6 +143,12 @@ impl<'a> PowerSequencer<'a> {
     /// 3. Power on main domain
     /// 4. Wait for repair completion (if domain has repair control)
     /// 5. Verify power state
+    /// 6. Restore QoS (if saved by an earlier power off)
+    ///
+    /// Nothing is done if the domain is already on.
+    ///
+    /// Domains with clock ungate control have their clocks ungated for the
+    /// whole sequence.
     ///
     /// # Arguments
     /// * `domain` - Power domain to enable
@@ -55,70 +157,101 @@ impl<'a> PowerSequencer<'a> {
     /// * `Ok(())` if successful
     /// * `Err(PowerError)` if any step fails
     pub fn power_on_sequence(&mut self, domain: PowerDomain) -> Result<(), PowerError> {
+        // Blocking waits poll continuously, nothing needs to wake them
+        self.irq = None;
+        block_on(self.power_on_sequence_async(domain))
+    }
+
+    /// Execute the power-on sequence, yielding to the executor between polls
+    ///
+    /// Same sequence as [`power_on_sequence`](Self::power_on_sequence).
+    pub async fn power_on_sequence_async(&mut self, domain: PowerDomain) -> Result<(), PowerError> {
+        // Restoring QoS again would overwrite settings tuned since power on
+        if self.is_in_state(domain, true)? {
+            return Ok(());
+        }
+
+        self.start_timing();
+        let result = self.run_power_on(domain).await;
+        self.finish_timing(domain, true, result.is_ok());
+        self.step.set(None);
+        self.reg.sequencer_step(None);
+        result
+    }
+
+    async fn run_power_on(&mut self, domain: PowerDomain) -> Result<(), PowerError> {
         let domain_info = self
             .info
             .domains
             .get(&domain)
             .ok_or(PowerError::DomainNotFound)?;
 
+        let waiter = self.waiter(domain);
+        self.ungate_clock(domain, domain_info, true)?;
+
         // Step 1: Power on memory if domain has memory control
         if domain_info.mem_mask != 0 {
+            self.enter_step(domain, PowerStep::MemoryPower);
             self.memory_control
                 .set_memory_power(self.reg, domain_info, true)?;
-            self.memory_control.wait_memory_stable(
-                self.reg,
-                domain_info,
-                true,
-                self.info.repair_status_offset,
-            )?;
+            self.memory_control
+                .wait_memory_stable(
+                    self.reg,
+                    &waiter,
+                    domain_info,
+                    true,
+                    self.info.repair_status_offset,
+                )
+                .await?;
         }
 
         // Step 2: Cancel bus idle request if domain has idle control
         if domain_info.req_mask != 0 {
+            self.enter_step(domain, PowerStep::IdleRequest);
             self.idle_control
-                .request_idle(self.reg, domain_info, false)?;
+                .request_idle(self.reg, &waiter, domain_info, false)
+                .await?;
         }
 
         // Step 3: Power on main domain
+        self.enter_step(domain, PowerStep::PowerWrite);
         self.write_power_control(domain_info, true)?;
 
         // Step 4: Wait for repair completion if domain has repair control
         if domain_info.repair_mask != 0 {
-            self.wait_repair_done(domain_info)?;
+            self.enter_step(domain, PowerStep::RepairWait);
+            self.wait_repair_done(&waiter, domain_info).await?;
         }
 
         // Step 5: Verify power state
-        self.wait_power_stable(domain_info, true)?;
-
-        // Step 6: Restore QoS if configured
-        if domain_info.num_qos > 0 && !domain_info.qos_offsets.is_empty() {
-            let qos_bases: Vec<NonNull<u8>> = domain_info
-                .qos_offsets
-                .iter()
-                .map(|&offset| unsafe { NonNull::new_unchecked(offset as *mut u8) })
-                .collect();
-
-            if let Some(qos_ctrl) = QoSControl::new(qos_bases) {
-                // Note: In a real implementation, we would need to have saved the QoS state
-                // before power off. For now, this demonstrates the integration point.
-                // A more complete implementation would store QoSControl in RockchipPM
-                // or PowerSequencer to maintain state across power cycles.
-                qos_ctrl.restore().ok(); // Ignore error if no saved state
-            }
+        self.enter_step(domain, PowerStep::PowerStable);
+        self.wait_power_stable(&waiter, domain_info, true).await?;
+
+        // Step 6: Restore QoS saved by the last power off, if any
+        if let Some((qos_states, _)) = self.qos
+            && let Some(qos_ctrl) = qos_states.borrow().get(&domain)
+        {
+            self.enter_step(domain, PowerStep::QosRestore);
+            qos_ctrl.restore()?;
         }
 
-        Ok(())
+        self.ungate_clock(domain, domain_info, false)
     }
 
     /// Execute complete power-off sequence for a domain
     ///
     /// Sequence:
-    /// 0. Save QoS (if domain has QoS control)
+    /// 0. Save QoS (if domain has QoS control), kept until the next power on
     /// 1. Request bus idle (if domain has idle control)
     /// 2. Power off main domain
     /// 3. Verify power state
     /// 4. Power off memory (if domain has memory)
     ///
+    /// Nothing is done if the domain is already off.
+    ///
+    /// Domains with clock ungate control have their clocks ungated for the
+    /// whole sequence.
+    ///
     /// # Arguments
     /// * `domain` - Power domain to disable
     ///
@@ -126,54 +259,194 @@ impl<'a> PowerSequencer<'a> {
     /// * `Ok(())` if successful
     /// * `Err(PowerError)` if any step fails
     pub fn power_off_sequence(&mut self, domain: PowerDomain) -> Result<(), PowerError> {
+        // Blocking waits poll continuously, nothing needs to wake them
+        self.irq = None;
+        block_on(self.power_off_sequence_async(domain))
+    }
+
+    /// Execute the power-off sequence, yielding to the executor between polls
+    ///
+    /// Same sequence as [`power_off_sequence`](Self::power_off_sequence).
+    pub async fn power_off_sequence_async(
+        &mut self,
+        domain: PowerDomain,
+    ) -> Result<(), PowerError> {
+        // The QoS registers of an unpowered domain must not be read, and the
+        // state saved at the real power off must be kept
+        if self.is_in_state(domain, false)? {
+            return Ok(());
+        }
+
+        self.start_timing();
+        let result = self.run_power_off(domain).await;
+        self.finish_timing(domain, false, result.is_ok());
+        self.step.set(None);
+        self.reg.sequencer_step(None);
+        result
+    }
+
+    async fn run_power_off(&mut self, domain: PowerDomain) -> Result<(), PowerError> {
         let domain_info = self
             .info
             .domains
             .get(&domain)
             .ok_or(PowerError::DomainNotFound)?;
 
+        let waiter = self.waiter(domain);
+        self.ungate_clock(domain, domain_info, true)?;
+
         // Step 0: Save QoS if configured
-        if domain_info.num_qos > 0 && !domain_info.qos_offsets.is_empty() {
-            let qos_bases: Vec<NonNull<u8>> = domain_info
-                .qos_offsets
-                .iter()
-                .map(|&offset| unsafe { NonNull::new_unchecked(offset as *mut u8) })
-                .collect();
-
-            if let Some(mut qos_ctrl) = QoSControl::new(qos_bases) {
-                qos_ctrl.save()?;
-                // Note: In a real implementation, we would need to store this QoSControl
-                // instance somewhere (e.g., in RockchipPM or a global state) to be able
-                // to restore it later during power_on_sequence. This demonstrates the
-                // integration point, but a complete implementation needs state persistence.
+        if !domain_info.qos_ports.is_empty() {
+            match self.qos {
+                Some((qos_states, mapper)) => {
+                    self.enter_step(domain, PowerStep::QosSave);
+                    let qos_bases = qos_control::map_ports(domain_info.qos_ports, mapper)?;
+
+                    if let Some(mut qos_ctrl) = QoSControl::new(qos_bases) {
+                        qos_ctrl.save()?;
+                        qos_states.borrow_mut().insert(domain, qos_ctrl);
+                    }
+                }
+                None => {
+                    if let Some(warned) = self.qos_unmapped_warned
+                        && !warned.replace(true)
+                    {
+                        log::warn!(
+                            "{}: QoS of {} port(s) not saved at power off, no QoS mapper configured",
+                            domain_info.name,
+                            domain_info.qos_ports.len()
+                        );
+                    }
+                }
             }
         }
 
         // Step 1: Request bus idle if domain has idle control
         if domain_info.req_mask != 0 {
+            self.enter_step(domain, PowerStep::IdleRequest);
             self.idle_control
-                .request_idle(self.reg, domain_info, true)?;
+                .request_idle(self.reg, &waiter, domain_info, true)
+                .await?;
         }
 
         // Step 2: Power off main domain
+        self.enter_step(domain, PowerStep::PowerWrite);
         self.write_power_control(domain_info, false)?;
 
         // Step 3: Verify power state
-        self.wait_power_stable(domain_info, false)?;
+        self.enter_step(domain, PowerStep::PowerStable);
+        self.wait_power_stable(&waiter, domain_info, false).await?;
 
         // Step 4: Power off memory if domain has memory control
         if domain_info.mem_mask != 0 {
+            self.enter_step(domain, PowerStep::MemoryPower);
             self.memory_control
                 .set_memory_power(self.reg, domain_info, false)?;
-            self.memory_control.wait_memory_stable(
-                self.reg,
-                domain_info,
-                false,
-                self.info.repair_status_offset,
-            )?;
+            self.memory_control
+                .wait_memory_stable(
+                    self.reg,
+                    &waiter,
+                    domain_info,
+                    false,
+                    self.info.repair_status_offset,
+                )
+                .await?;
         }
 
-        Ok(())
+        self.ungate_clock(domain, domain_info, false)
+    }
+
+    /// Check if `domain` is already powered on or off as requested
+    ///
+    /// Like Linux `rockchip_do_pmu_set_power_domain`, a transition to the
+    /// current state does nothing.
+    fn is_in_state(&self, domain: PowerDomain, power_on: bool) -> Result<bool, PowerError> {
+        let domain_info = self
+            .info
+            .domains
+            .get(&domain)
+            .ok_or(PowerError::DomainNotFound)?;
+        Ok(self.check_domain_on(domain_info)? == power_on)
+    }
+
+    /// Get the clock and timeouts for the waits of `domain`
+    fn waiter(&self, domain: PowerDomain) -> Waiter<'a> {
+        Waiter {
+            time: self.time,
+            policy: self.timeouts,
+            retry: self.idle_retry,
+            irq: self.irq,
+            domain,
+        }
+    }
+
+    /// Tell the register backend which step the following accesses belong to
+    fn enter_step(&self, domain: PowerDomain, step: PowerStep) {
+        self.finish_step(domain);
+        self.step.set(Some(step));
+        self.reg.sequencer_step(Some((domain, step)));
+    }
+
+    /// Timestamp the start of a transition
+    fn start_timing(&self) {
+        if self.stats.is_some() {
+            let now = self.time.now_us();
+            self.started_us.set((now, now));
+        }
+    }
+
+    /// Record the duration of the step in progress, if any
+    fn finish_step(&self, domain: PowerDomain) {
+        let (Some(stats), Some(step)) = (self.stats, self.step.get()) else {
+            return;
+        };
+        let now = self.time.now_us();
+        let (transition, started) = self.started_us.get();
+        stats
+            .borrow_mut()
+            .record_step(domain, step, now.wrapping_sub(started));
+        self.started_us.set((transition, now));
+    }
+
+    /// Record the last step and, if it succeeded, the whole transition
+    fn finish_timing(&self, domain: PowerDomain, power_on: bool, succeeded: bool) {
+        self.finish_step(domain);
+        if let Some(stats) = self.stats
+            && succeeded
+        {
+            let (transition, finished) = self.started_us.get();
+            stats.borrow_mut().record_transition(
+                domain,
+                power_on,
+                finished.wrapping_sub(transition),
+            );
+        }
+    }
+
+    /// Ungate or gate the domain clocks around a power transition
+    ///
+    /// # Arguments
+    /// * `domain` - Power domain in transition
+    /// * `domain_info` - Domain information
+    /// * `ungate` - True to ungate the clocks, false to gate them again
+    fn ungate_clock(
+        &self,
+        domain: PowerDomain,
+        domain_info: &crate::variants::RockchipDomainInfo,
+        ungate: bool,
+    ) -> Result<(), PowerError> {
+        if domain_info.clk_ungate_mask == 0 {
+            return Ok(());
+        }
+
+        self.enter_step(domain, PowerStep::ClockUngate);
+        write_domain_bits(
+            self.reg,
+            self.info.clk_ungate_offset as usize,
+            domain_info.clk_ungate_mask,
+            domain_info.clk_ungate_w_mask,
+            ungate,
+        )
     }
 
     /// Write power control register
@@ -192,24 +465,14 @@ impl<'a> PowerSequencer<'a> {
 
         let pwr_offset = self.info.pwr_offset + domain_info.pwr_offset;
 
-        if domain_info.pwr_w_mask != 0 {
-            // Use write enable mask method
-            let value = if power_on {
-                domain_info.pwr_w_mask
-            } else {
-                domain_info.pwr_mask | domain_info.pwr_w_mask
-            };
-            self.reg.write_u32(pwr_offset as usize, value as u32);
-        } else {
-            // Use read-modify-write method
-            let current = self.reg.read_u32(pwr_offset as usize);
-            let new_value = if power_on {
-                current & !(domain_info.pwr_mask as u32)
-            } else {
-                current | (domain_info.pwr_mask as u32)
-            };
-            self.reg.write_u32(pwr_offset as usize, new_value);
-        }
+        // Power gate bit set means domain powered off
+        write_domain_bits(
+            self.reg,
+            pwr_offset as usize,
+            domain_info.pwr_mask,
+            domain_info.pwr_w_mask,
+            !power_on,
+        )?;
 
         mb();
 
@@ -219,20 +482,25 @@ impl<'a> PowerSequencer<'a> {
     /// Wait for power state to stabilize
     ///
     /// # Arguments
+    /// * `waiter` - Clock and timeouts of the domain transition
     /// * `domain_info` - Domain information
     /// * `expected_on` - Expected power state
-    fn wait_power_stable(
+    async fn wait_power_stable(
         &self,
+        waiter: &Waiter<'_>,
         domain_info: &crate::variants::RockchipDomainInfo,
         expected_on: bool,
     ) -> Result<(), PowerError> {
-        for _ in 0..POWER_STABLE_TIMEOUT {
-            let is_on = self.check_domain_on(domain_info)?;
-            if is_on == expected_on {
-                return Ok(());
-            }
+        let stable = waiter
+            .poll(TimeoutStep::PowerStable, || {
+                Ok(self.check_domain_on(domain_info)? == expected_on)
+            })
+            .await?;
+
+        if !stable {
+            return Err(PowerError::Timeout);
         }
-        Err(PowerError::Timeout)
+        Ok(())
     }
 
     /// Check if domain is powered on
@@ -245,19 +513,19 @@ impl<'a> PowerSequencer<'a> {
     ) -> Result<bool, PowerError> {
         if domain_info.repair_status_mask != 0 {
             // Use repair status register
-            let val = self.reg.read_u32(self.info.repair_status_offset as usize);
+            let val = self.reg.read_u32(self.info.repair_status_offset as usize)?;
             // 1'b1: power on, 1'b0: power off
             return Ok((val & (domain_info.repair_status_mask as u32)) != 0);
         }
 
         if domain_info.status_mask == 0 {
             // Check idle status only for domains without status mask
-            let val = self.reg.read_u32(self.info.idle_offset as usize);
+            let val = self.reg.read_u32(self.info.idle_offset as usize)?;
             let is_idle = (val & (domain_info.idle_mask as u32)) == (domain_info.idle_mask as u32);
             return Ok(!is_idle);
         }
 
-        let val = self.reg.read_u32(self.info.status_offset as usize);
+        let val = self.reg.read_u32(self.info.status_offset as usize)?;
         // 1'b0: power on, 1'b1: power off
         Ok((val & (domain_info.status_mask as u32)) == 0)
     }
@@ -265,9 +533,11 @@ impl<'a> PowerSequencer<'a> {
     /// Wait for repair operation to complete
     ///
     /// # Arguments
+    /// * `waiter` - Clock and timeouts of the domain transition
     /// * `domain_info` - Domain information
-    fn wait_repair_done(
+    async fn wait_repair_done(
         &self,
+        waiter: &Waiter<'_>,
         domain_info: &crate::variants::RockchipDomainInfo,
     ) -> Result<(), PowerError> {
         if domain_info.repair_mask == 0 {
@@ -276,14 +546,201 @@ impl<'a> PowerSequencer<'a> {
 
         let repair_offset = self.info.repair_status_offset + domain_info.repair_offset;
 
-        for _ in 0..REPAIR_TIMEOUT {
-            let val = self.reg.read_u32(repair_offset as usize);
-            // Check if repair is done (bit should be 1)
-            if (val & (domain_info.repair_mask as u32)) != 0 {
-                return Ok(());
+        let repaired = waiter
+            .poll(TimeoutStep::Repair, || {
+                let val = self.reg.read_u32(repair_offset as usize)?;
+                // Check if repair is done (bit should be 1)
+                Ok((val & (domain_info.repair_mask as u32)) != 0)
+            })
+            .await?;
+
+        if !repaired {
+            return Err(PowerError::RepairTimeout);
+        }
+        Ok(())
+    }
+}
+
+/// Power sequence future with the register backend type erased
+type Sequence<'a> = Pin<Box<dyn Future<Output = Result<(), PowerError>> + 'a>>;
+
+/// A power domain transition driven one poll at a time
+///
+/// Created by [`RockchipPM::start_power_on`](crate::RockchipPM::start_power_on)
+/// or [`RockchipPM::start_power_off`](crate::RockchipPM::start_power_off).
+/// Nothing is written until the first [`poll`](Self::poll). Every call then
+/// runs the sequence until a status register does not yet show the expected
+/// value, and returns `Poll::Pending` with that wait recorded in
+/// [`pending_step`](Self::pending_step). This lets a scheduler tick, timer or
+/// interrupt handler interleave several transitions without threads or an
+/// async executor.
+///
+/// Timeouts are measured from the first check of each wait with the driver's
+/// [`TimeSource`], so they keep running between calls. Dropping an unfinished
+/// transition leaves the domain wherever the sequence stopped.
+pub struct PowerTransition<'a> {
+    domain: PowerDomain,
+    power_on: bool,
+    step: Rc<Cell<Option<PowerStep>>>,
+    sequence: Option<Sequence<'a>>,
+    result: Option<Result<(), PowerError>>,
+}
+
+impl<'a> PowerTransition<'a> {
+    /// Wrap the power-on or power-off sequence of `sequencer` for `domain`
+    pub(crate) fn new<R: RegisterAccess>(
+        mut sequencer: PowerSequencer<'a, R>,
+        domain: PowerDomain,
+        power_on: bool,
+    ) -> Self {
+        // The caller polls again after an interrupt, no waker is involved
+        sequencer.irq = None;
+        let step = sequencer.step.clone();
+        let sequence = Box::pin(async move {
+            if power_on {
+                sequencer.power_on_sequence_async(domain).await
+            } else {
+                sequencer.power_off_sequence_async(domain).await
             }
+        });
+        Self {
+            domain,
+            power_on,
+            step,
+            sequence: Some(sequence),
+            result: None,
+        }
+    }
+
+    /// Get the domain in transition
+    pub fn domain(&self) -> PowerDomain {
+        self.domain
+    }
+
+    /// Check whether this is a power-on transition
+    pub fn is_power_on(&self) -> bool {
+        self.power_on
+    }
+
+    /// Advance the transition as far as the hardware allows
+    ///
+    /// # Returns
+    /// * `Poll::Pending` while a wait is not yet satisfied
+    /// * `Poll::Ready(result)` once the sequence has finished; further calls
+    ///   return the same result without touching the hardware
+    pub fn poll(&mut self) -> Poll<Result<(), PowerError>> {
+        if let Some(result) = self.result {
+            return Poll::Ready(result);
         }
+        let Some(sequence) = self.sequence.as_mut() else {
+            return Poll::Pending;
+        };
+
+        let mut cx = Context::from_waker(Waker::noop());
+        let result = core::task::ready!(sequence.as_mut().poll(&mut cx));
+        self.sequence = None;
+        self.result = Some(result);
+        Poll::Ready(result)
+    }
+
+    /// Get the step the transition is waiting in
+    ///
+    /// `None` before the first [`poll`](Self::poll) and once the transition
+    /// has finished.
+    pub fn pending_step(&self) -> Option<PowerStep> {
+        self.step.get()
+    }
+
+    /// Check whether the transition has finished, successfully or not
+    pub fn is_done(&self) -> bool {
+        self.result.is_some()
+    }
+
+    /// Get the result of a finished transition
+    pub fn result(&self) -> Option<Result<(), PowerError>> {
+        self.result
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{
+        RK3588,
+        sim::{Access, Fault, FaultInjector, Rk3588Pmu, TraceRecorder},
+        time::PollCounter,
+        variants::rk3588,
+    };
+
+    #[test]
+    fn test_repair_timeout() {
+        let info = rk3588::pmu_info();
+        let mut domain = info.domains[&RK3588::NPUTOP].clone();
+        domain.repair_mask = domain.repair_status_mask;
+
+        // Repair never reports completion
+        let regs = FaultInjector::new(Rk3588Pmu::new());
+        regs.inject(Fault::Stuck {
+            offset: info.repair_status_offset as usize,
+            mask: domain.repair_mask as u32,
+            value: 0,
+        });
+
+        let time = PollCounter::new();
+        let timeouts = TimeoutPolicy::default();
+        let sequencer = PowerSequencer::new(&regs, &info, &time, &timeouts);
+        assert_eq!(
+            block_on(sequencer.wait_repair_done(&sequencer.waiter(RK3588::NPUTOP), &domain)),
+            Err(PowerError::RepairTimeout)
+        );
+    }
+
+    #[test]
+    fn test_clock_ungate_around_sequence() {
+        let mut info = rk3588::pmu_info();
+        info.clk_ungate_offset = 0x140;
+        let domain = info.domains.get_mut(&RK3588::AV1).unwrap();
+        domain.clk_ungate_mask = 1 << 12;
+        domain.clk_ungate_w_mask = 1 << 28;
+
+        let regs = TraceRecorder::new(Rk3588Pmu::new());
+        PowerSequencer::new(&regs, &info, &PollCounter::new(), &TimeoutPolicy::default())
+            .power_off_sequence(RK3588::AV1)
+            .unwrap();
+
+        let trace = regs.take_trace();
+        let events = trace.events();
+        let ungate =
+            |e: &crate::sim::TraceEvent| e.step == Some((RK3588::AV1, PowerStep::ClockUngate));
+        // The state check comes first and is no part of the sequence
+        assert_eq!((events[0].access, events[0].step), (Access::Read, None));
+        let first = &events[1];
+        let last = events.last().unwrap();
+        assert!(ungate(first) && ungate(last));
+        assert_eq!(
+            (first.access, first.offset, first.value),
+            (Access::Write, 0x140, 0x1000_1000)
+        );
+        assert_eq!(
+            (last.access, last.offset, last.value),
+            (Access::Write, 0x140, 0x1000_0000)
+        );
+    }
 
-        Err(PowerError::RepairTimeout)
+    #[test]
+    fn test_inconsistent_write_mask() {
+        let info = rk3588::pmu_info();
+        let mut domain = info.domains[&RK3588::AV1].clone();
+        domain.pwr_w_mask = domain.pwr_mask << 15;
+
+        let regs = Rk3588Pmu::new();
+        let time = PollCounter::new();
+        let timeouts = TimeoutPolicy::default();
+        let mut sequencer = PowerSequencer::new(&regs, &info, &time, &timeouts);
+        assert_eq!(
+            sequencer.write_power_control(&domain, false),
+            Err(PowerError::InvalidWriteMask)
+        );
+        assert!(regs.is_domain_powered(RK3588::AV1));
     }
 }
@@ -5,9 +5,9 @@
 //! and other performance parameters that need to be preserved across
 //! power domain transitions.
 
-use crate::{PowerError, PowerResult};
-use alloc::vec::Vec;
-use core::ptr::NonNull;
+use crate::{PowerDomain, PowerError, PowerResult, QosPort};
+use alloc::{collections::BTreeMap, vec::Vec};
+use core::{cell::RefCell, ptr::NonNull};
 
 /// QoS register offsets
 const QOS_PRIORITY: usize = 0x08;
@@ -17,10 +17,351 @@ const QOS_SATURATION: usize = 0x14;
 const QOS_EXTCONTROL: usize = 0x18;
 
 /// Number of QoS registers to save/restore
-const MAX_QOS_REGS: usize = 5;
+pub(crate) const MAX_QOS_REGS: usize = 5;
 
-/// Maximum number of QoS ports per domain
-const MAX_QOS_PORTS: usize = 8;
+/// Registers saved for every port, in save order
+const QOS_REGS: [usize; MAX_QOS_REGS] = [
+    QOS_PRIORITY,
+    QOS_MODE,
+    QOS_BANDWIDTH,
+    QOS_SATURATION,
+    QOS_EXTCONTROL,
+];
+
+/// Size of the register block of one QoS port
+pub const QOS_PORT_SIZE: usize = QOS_EXTCONTROL + 4;
+
+/// Priority levels: P0 (write) in bits [1:0], P1 (read) in bits [9:8]
+const PRIORITY_MASK: u32 = 0x3;
+const PRIORITY_READ_SHIFT: u32 = 8;
+/// Regulation mode in bits [1:0]
+const MODE_MASK: u32 = 0x3;
+/// Bandwidth limit in bits [10:0]
+const BANDWIDTH_MASK: u32 = 0x7ff;
+/// Saturation in bits [9:0]
+const SATURATION_MASK: u32 = 0x3ff;
+
+/// Bits decoded by [`QosSettings`], in [`QOS_REGS`] order
+///
+/// Everything else is reserved and kept as read. EXTCONTROL is passed
+/// through as a whole.
+const FIELD_MASKS: [u32; MAX_QOS_REGS] = [
+    (PRIORITY_MASK << PRIORITY_READ_SHIFT) | PRIORITY_MASK,
+    MODE_MASK,
+    BANDWIDTH_MASK,
+    SATURATION_MASK,
+    u32::MAX,
+];
+
+/// Traffic regulation mode of a QoS port
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum QosMode {
+    /// Transactions keep the programmed priority
+    #[default]
+    Fixed,
+    /// Bandwidth is capped at the programmed limit
+    Limiter,
+    /// Priority is taken from the initiator
+    Bypass,
+    /// Priority is raised while the bandwidth stays below the limit
+    Regulator,
+}
+
+impl QosMode {
+    fn from_bits(bits: u32) -> Self {
+        match bits & MODE_MASK {
+            0 => Self::Fixed,
+            1 => Self::Limiter,
+            2 => Self::Bypass,
+            _ => Self::Regulator,
+        }
+    }
+
+    fn bits(self) -> u32 {
+        match self {
+            Self::Fixed => 0,
+            Self::Limiter => 1,
+            Self::Bypass => 2,
+            Self::Regulator => 3,
+        }
+    }
+}
+
+/// Decoded register values of one QoS port
+///
+/// Bandwidth and saturation only take effect in [`QosMode::Limiter`] and
+/// [`QosMode::Regulator`]; their units are described in the TRM.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct QosSettings {
+    /// Priority of read transactions, 0 (lowest) to 3
+    pub read_priority: u8,
+    /// Priority of write transactions, 0 (lowest) to 3
+    pub write_priority: u8,
+    /// Regulation mode
+    pub mode: QosMode,
+    /// Bandwidth limit, up to `0x7ff`
+    pub bandwidth: u16,
+    /// Number of bytes the bandwidth is averaged over, up to `0x3ff`
+    pub saturation: u16,
+    /// Raw EXTCONTROL register
+    pub extcontrol: u32,
+}
+
+impl QosSettings {
+    /// Decode the registers of a port, in [`QOS_REGS`] order
+    fn from_regs(regs: &[u32; MAX_QOS_REGS]) -> Self {
+        let [priority, mode, bandwidth, saturation, extcontrol] = *regs;
+        Self {
+            read_priority: ((priority >> PRIORITY_READ_SHIFT) & PRIORITY_MASK) as u8,
+            write_priority: (priority & PRIORITY_MASK) as u8,
+            mode: QosMode::from_bits(mode),
+            bandwidth: (bandwidth & BANDWIDTH_MASK) as u16,
+            saturation: (saturation & SATURATION_MASK) as u16,
+            extcontrol,
+        }
+    }
+
+    /// Check that every field fits its register
+    ///
+    /// # Returns
+    /// * `Err(PowerError::InvalidQoSConfig)` if a field is out of range
+    pub(crate) fn validate(&self) -> PowerResult<()> {
+        self.to_regs(&[0; MAX_QOS_REGS]).map(drop)
+    }
+
+    /// Encode the registers of a port, in [`QOS_REGS`] order
+    ///
+    /// Only the decoded fields are replaced; all other bits are taken from
+    /// `current`.
+    ///
+    /// # Arguments
+    /// * `current` - Register values the settings are merged into
+    ///
+    /// # Returns
+    /// * `Err(PowerError::InvalidQoSConfig)` if a field does not fit its register
+    fn to_regs(self, current: &[u32; MAX_QOS_REGS]) -> PowerResult<[u32; MAX_QOS_REGS]> {
+        let read = u32::from(self.read_priority);
+        let write = u32::from(self.write_priority);
+        let bandwidth = u32::from(self.bandwidth);
+        let saturation = u32::from(self.saturation);
+        if read > PRIORITY_MASK
+            || write > PRIORITY_MASK
+            || bandwidth > BANDWIDTH_MASK
+            || saturation > SATURATION_MASK
+        {
+            return Err(PowerError::InvalidQoSConfig);
+        }
+
+        let fields = [
+            (read << PRIORITY_READ_SHIFT) | write,
+            self.mode.bits(),
+            bandwidth,
+            saturation,
+            self.extcontrol,
+        ];
+        Ok(core::array::from_fn(|i| {
+            (current[i] & !FIELD_MASKS[i]) | fields[i]
+        }))
+    }
+}
+
+/// Translation of QoS port physical addresses into mapped virtual addresses
+///
+/// QoS ports live outside the PMU window, in the NoC service area, and are
+/// described by physical address in the chip tables. Without a mapper the
+/// driver cannot reach them and skips QoS save and restore.
+///
+/// The driver calls the mapper at most once per port and keeps the returned
+/// address for as long as it lives, so a mapper may create a new mapping on
+/// every call.
+pub trait QosMapper {
+    /// Get a virtual address for `size` bytes of registers at `phys_addr`
+    ///
+    /// # Returns
+    /// `None` if the range is not mapped
+    fn map(&self, phys_addr: u64, size: usize) -> Option<NonNull<u8>>;
+}
+
+impl<F: Fn(u64, usize) -> Option<NonNull<u8>>> QosMapper for F {
+    fn map(&self, phys_addr: u64, size: usize) -> Option<NonNull<u8>> {
+        self(phys_addr, size)
+    }
+}
+
+/// A pre-mapped window of physical address space holding the QoS ports
+#[derive(Debug, Clone, Copy)]
+pub struct QosWindow {
+    base: NonNull<u8>,
+    phys_base: u64,
+    size: usize,
+}
+
+unsafe impl Send for QosWindow {}
+
+impl QosWindow {
+    /// Describe a mapped window
+    ///
+    /// # Arguments
+    /// * `base` - Virtual address the window is mapped at
+    /// * `phys_base` - Physical address of the start of the window
+    /// * `size` - Size of the window in bytes
+    pub fn new(base: NonNull<u8>, phys_base: u64, size: usize) -> Self {
+        Self {
+            base,
+            phys_base,
+            size,
+        }
+    }
+}
+
+impl QosMapper for QosWindow {
+    fn map(&self, phys_addr: u64, size: usize) -> Option<NonNull<u8>> {
+        let offset = usize::try_from(phys_addr.checked_sub(self.phys_base)?).ok()?;
+        if offset.checked_add(size)? > self.size {
+            return None;
+        }
+        Some(unsafe { self.base.add(offset) })
+    }
+}
+
+/// Mapper remembering the address of every port it mapped
+pub(crate) struct CachedQosMapper {
+    mapper: alloc::boxed::Box<dyn QosMapper + Send>,
+    /// Mapped ports by physical address
+    ports: RefCell<BTreeMap<u64, NonNull<u8>>>,
+}
+
+// SAFETY: the cached pointers are memory-mapped I/O addresses, like the ones
+// held by QoSControl
+unsafe impl Send for CachedQosMapper {}
+
+impl CachedQosMapper {
+    pub fn new(mapper: alloc::boxed::Box<dyn QosMapper + Send>) -> Self {
+        Self {
+            mapper,
+            ports: RefCell::new(BTreeMap::new()),
+        }
+    }
+}
+
+impl QosMapper for CachedQosMapper {
+    /// Map a port on first use; only `QOS_PORT_SIZE` blocks are ever requested
+    fn map(&self, phys_addr: u64, size: usize) -> Option<NonNull<u8>> {
+        if let Some(&base) = self.ports.borrow().get(&phys_addr) {
+            return Some(base);
+        }
+        let base = self.mapper.map(phys_addr, size)?;
+        self.ports.borrow_mut().insert(phys_addr, base);
+        Some(base)
+    }
+}
+
+/// Settings of one QoS port within a [`QosProfile`]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QosProfileEntry {
+    /// Power domain owning the port
+    pub domain: PowerDomain,
+    /// Index of the port in the domain's QoS port table
+    pub port: usize,
+    /// Settings programmed while the profile is active
+    pub settings: QosSettings,
+}
+
+/// Named set of QoS settings spanning the ports of several domains
+///
+/// A profile is applied and reverted as a unit with
+/// [`RockchipPM::apply_qos_profile`](crate::RockchipPM::apply_qos_profile)
+/// and [`RockchipPM::revert_qos_profile`](crate::RockchipPM::revert_qos_profile).
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QosProfile {
+    /// Profile name, e.g. `"display-first"`
+    pub name: &'static str,
+    /// Port settings, later entries for the same port take precedence
+    pub entries: Vec<QosProfileEntry>,
+}
+
+impl QosProfile {
+    /// Create an empty profile
+    pub fn new(name: &'static str) -> Self {
+        Self {
+            name,
+            entries: Vec::new(),
+        }
+    }
+
+    /// Add the settings of a port
+    ///
+    /// # Arguments
+    /// * `domain` - Power domain owning the port
+    /// * `port` - Index of the port in the domain's QoS port table
+    /// * `settings` - Settings programmed while the profile is active
+    pub fn with(mut self, domain: PowerDomain, port: usize, settings: QosSettings) -> Self {
+        self.entries.push(QosProfileEntry {
+            domain,
+            port,
+            settings,
+        });
+        self
+    }
+}
+
+/// The applied profile and the settings it replaced
+pub(crate) struct ActiveQosProfile {
+    pub name: &'static str,
+    /// Settings of every port touched by the profile before it was applied
+    pub baseline: BTreeMap<(PowerDomain, usize), QosSettings>,
+}
+
+/// Map the register blocks of `ports`
+///
+/// # Returns
+/// * `Err(PowerError::QoSError)` if any port can't be mapped
+pub(crate) fn map_ports(
+    ports: &[QosPort],
+    mapper: &dyn QosMapper,
+) -> PowerResult<Vec<NonNull<u8>>> {
+    ports
+        .iter()
+        .map(|port| {
+            mapper
+                .map(port.phys_addr, QOS_PORT_SIZE)
+                .ok_or(PowerError::QoSError)
+        })
+        .collect()
+}
+
+/// Read the settings of the port mapped at `base`
+pub(crate) fn read_settings(base: NonNull<u8>) -> QosSettings {
+    QosSettings::from_regs(&read_regs(base))
+}
+
+/// Program the port mapped at `base`
+///
+/// Each register is read back first so that reserved bits keep their value.
+///
+/// # Returns
+/// * `Err(PowerError::InvalidQoSConfig)` if a field is out of range, before
+///   any register is written
+pub(crate) fn write_settings(base: NonNull<u8>, settings: &QosSettings) -> PowerResult<()> {
+    write_regs(base, &settings.to_regs(&read_regs(base))?);
+    Ok(())
+}
+
+fn read_regs(base: NonNull<u8>) -> [u32; MAX_QOS_REGS] {
+    QOS_REGS
+        .map(|offset| unsafe { core::ptr::read_volatile(base.as_ptr().add(offset) as *const u32) })
+}
+
+fn write_regs(base: NonNull<u8>, regs: &[u32; MAX_QOS_REGS]) {
+    for (&value, offset) in regs.iter().zip(QOS_REGS) {
+        unsafe {
+            core::ptr::write_volatile(base.as_ptr().add(offset) as *mut u32, value);
+        }
+    }
+}
+
+/// Saved QoS state per domain, kept across power cycles
+pub(crate) type QoSStates = RefCell<BTreeMap<PowerDomain, QoSControl>>;
 
 /// QoS Control structure for managing QoS register save/restore
 ///
@@ -30,13 +371,9 @@ pub struct QoSControl {
     /// Base addresses of QoS ports for this domain
     qos_bases: Vec<NonNull<u8>>,
 
-    /// Saved QoS register values [register_index][port_index]
-    /// - Index 0: QOS_PRIORITY
-    /// - Index 1: QOS_MODE
-    /// - Index 2: QOS_BANDWIDTH
-    /// - Index 3: QOS_SATURATION
-    /// - Index 4: QOS_EXTCONTROL
-    saved_regs: [[u32; MAX_QOS_PORTS]; MAX_QOS_REGS],
+    /// Saved QoS register values [port_index][register_index], in
+    /// [`QOS_REGS`] order
+    saved_regs: Vec<[u32; MAX_QOS_REGS]>,
 
     /// Flag indicating whether QoS registers have been saved
     is_saved: bool,
@@ -59,14 +396,34 @@ impl QoSControl {
             return None;
         }
 
-        if qos_bases.len() > MAX_QOS_PORTS {
+        Some(Self {
+            saved_regs: alloc::vec![[0; MAX_QOS_REGS]; qos_bases.len()],
+            qos_bases,
+            is_saved: false,
+        })
+    }
+
+    /// Create a QoSControl holding already saved register values
+    ///
+    /// # Arguments
+    /// * `qos_bases` - Vector of base addresses for QoS ports
+    /// * `saved_regs` - Saved registers of every port, in [`QOS_REGS`] order
+    ///
+    /// # Returns
+    /// A QoSControl ready to restore, or None if no QoS ports are configured
+    /// or the number of saved ports doesn't match
+    pub fn with_saved(
+        qos_bases: Vec<NonNull<u8>>,
+        saved_regs: Vec<[u32; MAX_QOS_REGS]>,
+    ) -> Option<Self> {
+        if qos_bases.is_empty() || qos_bases.len() != saved_regs.len() {
             return None;
         }
 
         Some(Self {
             qos_bases,
-            saved_regs: [[0; MAX_QOS_PORTS]; MAX_QOS_REGS],
-            is_saved: false,
+            saved_regs,
+            is_saved: true,
         })
     }
 
@@ -79,28 +436,8 @@ impl QoSControl {
     /// * `Ok(())` if successful
     /// * `Err(PowerError::QoSError)` if save fails
     pub fn save(&mut self) -> PowerResult<()> {
-        for (port_idx, base) in self.qos_bases.iter().enumerate() {
-            // Save QOS_PRIORITY
-            self.saved_regs[0][port_idx] =
-                unsafe { core::ptr::read_volatile(base.as_ptr().add(QOS_PRIORITY) as *const u32) };
-
-            // Save QOS_MODE
-            self.saved_regs[1][port_idx] =
-                unsafe { core::ptr::read_volatile(base.as_ptr().add(QOS_MODE) as *const u32) };
-
-            // Save QOS_BANDWIDTH
-            self.saved_regs[2][port_idx] =
-                unsafe { core::ptr::read_volatile(base.as_ptr().add(QOS_BANDWIDTH) as *const u32) };
-
-            // Save QOS_SATURATION
-            self.saved_regs[3][port_idx] = unsafe {
-                core::ptr::read_volatile(base.as_ptr().add(QOS_SATURATION) as *const u32)
-            };
-
-            // Save QOS_EXTCONTROL
-            self.saved_regs[4][port_idx] = unsafe {
-                core::ptr::read_volatile(base.as_ptr().add(QOS_EXTCONTROL) as *const u32)
-            };
+        for (&base, saved) in self.qos_bases.iter().zip(&mut self.saved_regs) {
+            *saved = read_regs(base);
         }
 
         self.is_saved = true;
@@ -120,46 +457,8 @@ impl QoSControl {
             return Err(PowerError::QoSError);
         }
 
-        for (port_idx, base) in self.qos_bases.iter().enumerate() {
-            // Restore QOS_PRIORITY
-            unsafe {
-                core::ptr::write_volatile(
-                    base.as_ptr().add(QOS_PRIORITY) as *mut u32,
-                    self.saved_regs[0][port_idx],
-                );
-            }
-
-            // Restore QOS_MODE
-            unsafe {
-                core::ptr::write_volatile(
-                    base.as_ptr().add(QOS_MODE) as *mut u32,
-                    self.saved_regs[1][port_idx],
-                );
-            }
-
-            // Restore QOS_BANDWIDTH
-            unsafe {
-                core::ptr::write_volatile(
-                    base.as_ptr().add(QOS_BANDWIDTH) as *mut u32,
-                    self.saved_regs[2][port_idx],
-                );
-            }
-
-            // Restore QOS_SATURATION
-            unsafe {
-                core::ptr::write_volatile(
-                    base.as_ptr().add(QOS_SATURATION) as *mut u32,
-                    self.saved_regs[3][port_idx],
-                );
-            }
-
-            // Restore QOS_EXTCONTROL
-            unsafe {
-                core::ptr::write_volatile(
-                    base.as_ptr().add(QOS_EXTCONTROL) as *mut u32,
-                    self.saved_regs[4][port_idx],
-                );
-            }
+        for (&base, saved) in self.qos_bases.iter().zip(&self.saved_regs) {
+            write_regs(base, saved);
         }
 
         Ok(())
@@ -176,4 +475,90 @@ impl QoSControl {
     pub fn num_ports(&self) -> usize {
         self.qos_bases.len()
     }
+
+    /// Get the saved registers of every port, in [`QOS_REGS`] order
+    ///
+    /// # Returns
+    /// `None` if nothing was saved yet
+    pub fn saved_regs(&self) -> Option<&[[u32; MAX_QOS_REGS]]> {
+        self.is_saved.then_some(&self.saved_regs[..])
+    }
+
+    /// Get the saved settings of a port
+    ///
+    /// # Returns
+    /// `None` if `port` is out of range or nothing was saved yet
+    pub fn settings(&self, port: usize) -> Option<QosSettings> {
+        if !self.is_saved {
+            return None;
+        }
+        self.saved_regs.get(port).map(QosSettings::from_regs)
+    }
+
+    /// Replace the saved settings of a port, written at the next restore
+    ///
+    /// # Returns
+    /// * `Ok(())` if successful
+    /// * `Err(PowerError::InvalidQoSConfig)` if `port` or a field is out of range
+    /// * `Err(PowerError::QoSError)` if nothing was saved yet
+    pub fn set_settings(&mut self, port: usize, settings: &QosSettings) -> PowerResult<()> {
+        if !self.is_saved {
+            return Err(PowerError::QoSError);
+        }
+        let saved = self
+            .saved_regs
+            .get_mut(port)
+            .ok_or(PowerError::InvalidQoSConfig)?;
+        *saved = settings.to_regs(saved)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_qos_settings_encoding() {
+        let regs = [0x8000_0302, 0x1, 0x7ff, 0x3ff, 0x5];
+        let settings = QosSettings::from_regs(&regs);
+        assert_eq!(
+            settings,
+            QosSettings {
+                read_priority: 3,
+                write_priority: 2,
+                mode: QosMode::Limiter,
+                bandwidth: 0x7ff,
+                saturation: 0x3ff,
+                extcontrol: 0x5,
+            }
+        );
+        assert_eq!(
+            settings.to_regs(&[0; 5]),
+            Ok([0x302, 0x1, 0x7ff, 0x3ff, 0x5])
+        );
+        assert_eq!(settings.to_regs(&regs), Ok(regs));
+
+        let reserved = [0xffff_fcfc, 0xffff_fffc, 0xffff_f800, 0xffff_fc00, 0];
+        let cleared = QosSettings {
+            read_priority: 0,
+            write_priority: 0,
+            mode: QosMode::Fixed,
+            bandwidth: 0,
+            saturation: 0,
+            extcontrol: 0,
+        };
+        assert_eq!(cleared.to_regs(&[u32::MAX; 5]), Ok(reserved));
+
+        let too_high = QosSettings {
+            read_priority: 4,
+            ..settings
+        };
+        assert_eq!(too_high.to_regs(&regs), Err(PowerError::InvalidQoSConfig));
+        let too_wide = QosSettings {
+            bandwidth: 0x800,
+            ..settings
+        };
+        assert_eq!(too_wide.to_regs(&regs), Err(PowerError::InvalidQoSConfig));
+    }
 }
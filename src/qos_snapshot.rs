@@ -0,0 +1,282 @@
+//! QoS snapshot format
+//!
+//! The saved QoS state of all domains can be exported into a compact binary
+//! blob and imported again, e.g. to hand tuned settings from the bootloader
+//! to the kernel across a warm reboot or deep suspend. All fields are little
+//! endian:
+//!
+//! | Offset | Size | Field                                         |
+//! |--------|------|-----------------------------------------------|
+//! | 0      | 4    | Magic `RKQS`                                  |
+//! | 4      | 2    | Format version ([`VERSION`])                  |
+//! | 6      | 2    | Chip (`0x3588`, `0x3568`)                     |
+//! | 8      | 2    | Number of domains                             |
+//! | 10     | ...  | Per domain: id (2), number of ports (2),      |
+//! |        |      | port table fingerprint (4), then PRIORITY,    |
+//! |        |      | MODE, BANDWIDTH, SATURATION and EXTCONTROL    |
+//! |        |      | (4 each) of every port                        |
+//! | end-4  | 4    | CRC-32 (IEEE) of all preceding bytes          |
+//!
+//! The fingerprint is the CRC-32 of the physical addresses of the domain's
+//! ports (8 bytes each), so a snapshot is only accepted by a driver whose
+//! port table lists the same ports in the same order.
+
+use alloc::vec::Vec;
+
+use crate::{PowerDomain, PowerError, PowerResult, QosPort, RkBoard, qos_control::MAX_QOS_REGS};
+
+/// Start of every snapshot
+const MAGIC: [u8; 4] = *b"RKQS";
+
+/// Version of the snapshot layout
+pub(crate) const VERSION: u16 = 1;
+
+/// Saved registers of one domain's ports
+pub(crate) type DomainSnapshot = (PowerDomain, Vec<[u32; MAX_QOS_REGS]>);
+
+/// Get the chip identifier stored in snapshots
+fn chip_id(board: RkBoard) -> u16 {
+    match board {
+        RkBoard::Rk3588 => 0x3588,
+        RkBoard::Rk3568 => 0x3568,
+    }
+}
+
+/// Fingerprint of a domain's port table
+fn fingerprint(ports: &[QosPort]) -> u32 {
+    let addrs = ports
+        .iter()
+        .flat_map(|port| port.phys_addr.to_le_bytes())
+        .collect::<Vec<_>>();
+    crc32(&addrs)
+}
+
+/// Encode a count or identifier into a 16-bit field
+fn field(value: usize) -> PowerResult<[u8; 2]> {
+    u16::try_from(value)
+        .map(u16::to_le_bytes)
+        .map_err(|_| PowerError::InvalidQoSSnapshot)
+}
+
+/// Encode the saved registers of `domains`
+///
+/// # Arguments
+/// * `board` - Chip the registers were saved on
+/// * `domains` - Port table and saved registers of every domain
+///
+/// # Returns
+/// * `Err(PowerError::InvalidQoSSnapshot)` if a count or domain id doesn't
+///   fit its field
+pub(crate) fn encode<'a>(
+    board: RkBoard,
+    domains: impl ExactSizeIterator<Item = (PowerDomain, &'a [QosPort], &'a [[u32; MAX_QOS_REGS]])>,
+) -> PowerResult<Vec<u8>> {
+    let mut blob = Vec::new();
+    blob.extend_from_slice(&MAGIC);
+    blob.extend_from_slice(&VERSION.to_le_bytes());
+    blob.extend_from_slice(&chip_id(board).to_le_bytes());
+    blob.extend_from_slice(&field(domains.len())?);
+
+    for (domain, ports, regs) in domains {
+        blob.extend_from_slice(&field(domain.id())?);
+        blob.extend_from_slice(&field(regs.len())?);
+        blob.extend_from_slice(&fingerprint(ports).to_le_bytes());
+        for value in regs.iter().flatten() {
+            blob.extend_from_slice(&value.to_le_bytes());
+        }
+    }
+
+    let crc = crc32(&blob);
+    blob.extend_from_slice(&crc.to_le_bytes());
+    Ok(blob)
+}
+
+/// Decode a snapshot taken on `board`
+///
+/// # Arguments
+/// * `board` - Chip the snapshot must have been taken on
+/// * `qos_ports` - Port table of a domain, `None` for unknown domains
+///
+/// # Returns
+/// * `Err(PowerError::InvalidQoSSnapshot)` if the blob is truncated,
+///   corrupted, was written by another format version or chip, lists a
+///   domain twice, or doesn't match the port tables
+pub(crate) fn decode(
+    board: RkBoard,
+    blob: &[u8],
+    qos_ports: impl Fn(PowerDomain) -> Option<&'static [QosPort]>,
+) -> PowerResult<Vec<DomainSnapshot>> {
+    let (body, crc) = blob
+        .split_last_chunk::<4>()
+        .ok_or(PowerError::InvalidQoSSnapshot)?;
+    if crc32(body) != u32::from_le_bytes(*crc) {
+        return Err(PowerError::InvalidQoSSnapshot);
+    }
+
+    let mut reader = Reader(body);
+    if reader.take(MAGIC.len())? != MAGIC
+        || reader.u16()? != VERSION
+        || reader.u16()? != chip_id(board)
+    {
+        return Err(PowerError::InvalidQoSSnapshot);
+    }
+
+    let count = reader.u16()?;
+    let mut domains: Vec<DomainSnapshot> = Vec::with_capacity(count as usize);
+    for _ in 0..count {
+        let domain = PowerDomain::new(reader.u16()? as usize);
+        let num_ports = reader.u16()? as usize;
+        let ports = qos_ports(domain).ok_or(PowerError::InvalidQoSSnapshot)?;
+        if domains.iter().any(|&(seen, _)| seen == domain)
+            || ports.len() != num_ports
+            || reader.u32()? != fingerprint(ports)
+        {
+            return Err(PowerError::InvalidQoSSnapshot);
+        }
+
+        let regs = (0..num_ports)
+            .map(|_| {
+                let mut regs = [0; MAX_QOS_REGS];
+                for value in &mut regs {
+                    *value = reader.u32()?;
+                }
+                Ok(regs)
+            })
+            .collect::<PowerResult<_>>()?;
+        domains.push((domain, regs));
+    }
+
+    if !reader.0.is_empty() {
+        return Err(PowerError::InvalidQoSSnapshot);
+    }
+    Ok(domains)
+}
+
+/// Little-endian field reader over the snapshot body
+struct Reader<'a>(&'a [u8]);
+
+impl<'a> Reader<'a> {
+    fn take(&mut self, len: usize) -> PowerResult<&'a [u8]> {
+        let (head, rest) = self
+            .0
+            .split_at_checked(len)
+            .ok_or(PowerError::InvalidQoSSnapshot)?;
+        self.0 = rest;
+        Ok(head)
+    }
+
+    fn u16(&mut self) -> PowerResult<u16> {
+        Ok(u16::from_le_bytes(self.take(2)?.try_into().unwrap()))
+    }
+
+    fn u32(&mut self) -> PowerResult<u32> {
+        Ok(u32::from_le_bytes(self.take(4)?.try_into().unwrap()))
+    }
+}
+
+/// CRC-32 with the IEEE 802.3 polynomial, as used by zlib
+fn crc32(data: &[u8]) -> u32 {
+    let mut crc = !0u32;
+    for &byte in data {
+        crc ^= u32::from(byte);
+        for _ in 0..8 {
+            crc = (crc >> 1) ^ (0xedb8_8320 & (crc & 1).wrapping_neg());
+        }
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RK3588;
+
+    static GPU_PORTS: &[QosPort] = &[
+        QosPort::new("gpu_m0", RK3588::GPU, 0xfdf3_5000),
+        QosPort::new("gpu_m1", RK3588::GPU, 0xfdf3_5200),
+    ];
+    static MOVED_PORTS: &[QosPort] = &[
+        QosPort::new("gpu_m0", RK3588::GPU, 0xfdf3_5000),
+        QosPort::new("gpu_m1", RK3588::GPU, 0xfdf3_5400),
+    ];
+
+    fn gpu_ports(domain: PowerDomain) -> Option<&'static [QosPort]> {
+        (domain == RK3588::GPU).then_some(GPU_PORTS)
+    }
+
+    #[test]
+    fn test_snapshot_round_trip() {
+        assert_eq!(crc32(b"123456789"), 0xcbf4_3926);
+
+        let gpu = [[0x303, 1, 0x80, 0x40, 0], [0x101, 0, 0, 0, 1]];
+        let blob = encode(
+            RkBoard::Rk3588,
+            [(RK3588::GPU, GPU_PORTS, &gpu[..])].into_iter(),
+        )
+        .unwrap();
+        assert_eq!(blob.len(), 10 + 8 + 2 * 5 * 4 + 4);
+        assert_eq!(
+            decode(RkBoard::Rk3588, &blob, gpu_ports),
+            Ok(alloc::vec![(RK3588::GPU, gpu.to_vec())])
+        );
+
+        assert_eq!(
+            decode(RkBoard::Rk3568, &blob, gpu_ports),
+            Err(PowerError::InvalidQoSSnapshot)
+        );
+        let mut corrupted = blob.clone();
+        corrupted[20] ^= 1;
+        assert_eq!(
+            decode(RkBoard::Rk3588, &corrupted, gpu_ports),
+            Err(PowerError::InvalidQoSSnapshot)
+        );
+        assert_eq!(
+            decode(RkBoard::Rk3588, &blob[..blob.len() - 1], gpu_ports),
+            Err(PowerError::InvalidQoSSnapshot)
+        );
+    }
+
+    #[test]
+    fn test_snapshot_port_tables() {
+        let gpu = [[0x303, 1, 0x80, 0x40, 0], [0x101, 0, 0, 0, 1]];
+        let blob = encode(
+            RkBoard::Rk3588,
+            [(RK3588::GPU, GPU_PORTS, &gpu[..])].into_iter(),
+        )
+        .unwrap();
+
+        // Same number of ports at other addresses
+        let moved = |domain| (domain == RK3588::GPU).then_some(MOVED_PORTS);
+        assert_eq!(
+            decode(RkBoard::Rk3588, &blob, moved),
+            Err(PowerError::InvalidQoSSnapshot)
+        );
+        assert_eq!(
+            decode(RkBoard::Rk3588, &blob, |_| None),
+            Err(PowerError::InvalidQoSSnapshot)
+        );
+
+        let twice = encode(
+            RkBoard::Rk3588,
+            [
+                (RK3588::GPU, GPU_PORTS, &gpu[..]),
+                (RK3588::GPU, GPU_PORTS, &gpu[..]),
+            ]
+            .into_iter(),
+        )
+        .unwrap();
+        assert_eq!(
+            decode(RkBoard::Rk3588, &twice, gpu_ports),
+            Err(PowerError::InvalidQoSSnapshot)
+        );
+
+        let too_many = alloc::vec![[0; MAX_QOS_REGS]; 0x1_0000];
+        assert_eq!(
+            encode(
+                RkBoard::Rk3588,
+                [(RK3588::GPU, GPU_PORTS, &too_many[..])].into_iter(),
+            ),
+            Err(PowerError::InvalidQoSSnapshot)
+        );
+    }
+}
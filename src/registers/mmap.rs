@@ -0,0 +1,183 @@
+//! Linux userspace register backend
+//!
+//! Maps register windows through a file descriptor with `mmap(2)`. The file
+//! is usually `/dev/mem` or a UIO device, which lets the driver run as a
+//! userspace diagnostic tool, but any regular file works too: the mapping is
+//! shared, so writes land in the file and a register image can be inspected
+//! or prepared on a host.
+
+use std::{fs::File, io, os::fd::AsRawFd, os::unix::fs::OpenOptionsExt, path::Path, ptr::NonNull};
+
+use super::{PmuRegs, RegisterAccess};
+use crate::{PowerResult, QosMapper, QosWindow};
+
+/// A shared memory mapping of part of a file
+///
+/// The mapping is released when the region is dropped.
+pub struct MmapRegion {
+    /// Start of the whole mapping (page aligned)
+    map: NonNull<u8>,
+    map_len: usize,
+    /// Start of the requested window inside the mapping
+    base: NonNull<u8>,
+    len: usize,
+}
+
+unsafe impl Send for MmapRegion {}
+
+impl MmapRegion {
+    /// Map `len` bytes of `file` starting at `offset`
+    ///
+    /// `offset` does not have to be page aligned; for `/dev/mem` it is the
+    /// physical address of the window.
+    ///
+    /// # Arguments
+    /// * `file` - File opened for reading and writing
+    /// * `offset` - Offset of the window in the file
+    /// * `len` - Size of the window in bytes
+    pub fn new(file: &File, offset: u64, len: usize) -> io::Result<Self> {
+        if len == 0 {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "empty register window",
+            ));
+        }
+
+        let page_size = unsafe { libc::sysconf(libc::_SC_PAGESIZE) } as u64;
+        let page_offset = (offset % page_size) as usize;
+        let map_offset = libc::off_t::try_from(offset - page_offset as u64)
+            .map_err(|_| io::Error::new(io::ErrorKind::InvalidInput, "offset out of range"))?;
+        let map_len = len + page_offset;
+
+        let ptr = unsafe {
+            libc::mmap(
+                core::ptr::null_mut(),
+                map_len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                file.as_raw_fd(),
+                map_offset,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+
+        let map = NonNull::new(ptr.cast::<u8>()).ok_or_else(io::Error::last_os_error)?;
+        Ok(Self {
+            map,
+            map_len,
+            base: unsafe { map.add(page_offset) },
+            len,
+        })
+    }
+
+    /// Open `path` and map `len` bytes starting at `offset`
+    ///
+    /// The file is opened with `O_SYNC`, which makes `/dev/mem` mappings
+    /// uncached.
+    pub fn open(path: impl AsRef<Path>, offset: u64, len: usize) -> io::Result<Self> {
+        let file = File::options()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_SYNC)
+            .open(path)?;
+        Self::new(&file, offset, len)
+    }
+
+    /// Get the start of the mapped window
+    pub fn as_ptr(&self) -> NonNull<u8> {
+        self.base
+    }
+
+    /// Get the size of the mapped window in bytes
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// Check whether the window is empty (never true for a mapped region)
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Use the region as the window holding the QoS ports
+    ///
+    /// # Arguments
+    /// * `phys_base` - Physical address the start of the window corresponds
+    ///   to, e.g. the file offset for `/dev/mem`
+    pub fn into_qos_window(self, phys_base: u64) -> MmapQosWindow {
+        MmapQosWindow {
+            window: QosWindow::new(self.base, phys_base, self.len),
+            region: self,
+        }
+    }
+}
+
+impl Drop for MmapRegion {
+    fn drop(&mut self) {
+        unsafe {
+            libc::munmap(self.map.as_ptr().cast(), self.map_len);
+        }
+    }
+}
+
+/// QoS ports reached through an [`MmapRegion`]
+///
+/// The window owns the mapping, so handing it to
+/// [`RockchipPM::with_qos_mapper`](crate::RockchipPM::with_qos_mapper) keeps
+/// the QoS ports mapped for as long as the driver lives.
+pub struct MmapQosWindow {
+    window: QosWindow,
+    region: MmapRegion,
+}
+
+impl MmapQosWindow {
+    /// Get the underlying mapping
+    pub fn region(&self) -> &MmapRegion {
+        &self.region
+    }
+}
+
+impl QosMapper for MmapQosWindow {
+    fn map(&self, phys_addr: u64, size: usize) -> Option<NonNull<u8>> {
+        self.window.map(phys_addr, size)
+    }
+}
+
+/// Register backend on top of an [`MmapRegion`]
+///
+/// Accesses are bounds-checked against the mapped window like [`PmuRegs`].
+pub struct MmapRegs {
+    regs: PmuRegs,
+    region: MmapRegion,
+}
+
+impl MmapRegs {
+    /// Access the registers in `region`
+    pub fn new(region: MmapRegion) -> Self {
+        Self {
+            regs: PmuRegs::new(region.as_ptr(), region.len()),
+            region,
+        }
+    }
+
+    /// Get the underlying mapping
+    pub fn region(&self) -> &MmapRegion {
+        &self.region
+    }
+
+    /// Get the bounds-checked accessor for the mapping
+    pub fn pmu_regs(&self) -> &PmuRegs {
+        &self.regs
+    }
+}
+
+impl RegisterAccess for MmapRegs {
+    fn read_u32(&self, offset: usize) -> PowerResult<u32> {
+        self.regs.read_u32(offset)
+    }
+
+    fn write_u32(&self, offset: usize, value: u32) -> PowerResult<()> {
+        self.regs.write_u32(offset, value)
+    }
+}
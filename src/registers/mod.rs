@@ -1,6 +1,175 @@
-use core::ptr::NonNull;
+use core::{marker::PhantomData, ptr::NonNull};
 
-use tock_registers::{register_bitfields, registers::*};
+use tock_registers::{
+    LocalRegisterCopy, RegisterLongName, fields::FieldValue, register_bitfields, registers::*,
+};
+
+use crate::{PowerDomain, PowerError, PowerResult, PowerStep};
+
+#[cfg(all(feature = "std", unix))]
+mod mmap;
+pub mod rk3568;
+pub mod rk3588;
+
+#[cfg(all(feature = "std", unix))]
+pub use mmap::{MmapQosWindow, MmapRegion, MmapRegs};
+
+/// Data bits of a hi-word write-enable register
+const HIWORD_DATA_MASK: u32 = 0xffff;
+
+/// PMU register access backend
+///
+/// All PMU accesses made by the driver go through this trait, with offsets
+/// relative to the PMU (syscon) base. [`PmuRegs`] is the memory-mapped
+/// implementation used on real hardware; other implementations can simulate,
+/// trace or forward register accesses.
+pub trait RegisterAccess {
+    /// Read the 32-bit register at `offset`
+    fn read_u32(&self, offset: usize) -> PowerResult<u32>;
+
+    /// Write `value` to the 32-bit register at `offset`
+    fn write_u32(&self, offset: usize, value: u32) -> PowerResult<()>;
+
+    /// Write the `mask` bits of a hi-word write-enable register
+    ///
+    /// The upper 16 bits of these registers select which of the lower 16
+    /// bits a write changes, so the update is a single write with no
+    /// read-modify-write. Bits of `value` outside `mask` are ignored.
+    ///
+    /// # Returns
+    /// * `Err(PowerError::InvalidWriteMask)` if `mask` has bits above bit 15
+    fn write_hiword(&self, offset: usize, mask: u32, value: u32) -> PowerResult<()> {
+        if mask & !HIWORD_DATA_MASK != 0 {
+            return Err(PowerError::InvalidWriteMask);
+        }
+        self.write_u32(offset, (mask << 16) | (value & mask))
+    }
+
+    /// Called by the power sequencer when it starts `step` for a domain, and
+    /// with `None` once the sequence has finished
+    ///
+    /// Register accesses made until the next call belong to that step. The
+    /// default implementation ignores it.
+    fn sequencer_step(&self, _step: Option<(PowerDomain, PowerStep)>) {}
+}
+
+impl<T: RegisterAccess + ?Sized> RegisterAccess for &T {
+    fn read_u32(&self, offset: usize) -> PowerResult<u32> {
+        (**self).read_u32(offset)
+    }
+
+    fn write_u32(&self, offset: usize, value: u32) -> PowerResult<()> {
+        (**self).write_u32(offset, value)
+    }
+
+    fn write_hiword(&self, offset: usize, mask: u32, value: u32) -> PowerResult<()> {
+        (**self).write_hiword(offset, mask, value)
+    }
+
+    fn sequencer_step(&self, step: Option<(PowerDomain, PowerStep)>) {
+        (**self).sequencer_step(step)
+    }
+}
+
+/// Set or clear the `mask` bits of a domain control register
+///
+/// Registers described with a write-enable mask (`w_mask != 0`) are updated
+/// with [`RegisterAccess::write_hiword`]; `w_mask` must then be `mask << 16`.
+/// Plain registers fall back to read-modify-write.
+///
+/// # Arguments
+/// * `reg` - PMU register accessor
+/// * `offset` - Register offset
+/// * `mask` - Domain bits in the register
+/// * `w_mask` - Write-enable bits from the domain table, or 0
+/// * `set` - True to set the bits, false to clear them
+///
+/// # Returns
+/// * `Err(PowerError::InvalidWriteMask)` if `mask` and `w_mask` do not
+///   describe a valid hi-word write
+pub(crate) fn write_domain_bits<R: RegisterAccess + ?Sized>(
+    reg: &R,
+    offset: usize,
+    mask: i32,
+    w_mask: i32,
+    set: bool,
+) -> PowerResult<()> {
+    let mask = mask as u32;
+    let value = if set { mask } else { 0 };
+
+    if w_mask != 0 {
+        if mask & !HIWORD_DATA_MASK != 0 || w_mask as u32 != mask << 16 {
+            return Err(PowerError::InvalidWriteMask);
+        }
+        reg.write_hiword(offset, mask, value)
+    } else {
+        let current = reg.read_u32(offset)?;
+        reg.write_u32(offset, (current & !mask) | value)
+    }
+}
+
+/// A PMU register with a known bitfield layout
+///
+/// Wraps an offset together with the `register_bitfields!` type describing
+/// it, so values read through any [`RegisterAccess`] backend can be inspected
+/// by field name.
+pub struct TypedRegister<T: RegisterLongName> {
+    offset: usize,
+    _layout: PhantomData<T>,
+}
+
+impl<T: RegisterLongName> Clone for TypedRegister<T> {
+    fn clone(&self) -> Self {
+        *self
+    }
+}
+
+impl<T: RegisterLongName> Copy for TypedRegister<T> {}
+
+impl<T: RegisterLongName> TypedRegister<T> {
+    /// Describe the register at `offset` from the PMU base
+    pub const fn new(offset: usize) -> Self {
+        Self {
+            offset,
+            _layout: PhantomData,
+        }
+    }
+
+    /// Get the register offset from the PMU base
+    pub const fn offset(&self) -> usize {
+        self.offset
+    }
+
+    /// Read the register through `reg`
+    ///
+    /// # Arguments
+    /// * `reg` - PMU register accessor
+    ///
+    /// # Returns
+    /// * A local copy of the register value that can be queried by field
+    pub fn get<R: RegisterAccess + ?Sized>(
+        &self,
+        reg: &R,
+    ) -> PowerResult<LocalRegisterCopy<u32, T>> {
+        Ok(LocalRegisterCopy::new(reg.read_u32(self.offset)?))
+    }
+
+    /// Write the register through `reg`
+    ///
+    /// Fields not mentioned in `value` are written as 0. For hi-word
+    /// write-enable registers, include the matching `WRITE_ENABLE` bits.
+    ///
+    /// # Arguments
+    /// * `reg` - PMU register accessor
+    /// * `value` - Field values to write
+    pub fn set<R: RegisterAccess + ?Sized>(
+        &self,
+        reg: &R,
+        value: FieldValue<u32, T>,
+    ) -> PowerResult<()> {
+        reg.write_u32(self.offset, value.value)
+    }
+}
 
 // 定义位域（bitfields）
 register_bitfields! [
@@ -48,36 +217,71 @@ register_bitfields! [
     ],
 ];
 
+/// Memory-mapped PMU register accessor
+///
+/// Accesses are checked against the size of the mapped register window;
+/// offsets past the end, or not 4-byte aligned, fail with
+/// [`PowerError::RegisterOutOfRange`] instead of touching memory.
 #[derive(Clone, Copy)]
 pub struct PmuRegs {
     base_addr: NonNull<u8>,
+    size: usize,
 }
 
 unsafe impl Send for PmuRegs {}
+// Every access is a single volatile read or write of the MMIO window
+unsafe impl Sync for PmuRegs {}
 
 impl PmuRegs {
-    pub const fn new(base_addr: NonNull<u8>) -> Self {
-        Self { base_addr }
+    /// Create an accessor for the register window at `base_addr`
+    ///
+    /// # Arguments
+    /// * `base_addr` - Virtual address of the mapped PMU (syscon) window
+    /// * `size` - Size of the mapped window in bytes
+    pub const fn new(base_addr: NonNull<u8>, size: usize) -> Self {
+        Self { base_addr, size }
     }
 
-    fn reg<T>(&self, offset: usize) -> &T {
-        unsafe { &*(self.base_addr.as_ptr().add(offset) as *const T) }
+    /// Get the size of the mapped register window in bytes
+    pub const fn size(&self) -> usize {
+        self.size
     }
 
-    #[allow(unused)]
-    pub fn pwr_con0(&self) -> &ReadWrite<u32, PMU_PWR_CON0::Register> {
-        self.reg(0x0)
+    /// Check whether a 32-bit register at `offset` lies inside the window
+    pub const fn contains(&self, offset: usize) -> bool {
+        offset % 4 == 0 && offset < self.size && self.size - offset >= 4
     }
 
+    fn reg_ptr(&self, offset: usize) -> PowerResult<*mut u32> {
+        if !self.contains(offset) {
+            return Err(PowerError::RegisterOutOfRange);
+        }
+        Ok(unsafe { self.base_addr.as_ptr().add(offset) as *mut u32 })
+    }
+
+    /// Get the `PMU_PWR_CON0` register
+    ///
+    /// # Returns
+    /// * `Err(PowerError::RegisterOutOfRange)` if the window doesn't cover it
+    pub fn pwr_con0(&self) -> PowerResult<&ReadWrite<u32, PMU_PWR_CON0::Register>> {
+        let ptr = self.reg_ptr(0x0)?;
+        Ok(unsafe { &*(ptr as *const ReadWrite<u32, PMU_PWR_CON0::Register>) })
+    }
+}
+
+impl RegisterAccess for PmuRegs {
     /// 读取32位寄存器值
-    pub fn read_u32(&self, offset: usize) -> u32 {
-        unsafe { core::ptr::read_volatile(self.base_addr.as_ptr().add(offset) as *const u32) }
+    fn read_u32(&self, offset: usize) -> PowerResult<u32> {
+        let ptr = self.reg_ptr(offset)?;
+        Ok(unsafe { core::ptr::read_volatile(ptr) })
     }
 
     /// 写入32位寄存器值
-    pub fn write_u32(&self, offset: usize, value: u32) {
+    fn write_u32(&self, offset: usize, value: u32) -> PowerResult<()> {
+        let ptr = self.reg_ptr(offset)?;
         unsafe {
-            core::ptr::write_volatile(self.base_addr.as_ptr().add(offset) as *mut u32, value);
+            core::ptr::write_volatile(ptr, value);
         }
+        Ok(())
     }
 }
@@ -0,0 +1,93 @@
+//! RK3568 PMU power domain registers
+//!
+//! Bitfield definitions for the single-bank `DOMAIN_M` register layout, with
+//! offsets relative to the PMU base used by [`PmuRegs`](super::PmuRegs).
+//! Field names follow the power domains in [`RK3568`](crate::RK3568), and the
+//! RK3568 domain table is built from these fields.
+//!
+//! `PMU_PWR_DWN_CON` and `PMU_BUS_IDLE_REQ` are hi-word write-enable
+//! registers: a bit in the lower half is only written if the matching
+//! `WRITE_ENABLE` bit is set.
+
+use tock_registers::register_bitfields;
+
+use super::TypedRegister;
+
+/// Bus idle request (`PMU_BUS_IDLE_REQ`)
+pub const BUS_IDLE_REQ: TypedRegister<PMU_BUS_IDLE_REQ::Register> = TypedRegister::new(0x50);
+/// Bus idle acknowledge (`PMU_BUS_IDLE_ACK`)
+pub const BUS_IDLE_ACK: TypedRegister<PMU_BUS_IDLE::Register> = TypedRegister::new(0x60);
+/// Bus idle status (`PMU_BUS_IDLE_ST`)
+pub const BUS_IDLE_ST: TypedRegister<PMU_BUS_IDLE::Register> = TypedRegister::new(0x68);
+/// Power down status (`PMU_PWR_DWN_ST`)
+pub const PWR_DWN_ST: TypedRegister<PMU_PWR_DWN_ST::Register> = TypedRegister::new(0x98);
+/// Power down control (`PMU_PWR_DWN_CON`)
+pub const PWR_DWN_CON: TypedRegister<PMU_PWR_DWN_CON::Register> = TypedRegister::new(0xa0);
+
+register_bitfields! [
+    u32,
+
+    /// Power down control register
+    /// Offset: 0x00a0
+    /// 1'b1: power off, 1'b0: power on
+    pub PMU_PWR_DWN_CON [
+        GPU OFFSET(0) NUMBITS(1),
+        NPU OFFSET(1) NUMBITS(1),
+        VPU OFFSET(2) NUMBITS(1),
+        RKVENC OFFSET(3) NUMBITS(1),
+        RKVDEC OFFSET(4) NUMBITS(1),
+        RGA OFFSET(5) NUMBITS(1),
+        VI OFFSET(6) NUMBITS(1),
+        VO OFFSET(7) NUMBITS(1),
+        PIPE OFFSET(8) NUMBITS(1),
+        /// Write enable for lower 16 bits (WO)
+        WRITE_ENABLE OFFSET(16) NUMBITS(16)
+    ],
+
+    /// Power down status register (RO)
+    /// Offset: 0x0098
+    /// 1'b1: power off, 1'b0: power on
+    pub PMU_PWR_DWN_ST [
+        GPU OFFSET(0) NUMBITS(1),
+        NPU OFFSET(1) NUMBITS(1),
+        VPU OFFSET(2) NUMBITS(1),
+        RKVENC OFFSET(3) NUMBITS(1),
+        RKVDEC OFFSET(4) NUMBITS(1),
+        RGA OFFSET(5) NUMBITS(1),
+        VI OFFSET(6) NUMBITS(1),
+        VO OFFSET(7) NUMBITS(1),
+        PIPE OFFSET(8) NUMBITS(1)
+    ],
+
+    /// Bus idle request register
+    /// Offset: 0x0050
+    /// 1'b1: request idle, 1'b0: cancel idle request
+    pub PMU_BUS_IDLE_REQ [
+        GPU OFFSET(1) NUMBITS(1),
+        NPU OFFSET(2) NUMBITS(1),
+        VI OFFSET(3) NUMBITS(1),
+        VO OFFSET(4) NUMBITS(1),
+        RGA OFFSET(5) NUMBITS(1),
+        VPU OFFSET(6) NUMBITS(1),
+        RKVENC OFFSET(7) NUMBITS(1),
+        RKVDEC OFFSET(8) NUMBITS(1),
+        PIPE OFFSET(11) NUMBITS(1),
+        /// Write enable for lower 16 bits (WO)
+        WRITE_ENABLE OFFSET(16) NUMBITS(16)
+    ],
+
+    /// Bus idle acknowledge and status registers (RO)
+    /// Offsets: 0x0060 (ACK), 0x0068 (ST)
+    /// 1'b1: bus is idle / request acknowledged
+    pub PMU_BUS_IDLE [
+        GPU OFFSET(1) NUMBITS(1),
+        NPU OFFSET(2) NUMBITS(1),
+        VI OFFSET(3) NUMBITS(1),
+        VO OFFSET(4) NUMBITS(1),
+        RGA OFFSET(5) NUMBITS(1),
+        VPU OFFSET(6) NUMBITS(1),
+        RKVENC OFFSET(7) NUMBITS(1),
+        RKVDEC OFFSET(8) NUMBITS(1),
+        PIPE OFFSET(11) NUMBITS(1)
+    ],
+];
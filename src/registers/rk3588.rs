@@ -0,0 +1,277 @@
+//! RK3588 PMU power domain registers
+//!
+//! Bitfield definitions for the PMU2 power domain registers, with offsets
+//! relative to the PMU base used by [`PmuRegs`](super::PmuRegs). Field names
+//! follow the power domains in [`RK3588`](crate::RK3588), and the RK3588
+//! domain table is built from these fields.
+//!
+//! The control registers (`PWR_GATE_SFTCON`, `BUS_IDLE_SFTCON`,
+//! `MEMORY_GATE_SFTCON`) are hi-word write-enable registers: a bit in the
+//! lower half is only written if the matching `WRITE_ENABLE` bit is set.
+//!
+//! ```
+//! use rockchip_pm::registers::rk3588::{PMU_PWR_GATE_CON0, PWR_GATE_CON0};
+//! use rockchip_pm::sim::Rk3588Pmu;
+//!
+//! let pmu = Rk3588Pmu::new();
+//! let con = PWR_GATE_CON0.get(&pmu).unwrap();
+//! assert!(!con.is_set(PMU_PWR_GATE_CON0::AV1));
+//! ```
+
+use tock_registers::register_bitfields;
+
+use super::TypedRegister;
+
+/// Power gate control, domains 0-15 (`PMU2_PWR_GATE_SFTCON0`)
+pub const PWR_GATE_CON0: TypedRegister<PMU_PWR_GATE_CON0::Register> = TypedRegister::new(0x14c);
+/// Power gate control, domains 16-31 (`PMU2_PWR_GATE_SFTCON1`)
+pub const PWR_GATE_CON1: TypedRegister<PMU_PWR_GATE_CON1::Register> = TypedRegister::new(0x150);
+/// Power gate status (`PMU2_PWR_GATE_STS`)
+pub const PWR_GATE_ST: TypedRegister<PMU_PWR_GATE_ST::Register> = TypedRegister::new(0x180);
+/// Bus idle request, first bank (`PMU2_BUS_IDLE_SFTCON0`)
+pub const BUS_IDLE_REQ0: TypedRegister<PMU_BUS_IDLE_REQ0::Register> = TypedRegister::new(0x10c);
+/// Bus idle request, second bank (`PMU2_BUS_IDLE_SFTCON1`)
+pub const BUS_IDLE_REQ1: TypedRegister<PMU_BUS_IDLE_REQ1::Register> = TypedRegister::new(0x110);
+/// Bus idle acknowledge (`PMU2_BUS_IDLE_ACK`)
+pub const BUS_IDLE_ACK: TypedRegister<PMU_BUS_IDLE::Register> = TypedRegister::new(0x118);
+/// Bus idle status (`PMU2_BUS_IDLE_ST`)
+pub const BUS_IDLE_ST: TypedRegister<PMU_BUS_IDLE::Register> = TypedRegister::new(0x120);
+/// Memory power gate control, domains 0-15 (`PMU2_MEMORY_GATE_SFTCON0`)
+///
+/// Uses the same bit layout as [`PWR_GATE_CON0`].
+pub const MEM_PWR_GATE_CON0: TypedRegister<PMU_PWR_GATE_CON0::Register> = TypedRegister::new(0x1a0);
+/// Memory power gate control, domains 16-31 (`PMU2_MEMORY_GATE_SFTCON1`)
+///
+/// Uses the same bit layout as [`PWR_GATE_CON1`].
+pub const MEM_PWR_GATE_CON1: TypedRegister<PMU_PWR_GATE_CON1::Register> = TypedRegister::new(0x1a4);
+/// Memory chain status, first bank (`PMU2_PWR_CHAIN1_ST`)
+pub const CHAIN_STATUS0: TypedRegister<PMU_MEM_STATUS0::Register> = TypedRegister::new(0x1f0);
+/// Memory chain status, second bank (`PMU2_PWR_CHAIN1_ST1`)
+pub const CHAIN_STATUS1: TypedRegister<PMU_MEM_STATUS1::Register> = TypedRegister::new(0x1f4);
+/// Memory power status, first bank (`PMU2_MEMORY_PWR_ST0`)
+pub const MEM_STATUS0: TypedRegister<PMU_MEM_STATUS0::Register> = TypedRegister::new(0x1f8);
+/// Memory power status, second bank (`PMU2_MEMORY_PWR_ST1`)
+pub const MEM_STATUS1: TypedRegister<PMU_MEM_STATUS1::Register> = TypedRegister::new(0x1fc);
+/// Memory repair status (`PMU2_REPAIR_STATUS`)
+pub const REPAIR_STATUS: TypedRegister<PMU_REPAIR_STATUS::Register> = TypedRegister::new(0x290);
+
+register_bitfields! [
+    u32,
+
+    /// Power gate control register 0
+    /// Offset: 0x014c
+    /// 1'b1: power off, 1'b0: power on
+    pub PMU_PWR_GATE_CON0 [
+        GPU OFFSET(0) NUMBITS(1),
+        NPU OFFSET(1) NUMBITS(1),
+        VCODEC OFFSET(2) NUMBITS(1),
+        NPUTOP OFFSET(3) NUMBITS(1),
+        NPU1 OFFSET(4) NUMBITS(1),
+        NPU2 OFFSET(5) NUMBITS(1),
+        VENC0 OFFSET(6) NUMBITS(1),
+        VENC1 OFFSET(7) NUMBITS(1),
+        RKVDEC0 OFFSET(8) NUMBITS(1),
+        RKVDEC1 OFFSET(9) NUMBITS(1),
+        VDPU OFFSET(10) NUMBITS(1),
+        RGA30 OFFSET(11) NUMBITS(1),
+        AV1 OFFSET(12) NUMBITS(1),
+        VI OFFSET(13) NUMBITS(1),
+        FEC OFFSET(14) NUMBITS(1),
+        ISP1 OFFSET(15) NUMBITS(1),
+        /// Write enable for lower 16 bits (WO)
+        WRITE_ENABLE OFFSET(16) NUMBITS(16)
+    ],
+
+    /// Power gate control register 1
+    /// Offset: 0x0150
+    /// 1'b1: power off, 1'b0: power on
+    pub PMU_PWR_GATE_CON1 [
+        RGA31 OFFSET(0) NUMBITS(1),
+        VOP OFFSET(1) NUMBITS(1),
+        VO0 OFFSET(2) NUMBITS(1),
+        VO1 OFFSET(3) NUMBITS(1),
+        AUDIO OFFSET(4) NUMBITS(1),
+        PHP OFFSET(5) NUMBITS(1),
+        GMAC OFFSET(6) NUMBITS(1),
+        PCIE OFFSET(7) NUMBITS(1),
+        NVM OFFSET(8) NUMBITS(1),
+        NVM0 OFFSET(9) NUMBITS(1),
+        SDIO OFFSET(10) NUMBITS(1),
+        USB OFFSET(11) NUMBITS(1),
+        SDMMC OFFSET(13) NUMBITS(1),
+        /// Write enable for lower 16 bits (WO)
+        WRITE_ENABLE OFFSET(16) NUMBITS(16)
+    ],
+
+    /// Power gate status register (RO)
+    /// Offset: 0x0180
+    /// Bits mirror PWR_GATE_CON0 (15:0) and PWR_GATE_CON1 (31:16).
+    /// 1'b1: power off, 1'b0: power on
+    ///
+    /// The driver tables only use the NPU, VCODEC and NVM bits; the other
+    /// domains report their state through REPAIR_STATUS.
+    pub PMU_PWR_GATE_ST [
+        GPU OFFSET(0) NUMBITS(1),
+        NPU OFFSET(1) NUMBITS(1),
+        VCODEC OFFSET(2) NUMBITS(1),
+        NPUTOP OFFSET(3) NUMBITS(1),
+        NPU1 OFFSET(4) NUMBITS(1),
+        NPU2 OFFSET(5) NUMBITS(1),
+        VENC0 OFFSET(6) NUMBITS(1),
+        VENC1 OFFSET(7) NUMBITS(1),
+        RKVDEC0 OFFSET(8) NUMBITS(1),
+        RKVDEC1 OFFSET(9) NUMBITS(1),
+        VDPU OFFSET(10) NUMBITS(1),
+        RGA30 OFFSET(11) NUMBITS(1),
+        AV1 OFFSET(12) NUMBITS(1),
+        VI OFFSET(13) NUMBITS(1),
+        FEC OFFSET(14) NUMBITS(1),
+        ISP1 OFFSET(15) NUMBITS(1),
+        RGA31 OFFSET(16) NUMBITS(1),
+        VOP OFFSET(17) NUMBITS(1),
+        VO0 OFFSET(18) NUMBITS(1),
+        VO1 OFFSET(19) NUMBITS(1),
+        AUDIO OFFSET(20) NUMBITS(1),
+        PHP OFFSET(21) NUMBITS(1),
+        GMAC OFFSET(22) NUMBITS(1),
+        PCIE OFFSET(23) NUMBITS(1),
+        NVM OFFSET(24) NUMBITS(1),
+        NVM0 OFFSET(25) NUMBITS(1),
+        SDIO OFFSET(26) NUMBITS(1),
+        USB OFFSET(27) NUMBITS(1),
+        SDMMC OFFSET(29) NUMBITS(1)
+    ],
+
+    /// Bus idle request register 0
+    /// Offset: 0x010c
+    /// 1'b1: request idle, 1'b0: cancel idle request
+    pub PMU_BUS_IDLE_REQ0 [
+        GPU OFFSET(0) NUMBITS(1),
+        NPUTOP OFFSET(1) NUMBITS(1),
+        NPU1 OFFSET(2) NUMBITS(1),
+        NPU2 OFFSET(3) NUMBITS(1),
+        VENC0 OFFSET(4) NUMBITS(1),
+        VENC1 OFFSET(5) NUMBITS(1),
+        RKVDEC0 OFFSET(6) NUMBITS(1),
+        RKVDEC1 OFFSET(7) NUMBITS(1),
+        VDPU OFFSET(8) NUMBITS(1),
+        AV1 OFFSET(9) NUMBITS(1),
+        VI OFFSET(10) NUMBITS(1),
+        ISP1 OFFSET(11) NUMBITS(1),
+        RGA31 OFFSET(12) NUMBITS(1),
+        /// VOP and VOP channel interfaces
+        VOP OFFSET(13) NUMBITS(2),
+        VO0 OFFSET(15) NUMBITS(1),
+        /// Write enable for lower 16 bits (WO)
+        WRITE_ENABLE OFFSET(16) NUMBITS(16)
+    ],
+
+    /// Bus idle request register 1
+    /// Offset: 0x0110
+    /// 1'b1: request idle, 1'b0: cancel idle request
+    pub PMU_BUS_IDLE_REQ1 [
+        VO1 OFFSET(0) NUMBITS(1),
+        AUDIO OFFSET(1) NUMBITS(1),
+        NVM OFFSET(2) NUMBITS(1),
+        SDIO OFFSET(3) NUMBITS(1),
+        USB OFFSET(4) NUMBITS(1),
+        PHP OFFSET(5) NUMBITS(1),
+        /// Write enable for lower 16 bits (WO)
+        WRITE_ENABLE OFFSET(16) NUMBITS(16)
+    ],
+
+    /// Bus idle acknowledge and status registers (RO)
+    /// Offsets: 0x0118 (ACK), 0x0120 (ST)
+    /// 1'b1: bus is idle / request acknowledged
+    pub PMU_BUS_IDLE [
+        GPU OFFSET(0) NUMBITS(1),
+        NPUTOP OFFSET(1) NUMBITS(1),
+        NPU1 OFFSET(2) NUMBITS(1),
+        NPU2 OFFSET(3) NUMBITS(1),
+        VENC0 OFFSET(4) NUMBITS(1),
+        VENC1 OFFSET(5) NUMBITS(1),
+        RKVDEC0 OFFSET(6) NUMBITS(1),
+        RKVDEC1 OFFSET(7) NUMBITS(1),
+        VDPU OFFSET(8) NUMBITS(1),
+        AV1 OFFSET(9) NUMBITS(1),
+        VI OFFSET(10) NUMBITS(1),
+        ISP1 OFFSET(11) NUMBITS(1),
+        RGA31 OFFSET(12) NUMBITS(1),
+        /// VOP and VOP channel interfaces
+        VOP OFFSET(13) NUMBITS(2),
+        VO0 OFFSET(15) NUMBITS(1),
+        VO1 OFFSET(16) NUMBITS(1),
+        AUDIO OFFSET(17) NUMBITS(1),
+        NVM OFFSET(18) NUMBITS(1),
+        SDIO OFFSET(19) NUMBITS(1),
+        USB OFFSET(20) NUMBITS(1),
+        PHP OFFSET(21) NUMBITS(1)
+    ],
+
+    /// Memory chain and memory power status, first bank (RO)
+    /// Offsets: 0x01f0 (CHAIN_STATUS0), 0x01f8 (MEM_STATUS0)
+    pub PMU_MEM_STATUS0 [
+        NPUTOP OFFSET(11) NUMBITS(1),
+        NPU1 OFFSET(12) NUMBITS(1),
+        NPU2 OFFSET(13) NUMBITS(1),
+        VENC0 OFFSET(14) NUMBITS(1),
+        VENC1 OFFSET(15) NUMBITS(1),
+        RKVDEC0 OFFSET(16) NUMBITS(1),
+        RKVDEC1 OFFSET(17) NUMBITS(1),
+        VDPU OFFSET(18) NUMBITS(1),
+        RGA30 OFFSET(19) NUMBITS(1),
+        AV1 OFFSET(20) NUMBITS(1),
+        VI OFFSET(21) NUMBITS(1),
+        FEC OFFSET(22) NUMBITS(1),
+        ISP1 OFFSET(23) NUMBITS(1),
+        RGA31 OFFSET(24) NUMBITS(1),
+        VOP OFFSET(25) NUMBITS(1),
+        VO0 OFFSET(26) NUMBITS(1),
+        VO1 OFFSET(27) NUMBITS(1),
+        AUDIO OFFSET(28) NUMBITS(1),
+        PHP OFFSET(29) NUMBITS(1),
+        GMAC OFFSET(30) NUMBITS(1),
+        PCIE OFFSET(31) NUMBITS(1)
+    ],
+
+    /// Memory chain and memory power status, second bank (RO)
+    /// Offsets: 0x01f4 (CHAIN_STATUS1), 0x01fc (MEM_STATUS1)
+    pub PMU_MEM_STATUS1 [
+        NVM0 OFFSET(1) NUMBITS(1),
+        SDIO OFFSET(2) NUMBITS(1),
+        USB OFFSET(3) NUMBITS(1),
+        SDMMC OFFSET(5) NUMBITS(1)
+    ],
+
+    /// Memory repair status register (RO)
+    /// Offset: 0x0290
+    /// 1'b1: power on, 1'b0: power off
+    pub PMU_REPAIR_STATUS [
+        GPU OFFSET(1) NUMBITS(1),
+        NPUTOP OFFSET(2) NUMBITS(1),
+        NPU1 OFFSET(3) NUMBITS(1),
+        NPU2 OFFSET(4) NUMBITS(1),
+        VENC0 OFFSET(5) NUMBITS(1),
+        VENC1 OFFSET(6) NUMBITS(1),
+        RKVDEC0 OFFSET(7) NUMBITS(1),
+        RKVDEC1 OFFSET(8) NUMBITS(1),
+        VDPU OFFSET(9) NUMBITS(1),
+        RGA30 OFFSET(10) NUMBITS(1),
+        AV1 OFFSET(11) NUMBITS(1),
+        VI OFFSET(12) NUMBITS(1),
+        FEC OFFSET(13) NUMBITS(1),
+        ISP1 OFFSET(14) NUMBITS(1),
+        RGA31 OFFSET(15) NUMBITS(1),
+        VOP OFFSET(16) NUMBITS(1),
+        VO0 OFFSET(17) NUMBITS(1),
+        VO1 OFFSET(18) NUMBITS(1),
+        AUDIO OFFSET(19) NUMBITS(1),
+        PHP OFFSET(20) NUMBITS(1),
+        GMAC OFFSET(21) NUMBITS(1),
+        PCIE OFFSET(22) NUMBITS(1),
+        NVM0 OFFSET(23) NUMBITS(1),
+        SDIO OFFSET(24) NUMBITS(1),
+        USB OFFSET(25) NUMBITS(1),
+        SDMMC OFFSET(26) NUMBITS(1)
+    ],
+];
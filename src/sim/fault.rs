@@ -0,0 +1,176 @@
+//! Fault-injection register backend
+//!
+//! [`FaultInjector`] wraps another [`RegisterAccess`] backend (usually one of
+//! the PMU models) and distorts what the driver sees according to a script of
+//! [`Fault`]s, so the timeout and error paths can be exercised.
+
+use alloc::vec::Vec;
+use core::cell::RefCell;
+
+use crate::{PowerDomain, PowerError, PowerResult, PowerStep, registers::RegisterAccess};
+
+/// A scripted register fault
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Fault {
+    /// Force the `mask` bits of the register at `offset` to `value` on every read
+    Stuck {
+        offset: usize,
+        mask: u32,
+        value: u32,
+    },
+    /// Like [`Stuck`](Fault::Stuck), but only for the first `reads` reads
+    /// of the register
+    StuckFor {
+        offset: usize,
+        mask: u32,
+        value: u32,
+        reads: u32,
+    },
+    /// After every write, keep reporting the previous value of the `mask`
+    /// bits of the register at `offset` for the next `polls` reads
+    Delayed {
+        offset: usize,
+        mask: u32,
+        polls: u32,
+    },
+    /// Invert the `mask` bits of the register at `offset` once, on its
+    /// `nth` read (counting from 0)
+    Flip { offset: usize, mask: u32, nth: u32 },
+    /// Fail every access to the register at `offset` with
+    /// [`PowerError::HardwareError`]
+    BusError { offset: usize },
+}
+
+impl Fault {
+    fn offset(&self) -> usize {
+        match *self {
+            Fault::Stuck { offset, .. }
+            | Fault::StuckFor { offset, .. }
+            | Fault::Delayed { offset, .. }
+            | Fault::Flip { offset, .. }
+            | Fault::BusError { offset } => offset,
+        }
+    }
+}
+
+/// Runtime state of an injected fault
+struct ArmedFault {
+    fault: Fault,
+    /// Reads of the faulted register seen so far
+    reads: u32,
+    /// Value reported by a delayed register while `pending` reads remain
+    stale: u32,
+    pending: u32,
+}
+
+/// Register backend that injects scripted faults into another backend
+///
+/// Faults can be added and removed through a shared reference, so they can be
+/// scripted while the backend is owned by a
+/// [`RockchipPM`](crate::RockchipPM) and reached through
+/// [`regs`](crate::RockchipPM::regs).
+pub struct FaultInjector<R: RegisterAccess> {
+    inner: R,
+    faults: RefCell<Vec<ArmedFault>>,
+}
+
+impl<R: RegisterAccess> FaultInjector<R> {
+    /// Wrap `inner` with no faults injected
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            faults: RefCell::new(Vec::new()),
+        }
+    }
+
+    /// Add a fault to the script
+    pub fn inject(&self, fault: Fault) {
+        self.faults.borrow_mut().push(ArmedFault {
+            fault,
+            reads: 0,
+            stale: 0,
+            pending: 0,
+        });
+    }
+
+    /// Remove all injected faults
+    pub fn clear(&self) {
+        self.faults.borrow_mut().clear();
+    }
+
+    /// Get the wrapped backend
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    fn check_bus(&self, offset: usize) -> PowerResult<()> {
+        let bus_error = self
+            .faults
+            .borrow()
+            .iter()
+            .any(|f| f.fault == Fault::BusError { offset });
+        if bus_error {
+            return Err(PowerError::HardwareError);
+        }
+        Ok(())
+    }
+}
+
+impl<R: RegisterAccess> RegisterAccess for FaultInjector<R> {
+    fn read_u32(&self, offset: usize) -> PowerResult<u32> {
+        self.check_bus(offset)?;
+        let mut value = self.inner.read_u32(offset)?;
+
+        for armed in self.faults.borrow_mut().iter_mut() {
+            if armed.fault.offset() != offset {
+                continue;
+            }
+            match armed.fault {
+                Fault::Stuck { mask, value: v, .. } => value = (value & !mask) | (v & mask),
+                Fault::StuckFor {
+                    mask,
+                    value: v,
+                    reads,
+                    ..
+                } => {
+                    if armed.reads < reads {
+                        value = (value & !mask) | (v & mask);
+                    }
+                }
+                Fault::Delayed { mask, .. } => {
+                    if armed.pending > 0 {
+                        armed.pending -= 1;
+                        value = (value & !mask) | (armed.stale & mask);
+                    }
+                }
+                Fault::Flip { mask, nth, .. } => {
+                    if armed.reads == nth {
+                        value ^= mask;
+                    }
+                }
+                Fault::BusError { .. } => {}
+            }
+            armed.reads = armed.reads.saturating_add(1);
+        }
+
+        Ok(value)
+    }
+
+    fn write_u32(&self, offset: usize, value: u32) -> PowerResult<()> {
+        self.check_bus(offset)?;
+
+        // Capture what delayed registers read before the write takes effect
+        for armed in self.faults.borrow_mut().iter_mut() {
+            if let Fault::Delayed { offset, polls, .. } = armed.fault {
+                armed.stale = self.inner.read_u32(offset)?;
+                armed.pending = polls;
+            }
+        }
+
+        self.inner.write_u32(offset, value)
+    }
+
+    fn sequencer_step(&self, step: Option<(PowerDomain, PowerStep)>) {
+        self.inner.sequencer_step(step)
+    }
+}
@@ -0,0 +1,102 @@
+//! Behavioral PMU models for host-side testing
+//!
+//! The models in this module implement [`RegisterAccess`](crate::RegisterAccess)
+//! and react to register writes the way the PMU silicon does, so the complete
+//! power on/off sequences can be exercised without a board. Wrapper backends
+//! inject faults ([`FaultInjector`]) or record and replay register traces
+//! ([`TraceRecorder`], [`TraceReplayer`]) on top of any other backend:
+//!
+//! ```rust
+//! use rockchip_pm::{RK3588, RkBoard, RockchipPM, sim::Rk3588Pmu};
+//!
+//! let mut pm = RockchipPM::with_regs(Rk3588Pmu::new(), RkBoard::Rk3588);
+//! pm.power_domain_off(RK3588::AV1).unwrap();
+//! assert!(!pm.regs().is_domain_powered(RK3588::AV1));
+//! ```
+
+use alloc::collections::{BTreeMap, BTreeSet};
+use core::cell::RefCell;
+
+use tock_registers::{RegisterLongName, fields::Field};
+
+use crate::irq::IrqLayout;
+
+mod fault;
+mod rk3568;
+mod rk3588;
+mod trace;
+
+pub use fault::{Fault, FaultInjector};
+pub use rk3568::Rk3568Pmu;
+pub use rk3588::Rk3588Pmu;
+pub use trace::{Access, Divergence, Trace, TraceEvent, TraceRecorder, TraceReplayer};
+
+/// Interrupt wiring of a PMU model
+///
+/// The models don't know where a board routes the PMU interrupt, so a test
+/// places the interrupt registers itself, at offsets the model doesn't use,
+/// and picks the source bits the model latches. This wiring only lets the
+/// interrupt path of the driver run against a model; it says nothing about
+/// which events the silicon reports or where.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SimIrq {
+    /// Interrupt registers, as passed to
+    /// [`RockchipPM::with_irq`](crate::RockchipPM::with_irq)
+    pub layout: IrqLayout,
+    /// Source latched when a write switches the power of a domain
+    pub pwr_gate: u32,
+    /// Source latched when a write changes a bus idle request
+    pub bus_idle: u32,
+}
+
+/// Apply a Rockchip hi-word write to the current register contents
+///
+/// The upper 16 bits of `value` are write-enable bits for the lower 16 bits;
+/// lower bits whose write-enable bit is clear keep their current value.
+fn hiword_apply(current: u32, value: u32) -> u32 {
+    let write_enable = value >> 16;
+    (current & !write_enable) | (value & write_enable)
+}
+
+/// Shifted mask of a `register_bitfields!` field
+const fn field_bits<R: RegisterLongName>(field: Field<u32, R>) -> u32 {
+    field.mask << field.shift
+}
+
+/// Get the only item of `items`, or `None` if it is empty
+const fn one<T: Copy>(items: &[T]) -> Option<T> {
+    match items {
+        [item] => Some(*item),
+        _ => None,
+    }
+}
+
+/// Backing storage for the writable registers of a PMU model
+struct RegisterFile {
+    /// Offsets of the hi-word write-enable control registers
+    hiword_regs: BTreeSet<usize>,
+    regs: RefCell<BTreeMap<usize, u32>>,
+}
+
+impl RegisterFile {
+    fn new(hiword_regs: BTreeSet<usize>) -> Self {
+        Self {
+            hiword_regs,
+            regs: RefCell::new(BTreeMap::new()),
+        }
+    }
+
+    fn get(&self, offset: usize) -> u32 {
+        self.regs.borrow().get(&offset).copied().unwrap_or(0)
+    }
+
+    fn write(&self, offset: usize, value: u32) {
+        let mut regs = self.regs.borrow_mut();
+        let reg = regs.entry(offset).or_insert(0);
+        if self.hiword_regs.contains(&offset) {
+            *reg = hiword_apply(*reg, value);
+        } else {
+            *reg = value;
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! Behavioral model of the RK3568 PMU power domain registers
+
+use alloc::collections::BTreeSet;
+
+use super::{RegisterFile, field_bits};
+use crate::{
+    PowerDomain, PowerResult, RK3568,
+    registers::{
+        RegisterAccess,
+        rk3568::{
+            BUS_IDLE_ACK, BUS_IDLE_REQ, BUS_IDLE_ST, PMU_BUS_IDLE, PMU_BUS_IDLE_REQ,
+            PMU_PWR_DWN_CON, PMU_PWR_DWN_ST, PWR_DWN_CON, PWR_DWN_ST,
+        },
+    },
+};
+
+/// Register bits of a domain, from the typed register maps
+///
+/// Like the RK3588 model, this deliberately doesn't use the driver's domain
+/// table.
+struct DomainBits {
+    domain: PowerDomain,
+    /// `PMU_PWR_DWN_CON` bit
+    pwr: u32,
+    /// `PMU_PWR_DWN_ST` bit
+    status: u32,
+    /// `PMU_BUS_IDLE_REQ` bit
+    req: u32,
+    /// `PMU_BUS_IDLE_ACK`/`PMU_BUS_IDLE_ST` bit
+    idle: u32,
+}
+
+/// Build the [`DomainBits`] of each domain from the fields named after it
+macro_rules! domain_bits {
+    ($($domain:ident),* $(,)?) => {
+        &[$(DomainBits {
+            domain: RK3568::$domain,
+            pwr: field_bits(PMU_PWR_DWN_CON::$domain),
+            status: field_bits(PMU_PWR_DWN_ST::$domain),
+            req: field_bits(PMU_BUS_IDLE_REQ::$domain),
+            idle: field_bits(PMU_BUS_IDLE::$domain),
+        }),*]
+    };
+}
+
+static DOMAINS: &[DomainBits] = domain_bits![GPU, NPU, VPU, RKVENC, RKVDEC, RGA, VI, VO, PIPE];
+
+/// OR together the bits reported by every domain
+fn collect_bits(bits: impl Fn(&DomainBits) -> u32) -> u32 {
+    DOMAINS.iter().fold(0, |acc, d| acc | bits(d))
+}
+
+/// Simulated RK3568 PMU
+///
+/// Models the single-bank `DOMAIN_M` layout of
+/// [`registers::rk3568`](crate::registers::rk3568), with the bits of each
+/// domain taken from the fields named after it:
+///
+/// - `PMU_PWR_DWN_CON` (`0xa0`) and `PMU_BUS_IDLE_REQ` (`0x50`) are hi-word
+///   write-enable registers; writes without the matching write-enable bit are
+///   ignored.
+/// - `PMU_PWR_DWN_ST` (`0x98`) reads 1 for domains that are powered off.
+/// - `PMU_BUS_IDLE_ACK` (`0x60`) and `PMU_BUS_IDLE_ST` (`0x68`) follow the
+///   idle request bits.
+///
+/// The RK3568 PMU has no memory power or repair registers. Writes to status
+/// registers are dropped and any other offset behaves as plain storage. All
+/// domains come out of reset powered on and not idle.
+pub struct Rk3568Pmu {
+    regs: RegisterFile,
+}
+
+impl Rk3568Pmu {
+    /// Create a PMU model in its reset state
+    pub fn new() -> Self {
+        let hiword_regs = BTreeSet::from([PWR_DWN_CON.offset(), BUS_IDLE_REQ.offset()]);
+
+        Self {
+            regs: RegisterFile::new(hiword_regs),
+        }
+    }
+
+    /// Check whether the model has `domain` powered on
+    ///
+    /// # Panics
+    /// Panics if `domain` is not an RK3568 power domain
+    pub fn is_domain_powered(&self, domain: PowerDomain) -> bool {
+        self.domain_on(Self::domain(domain))
+    }
+
+    /// Check whether the model has the bus of `domain` in idle
+    ///
+    /// # Panics
+    /// Panics if `domain` is not an RK3568 power domain
+    pub fn is_domain_idle(&self, domain: PowerDomain) -> bool {
+        self.idle_requested(Self::domain(domain))
+    }
+
+    /// Read a register the way the driver would see it
+    pub fn peek(&self, offset: usize) -> u32 {
+        self.status(offset).unwrap_or_else(|| self.regs.get(offset))
+    }
+
+    fn domain(domain: PowerDomain) -> &'static DomainBits {
+        DOMAINS
+            .iter()
+            .find(|d| d.domain == domain)
+            .expect("not an RK3568 power domain")
+    }
+
+    fn domain_on(&self, d: &DomainBits) -> bool {
+        self.regs.get(PWR_DWN_CON.offset()) & d.pwr == 0
+    }
+
+    fn idle_requested(&self, d: &DomainBits) -> bool {
+        self.regs.get(BUS_IDLE_REQ.offset()) & d.req == d.req
+    }
+
+    /// Compute a status register from the control registers
+    ///
+    /// Returns `None` if `offset` is not a status register.
+    fn status(&self, offset: usize) -> Option<u32> {
+        let bits = if offset == PWR_DWN_ST.offset() {
+            collect_bits(|d| if self.domain_on(d) { 0 } else { d.status })
+        } else if offset == BUS_IDLE_ACK.offset() || offset == BUS_IDLE_ST.offset() {
+            collect_bits(|d| if self.idle_requested(d) { d.idle } else { 0 })
+        } else {
+            return None;
+        };
+
+        Some(bits)
+    }
+}
+
+impl Default for Rk3568Pmu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RegisterAccess for Rk3568Pmu {
+    fn read_u32(&self, offset: usize) -> PowerResult<u32> {
+        Ok(self.peek(offset))
+    }
+
+    fn write_u32(&self, offset: usize, value: u32) -> PowerResult<()> {
+        // Status registers are read-only
+        if self.status(offset).is_none() {
+            self.regs.write(offset, value);
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variants::rk3568::pmu_info;
+
+    #[test]
+    fn test_domain_table_matches_register_maps() {
+        let info = pmu_info();
+        assert_eq!(info.pwr_offset as usize, PWR_DWN_CON.offset());
+        assert_eq!(info.status_offset as usize, PWR_DWN_ST.offset());
+        assert_eq!(info.req_offset as usize, BUS_IDLE_REQ.offset());
+        assert_eq!(info.idle_offset as usize, BUS_IDLE_ST.offset());
+        assert_eq!(info.ack_offset as usize, BUS_IDLE_ACK.offset());
+        assert_eq!(info.domains.len(), DOMAINS.len());
+
+        for bits in DOMAINS {
+            let d = &info.domains[&bits.domain];
+            let name = d.name;
+            assert_eq!((d.pwr_offset, d.req_offset), (0, 0), "{name}");
+            assert_eq!(d.pwr_mask as u32, bits.pwr, "{name}");
+            assert_eq!(d.status_mask as u32, bits.status, "{name}");
+            assert_eq!(d.req_mask as u32, bits.req, "{name}");
+            assert_eq!(d.idle_mask as u32, bits.idle, "{name}");
+            assert_eq!(d.ack_mask as u32, bits.idle, "{name}");
+            assert_eq!((d.repair_status_mask, d.mem_status_mask), (0, 0), "{name}");
+        }
+    }
+}
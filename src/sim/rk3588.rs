@@ -0,0 +1,368 @@
+//! Behavioral model of the RK3588 PMU power domain registers
+
+use alloc::collections::BTreeSet;
+
+use super::{RegisterFile, SimIrq, field_bits, one};
+use crate::{
+    PowerDomain, PowerResult, RK3588,
+    registers::{
+        RegisterAccess,
+        rk3588::{
+            BUS_IDLE_ACK, BUS_IDLE_REQ0, BUS_IDLE_REQ1, BUS_IDLE_ST, CHAIN_STATUS0, CHAIN_STATUS1,
+            MEM_PWR_GATE_CON0, MEM_PWR_GATE_CON1, MEM_STATUS0, MEM_STATUS1, PMU_BUS_IDLE,
+            PMU_BUS_IDLE_REQ0, PMU_BUS_IDLE_REQ1, PMU_MEM_STATUS0, PMU_MEM_STATUS1,
+            PMU_PWR_GATE_CON0, PMU_PWR_GATE_CON1, PMU_PWR_GATE_ST, PMU_REPAIR_STATUS,
+            PWR_GATE_CON0, PWR_GATE_CON1, PWR_GATE_ST, REPAIR_STATUS,
+        },
+    },
+};
+
+/// `PWR_GATE_CON` banks
+const PWR_GATE_CON: [usize; 2] = [PWR_GATE_CON0.offset(), PWR_GATE_CON1.offset()];
+/// `MEMORY_GATE_CON` banks, laid out like `PWR_GATE_CON`
+const MEM_PWR_GATE_CON: [usize; 2] = [MEM_PWR_GATE_CON0.offset(), MEM_PWR_GATE_CON1.offset()];
+/// `BUS_IDLE_REQ` banks
+const BUS_IDLE_REQ: [usize; 2] = [BUS_IDLE_REQ0.offset(), BUS_IDLE_REQ1.offset()];
+/// `CHAIN_STATUS` banks
+const CHAIN_STATUS: [usize; 2] = [CHAIN_STATUS0.offset(), CHAIN_STATUS1.offset()];
+/// `MEM_STATUS` banks, laid out like `CHAIN_STATUS`
+const MEM_STATUS: [usize; 2] = [MEM_STATUS0.offset(), MEM_STATUS1.offset()];
+
+/// Register bits of a domain, from the typed register maps
+///
+/// The model deliberately doesn't use the driver's domain table, so a wrong
+/// bit there makes the driver and the model disagree instead of being
+/// mirrored by both.
+struct DomainBits {
+    domain: PowerDomain,
+    /// `PWR_GATE_CON` bank and bit
+    pwr: (usize, u32),
+    /// `PWR_GATE_ST` bit
+    status: u32,
+    /// `REPAIR_STATUS` bit, 0 for domains without one
+    repair: u32,
+    /// `BUS_IDLE_REQ` bank and bits, with the `BUS_IDLE_ACK`/`BUS_IDLE_ST` bits
+    req: Option<(usize, u32, u32)>,
+    /// `CHAIN_STATUS`/`MEM_STATUS` bank and bit
+    mem: Option<(usize, u32)>,
+}
+
+/// Build the [`DomainBits`] of each domain from the fields named after it
+macro_rules! domain_bits {
+    ($(
+        $domain:ident: pwr $pwr:ident[$pwr_bank:literal]
+        $(, repair $repair:ident)?
+        $(, req $req:ident[$req_bank:literal])?
+        $(, mem $mem:ident[$mem_bank:literal])?;
+    )*) => {
+        &[$(DomainBits {
+            domain: RK3588::$domain,
+            pwr: ($pwr_bank, field_bits($pwr::$domain)),
+            status: field_bits(PMU_PWR_GATE_ST::$domain),
+            repair: 0 $(| field_bits($repair::$domain))?,
+            req: one(&[$((
+                $req_bank,
+                field_bits($req::$domain),
+                field_bits(PMU_BUS_IDLE::$domain),
+            ))?]),
+            mem: one(&[$(($mem_bank, field_bits($mem::$domain)))?]),
+        }),*]
+    };
+}
+
+#[rustfmt::skip]
+static DOMAINS: &[DomainBits] = domain_bits! [
+    GPU:     pwr PMU_PWR_GATE_CON0[0], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ0[0];
+    NPU:     pwr PMU_PWR_GATE_CON0[0];
+    VCODEC:  pwr PMU_PWR_GATE_CON0[0];
+    NPUTOP:  pwr PMU_PWR_GATE_CON0[0], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ0[0], mem PMU_MEM_STATUS0[0];
+    NPU1:    pwr PMU_PWR_GATE_CON0[0], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ0[0], mem PMU_MEM_STATUS0[0];
+    NPU2:    pwr PMU_PWR_GATE_CON0[0], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ0[0], mem PMU_MEM_STATUS0[0];
+    VENC0:   pwr PMU_PWR_GATE_CON0[0], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ0[0], mem PMU_MEM_STATUS0[0];
+    VENC1:   pwr PMU_PWR_GATE_CON0[0], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ0[0], mem PMU_MEM_STATUS0[0];
+    RKVDEC0: pwr PMU_PWR_GATE_CON0[0], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ0[0], mem PMU_MEM_STATUS0[0];
+    RKVDEC1: pwr PMU_PWR_GATE_CON0[0], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ0[0], mem PMU_MEM_STATUS0[0];
+    VDPU:    pwr PMU_PWR_GATE_CON0[0], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ0[0], mem PMU_MEM_STATUS0[0];
+    RGA30:   pwr PMU_PWR_GATE_CON0[0], repair PMU_REPAIR_STATUS, mem PMU_MEM_STATUS0[0];
+    AV1:     pwr PMU_PWR_GATE_CON0[0], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ0[0], mem PMU_MEM_STATUS0[0];
+    VI:      pwr PMU_PWR_GATE_CON0[0], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ0[0], mem PMU_MEM_STATUS0[0];
+    FEC:     pwr PMU_PWR_GATE_CON0[0], repair PMU_REPAIR_STATUS, mem PMU_MEM_STATUS0[0];
+    ISP1:    pwr PMU_PWR_GATE_CON0[0], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ0[0], mem PMU_MEM_STATUS0[0];
+    RGA31:   pwr PMU_PWR_GATE_CON1[1], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ0[0], mem PMU_MEM_STATUS0[0];
+    VOP:     pwr PMU_PWR_GATE_CON1[1], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ0[0], mem PMU_MEM_STATUS0[0];
+    VO0:     pwr PMU_PWR_GATE_CON1[1], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ0[0], mem PMU_MEM_STATUS0[0];
+    VO1:     pwr PMU_PWR_GATE_CON1[1], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ1[1], mem PMU_MEM_STATUS0[0];
+    AUDIO:   pwr PMU_PWR_GATE_CON1[1], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ1[1], mem PMU_MEM_STATUS0[0];
+    PHP:     pwr PMU_PWR_GATE_CON1[1], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ1[1], mem PMU_MEM_STATUS0[0];
+    GMAC:    pwr PMU_PWR_GATE_CON1[1], repair PMU_REPAIR_STATUS, mem PMU_MEM_STATUS0[0];
+    PCIE:    pwr PMU_PWR_GATE_CON1[1], repair PMU_REPAIR_STATUS, mem PMU_MEM_STATUS0[0];
+    NVM:     pwr PMU_PWR_GATE_CON1[1], req PMU_BUS_IDLE_REQ1[1];
+    NVM0:    pwr PMU_PWR_GATE_CON1[1], repair PMU_REPAIR_STATUS, mem PMU_MEM_STATUS1[1];
+    SDIO:    pwr PMU_PWR_GATE_CON1[1], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ1[1], mem PMU_MEM_STATUS1[1];
+    USB:     pwr PMU_PWR_GATE_CON1[1], repair PMU_REPAIR_STATUS, req PMU_BUS_IDLE_REQ1[1], mem PMU_MEM_STATUS1[1];
+    SDMMC:   pwr PMU_PWR_GATE_CON1[1], repair PMU_REPAIR_STATUS, mem PMU_MEM_STATUS1[1];
+];
+
+/// OR together the bits reported by every domain
+fn collect_bits(bits: impl Fn(&DomainBits) -> u32) -> u32 {
+    DOMAINS.iter().fold(0, |acc, d| acc | bits(d))
+}
+
+/// Simulated RK3588 PMU
+///
+/// Models the registers of [`registers::rk3588`](crate::registers::rk3588),
+/// with the bits of each domain taken from the fields named after it:
+///
+/// - `PWR_GATE_CON`, `BUS_IDLE_REQ` and `MEMORY_GATE_CON` are hi-word
+///   write-enable registers; writes without the matching write-enable bit are
+///   ignored.
+/// - `PWR_GATE_ST` reads 1 for domains that are powered off.
+/// - `REPAIR_STATUS` reads 1 for domains that are powered on, and
+///   `CHAIN_STATUS` reports their memory chains as up. `MEM_STATUS` reports
+///   the memories gated through `MEMORY_GATE_CON`.
+/// - `BUS_IDLE_ACK` and `BUS_IDLE_ST` follow the idle request bits.
+///
+/// Power and idle requests complete on the write that issues them. Writes to
+/// status registers are dropped. Any other offset behaves as plain storage.
+/// All domains come out of reset powered on and not idle, like the silicon.
+///
+/// The PMU interrupt is not modeled unless a test wires it up with
+/// [`with_irq`](Self::with_irq).
+pub struct Rk3588Pmu {
+    regs: RegisterFile,
+    irq: Option<SimIrq>,
+}
+
+impl Rk3588Pmu {
+    /// Create a PMU model in its reset state
+    pub fn new() -> Self {
+        let hiword_regs: BTreeSet<usize> = [PWR_GATE_CON, MEM_PWR_GATE_CON, BUS_IDLE_REQ]
+            .into_iter()
+            .flatten()
+            .collect();
+
+        Self {
+            regs: RegisterFile::new(hiword_regs),
+            irq: None,
+        }
+    }
+
+    /// Raise interrupts through the registers of `irq`
+    ///
+    /// The status register latches `irq.pwr_gate` when a write switches the
+    /// power of a domain and `irq.bus_idle` when it changes an idle request,
+    /// and clears the bits written as 1. The enable register is a hi-word
+    /// write-enable register with every source disabled out of reset. Both
+    /// offsets must be unused by the model.
+    pub fn with_irq(mut self, irq: SimIrq) -> Self {
+        let layout = irq.layout;
+        self.regs.hiword_regs.insert(layout.enable_offset);
+        if layout.masked_when_set {
+            self.regs.write(layout.enable_offset, 0xffff_ffff);
+        }
+        self.irq = Some(irq);
+        self
+    }
+
+    /// Check whether the model has `domain` powered on
+    ///
+    /// # Panics
+    /// Panics if `domain` is not an RK3588 power domain
+    pub fn is_domain_powered(&self, domain: PowerDomain) -> bool {
+        self.domain_on(Self::domain(domain))
+    }
+
+    /// Check whether the model has the bus of `domain` in idle
+    ///
+    /// # Panics
+    /// Panics if `domain` is not an RK3588 power domain
+    pub fn is_domain_idle(&self, domain: PowerDomain) -> bool {
+        self.idle_requested(Self::domain(domain))
+    }
+
+    /// Check whether the model asserts its interrupt line
+    ///
+    /// True while the status register holds an event whose source is
+    /// enabled. Always false without [`with_irq`](Self::with_irq).
+    pub fn is_irq_asserted(&self) -> bool {
+        self.irq.is_some_and(|irq| {
+            let layout = irq.layout;
+            let mut enabled = self.regs.get(layout.enable_offset);
+            if layout.masked_when_set {
+                enabled = !enabled;
+            }
+            self.regs.get(layout.status_offset) & enabled & 0xffff != 0
+        })
+    }
+
+    /// Read a register the way the driver would see it
+    pub fn peek(&self, offset: usize) -> u32 {
+        self.status(offset).unwrap_or_else(|| self.regs.get(offset))
+    }
+
+    fn domain(domain: PowerDomain) -> &'static DomainBits {
+        DOMAINS
+            .iter()
+            .find(|d| d.domain == domain)
+            .expect("not an RK3588 power domain")
+    }
+
+    fn domain_on(&self, d: &DomainBits) -> bool {
+        let (bank, pwr) = d.pwr;
+        self.regs.get(PWR_GATE_CON[bank]) & pwr == 0
+    }
+
+    fn idle_requested(&self, d: &DomainBits) -> bool {
+        d.req
+            .is_some_and(|(bank, req, _)| self.regs.get(BUS_IDLE_REQ[bank]) & req == req)
+    }
+
+    fn memory_gated(&self, d: &DomainBits) -> bool {
+        let (bank, pwr) = d.pwr;
+        self.regs.get(MEM_PWR_GATE_CON[bank]) & pwr != 0
+    }
+
+    /// Latch the interrupt events of a control register write
+    fn latch_irq(&self, offset: usize, changed: u32) {
+        let Some(irq) = self.irq else {
+            return;
+        };
+        let events = if changed == 0 {
+            0
+        } else if PWR_GATE_CON.contains(&offset) {
+            irq.pwr_gate
+        } else if BUS_IDLE_REQ.contains(&offset) {
+            irq.bus_idle
+        } else {
+            0
+        };
+        if events != 0 {
+            let status_offset = irq.layout.status_offset;
+            let status = self.regs.get(status_offset);
+            self.regs.write(status_offset, status | events);
+        }
+    }
+
+    /// Compute a status register from the control registers
+    ///
+    /// Returns `None` if `offset` is not a status register.
+    fn status(&self, offset: usize) -> Option<u32> {
+        let bank_of = |banks: [usize; 2]| banks.iter().position(|&bank| bank == offset);
+
+        let bits = if offset == PWR_GATE_ST.offset() {
+            collect_bits(|d| if self.domain_on(d) { 0 } else { d.status })
+        } else if offset == REPAIR_STATUS.offset() {
+            collect_bits(|d| if self.domain_on(d) { d.repair } else { 0 })
+        } else if offset == BUS_IDLE_ACK.offset() || offset == BUS_IDLE_ST.offset() {
+            collect_bits(|d| match d.req {
+                Some((_, _, idle)) if self.idle_requested(d) => idle,
+                _ => 0,
+            })
+        } else if let Some(bank) = bank_of(CHAIN_STATUS) {
+            collect_bits(|d| match d.mem {
+                Some((mem_bank, mem)) if mem_bank == bank && self.domain_on(d) => mem,
+                _ => 0,
+            })
+        } else {
+            let bank = bank_of(MEM_STATUS)?;
+            collect_bits(|d| match d.mem {
+                Some((mem_bank, mem)) if mem_bank == bank && self.memory_gated(d) => mem,
+                _ => 0,
+            })
+        };
+
+        Some(bits)
+    }
+}
+
+impl Default for Rk3588Pmu {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl RegisterAccess for Rk3588Pmu {
+    fn read_u32(&self, offset: usize) -> PowerResult<u32> {
+        Ok(self.peek(offset))
+    }
+
+    fn write_u32(&self, offset: usize, value: u32) -> PowerResult<()> {
+        if self
+            .irq
+            .is_some_and(|irq| irq.layout.status_offset == offset)
+        {
+            // Write 1 to clear
+            self.regs.write(offset, self.regs.get(offset) & !value);
+        } else if self.status(offset).is_none() {
+            // Status registers are read-only
+            let previous = self.regs.get(offset);
+            self.regs.write(offset, value);
+            self.latch_irq(offset, previous ^ self.regs.get(offset));
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::variants::rk3588::pmu_info;
+
+    #[test]
+    fn test_domain_table_matches_register_maps() {
+        let info = pmu_info();
+        assert_eq!(info.pwr_offset as usize, PWR_GATE_CON[0]);
+        assert_eq!(info.status_offset as usize, PWR_GATE_ST.offset());
+        assert_eq!(info.req_offset as usize, BUS_IDLE_REQ[0]);
+        assert_eq!(info.idle_offset as usize, BUS_IDLE_ST.offset());
+        assert_eq!(info.ack_offset as usize, BUS_IDLE_ACK.offset());
+        assert_eq!(info.mem_pwr_offset as usize, MEM_PWR_GATE_CON[0]);
+        assert_eq!(info.chain_status_offset as usize, CHAIN_STATUS[0]);
+        assert_eq!(info.mem_status_offset as usize, MEM_STATUS[0]);
+        assert_eq!(info.repair_status_offset as usize, REPAIR_STATUS.offset());
+        assert_eq!(info.domains.len(), DOMAINS.len());
+
+        for bits in DOMAINS {
+            let d = &info.domains[&bits.domain];
+            let name = d.name;
+            let (bank, pwr) = bits.pwr;
+            assert_eq!(
+                (info.pwr_offset + d.pwr_offset) as usize,
+                PWR_GATE_CON[bank],
+                "{name}"
+            );
+            assert_eq!(d.pwr_mask as u32, pwr, "{name}");
+
+            // Each domain reports its state through exactly one register
+            assert_eq!(d.repair_status_mask as u32, bits.repair, "{name}");
+            let status = if bits.repair == 0 { bits.status } else { 0 };
+            assert_eq!(d.status_mask as u32, status, "{name}");
+
+            match bits.req {
+                Some((bank, req, idle)) => {
+                    assert_eq!(
+                        (info.req_offset + d.req_offset) as usize,
+                        BUS_IDLE_REQ[bank],
+                        "{name}"
+                    );
+                    assert_eq!(d.req_mask as u32, req, "{name}");
+                    assert_eq!(d.idle_mask as u32, idle, "{name}");
+                    assert_eq!(d.ack_mask as u32, idle, "{name}");
+                }
+                None => assert_eq!((d.req_mask, d.idle_mask, d.ack_mask), (0, 0, 0), "{name}"),
+            }
+
+            match bits.mem {
+                Some((bank, mem)) => {
+                    assert_eq!(
+                        (info.chain_status_offset + d.mem_offset) as usize,
+                        CHAIN_STATUS[bank],
+                        "{name}"
+                    );
+                    assert_eq!(d.mem_status_mask as u32, mem, "{name}");
+                }
+                None => assert_eq!(d.mem_status_mask, 0, "{name}"),
+            }
+        }
+    }
+}
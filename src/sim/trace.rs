@@ -0,0 +1,334 @@
+//! Register access tracing and replay
+//!
+//! [`TraceRecorder`] wraps a register backend and records every access,
+//! tagged with the sequencer step that issued it. A recorded [`Trace`] can be
+//! serialized to a compact binary form, stored as a golden file and later fed
+//! back to the driver through [`TraceReplayer`], which reports the first
+//! access that diverges from the recording.
+
+use alloc::vec::Vec;
+use core::cell::{Cell, RefCell};
+
+use crate::{PowerDomain, PowerError, PowerResult, PowerStep, registers::RegisterAccess};
+
+/// Magic bytes at the start of a serialized trace
+const TRACE_MAGIC: &[u8; 4] = b"RKPT";
+/// Serialized trace format version
+const TRACE_VERSION: u8 = 1;
+/// Size of the serialized trace header (magic, version, event count)
+const HEADER_LEN: usize = 9;
+/// Size of one serialized trace event
+const EVENT_LEN: usize = 12;
+
+/// Direction of a register access
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Access {
+    Read,
+    Write,
+}
+
+/// A single recorded register access
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct TraceEvent {
+    /// Read or write
+    pub access: Access,
+    /// Register offset from the PMU base
+    pub offset: u32,
+    /// Value read or written
+    pub value: u32,
+    /// Domain and sequencer step that issued the access, if any
+    pub step: Option<(PowerDomain, PowerStep)>,
+}
+
+/// A recorded sequence of register accesses
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Trace {
+    events: Vec<TraceEvent>,
+}
+
+impl Trace {
+    /// Create an empty trace
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the recorded events in access order
+    pub fn events(&self) -> &[TraceEvent] {
+        &self.events
+    }
+
+    /// Append an event
+    pub fn push(&mut self, event: TraceEvent) {
+        self.events.push(event);
+    }
+
+    /// Find the index of the first event that differs from `other`
+    ///
+    /// If one trace is a prefix of the other, the length of the shorter one
+    /// is returned. Returns `None` if both traces are identical.
+    pub fn first_divergence(&self, other: &Trace) -> Option<usize> {
+        let common = self.events.len().min(other.events.len());
+        (0..common)
+            .find(|&i| self.events[i] != other.events[i])
+            .or((self.events.len() != other.events.len()).then_some(common))
+    }
+
+    /// Serialize the trace
+    ///
+    /// The format is a 9-byte header (`"RKPT"`, version, little-endian event
+    /// count) followed by 12 bytes per event: flags, step, domain ID (u16),
+    /// offset (u32) and value (u32), all little-endian.
+    pub fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = Vec::with_capacity(HEADER_LEN + self.events.len() * EVENT_LEN);
+        bytes.extend_from_slice(TRACE_MAGIC);
+        bytes.push(TRACE_VERSION);
+        bytes.extend_from_slice(&(self.events.len() as u32).to_le_bytes());
+
+        for event in &self.events {
+            let mut flags = 0u8;
+            if event.access == Access::Write {
+                flags |= 1 << 0;
+            }
+            let (domain, step) = match event.step {
+                Some((domain, step)) => {
+                    flags |= 1 << 1;
+                    (domain.id() as u16, step_to_u8(step))
+                }
+                None => (0, 0),
+            };
+            bytes.push(flags);
+            bytes.push(step);
+            bytes.extend_from_slice(&domain.to_le_bytes());
+            bytes.extend_from_slice(&event.offset.to_le_bytes());
+            bytes.extend_from_slice(&event.value.to_le_bytes());
+        }
+
+        bytes
+    }
+
+    /// Deserialize a trace produced by [`to_bytes`](Self::to_bytes)
+    ///
+    /// Returns `None` if the data is truncated, has a different format
+    /// version or contains an unknown step.
+    pub fn from_bytes(bytes: &[u8]) -> Option<Self> {
+        if bytes.len() < HEADER_LEN || &bytes[..4] != TRACE_MAGIC || bytes[4] != TRACE_VERSION {
+            return None;
+        }
+        let count = u32::from_le_bytes(bytes[5..9].try_into().ok()?) as usize;
+        let body = &bytes[HEADER_LEN..];
+        if body.len() != count.checked_mul(EVENT_LEN)? {
+            return None;
+        }
+
+        let events = body
+            .as_chunks::<EVENT_LEN>()
+            .0
+            .iter()
+            .map(|raw| {
+                let flags = raw[0];
+                let access = if flags & (1 << 0) != 0 {
+                    Access::Write
+                } else {
+                    Access::Read
+                };
+                let step = if flags & (1 << 1) != 0 {
+                    let domain = u16::from_le_bytes([raw[2], raw[3]]);
+                    Some((PowerDomain::new(domain as usize), step_from_u8(raw[1])?))
+                } else {
+                    None
+                };
+                Some(TraceEvent {
+                    access,
+                    offset: u32::from_le_bytes(raw[4..8].try_into().ok()?),
+                    value: u32::from_le_bytes(raw[8..12].try_into().ok()?),
+                    step,
+                })
+            })
+            .collect::<Option<Vec<_>>>()?;
+
+        Some(Self { events })
+    }
+}
+
+fn step_to_u8(step: PowerStep) -> u8 {
+    match step {
+        PowerStep::QosSave => 0,
+        PowerStep::MemoryPower => 1,
+        PowerStep::IdleRequest => 2,
+        PowerStep::PowerWrite => 3,
+        PowerStep::RepairWait => 4,
+        PowerStep::PowerStable => 5,
+        PowerStep::QosRestore => 6,
+        PowerStep::ClockUngate => 7,
+    }
+}
+
+fn step_from_u8(raw: u8) -> Option<PowerStep> {
+    Some(match raw {
+        0 => PowerStep::QosSave,
+        1 => PowerStep::MemoryPower,
+        2 => PowerStep::IdleRequest,
+        3 => PowerStep::PowerWrite,
+        4 => PowerStep::RepairWait,
+        5 => PowerStep::PowerStable,
+        6 => PowerStep::QosRestore,
+        7 => PowerStep::ClockUngate,
+        _ => return None,
+    })
+}
+
+/// Register backend that records every access made through it
+pub struct TraceRecorder<R: RegisterAccess> {
+    inner: R,
+    step: Cell<Option<(PowerDomain, PowerStep)>>,
+    trace: RefCell<Trace>,
+}
+
+impl<R: RegisterAccess> TraceRecorder<R> {
+    /// Wrap `inner` with an empty trace
+    pub fn new(inner: R) -> Self {
+        Self {
+            inner,
+            step: Cell::new(None),
+            trace: RefCell::new(Trace::new()),
+        }
+    }
+
+    /// Get a copy of the accesses recorded so far
+    pub fn trace(&self) -> Trace {
+        self.trace.borrow().clone()
+    }
+
+    /// Take the accesses recorded so far, leaving the trace empty
+    pub fn take_trace(&self) -> Trace {
+        self.trace.take()
+    }
+
+    /// Get the wrapped backend
+    pub fn inner(&self) -> &R {
+        &self.inner
+    }
+
+    fn record(&self, access: Access, offset: usize, value: u32) {
+        self.trace.borrow_mut().push(TraceEvent {
+            access,
+            offset: offset as u32,
+            value,
+            step: self.step.get(),
+        });
+    }
+}
+
+impl<R: RegisterAccess> RegisterAccess for TraceRecorder<R> {
+    fn read_u32(&self, offset: usize) -> PowerResult<u32> {
+        let value = self.inner.read_u32(offset)?;
+        self.record(Access::Read, offset, value);
+        Ok(value)
+    }
+
+    fn write_u32(&self, offset: usize, value: u32) -> PowerResult<()> {
+        self.inner.write_u32(offset, value)?;
+        self.record(Access::Write, offset, value);
+        Ok(())
+    }
+
+    fn sequencer_step(&self, step: Option<(PowerDomain, PowerStep)>) {
+        self.step.set(step);
+        self.inner.sequencer_step(step);
+    }
+}
+
+/// The first access that did not match a replayed trace
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Divergence {
+    /// Index of the event in the trace
+    pub index: usize,
+    /// Recorded event, or `None` if the trace was already exhausted
+    pub expected: Option<TraceEvent>,
+    /// Access the driver actually made (reads carry a value of 0)
+    pub actual: TraceEvent,
+}
+
+/// Register backend that plays a recorded trace back to the driver
+///
+/// Reads return the recorded values. Every access must match the next event
+/// of the trace in direction, offset and step, and writes must also match in
+/// value. The first mismatch is kept as a [`Divergence`] and fails that and
+/// every later access with [`PowerError::HardwareError`].
+pub struct TraceReplayer {
+    trace: Trace,
+    position: Cell<usize>,
+    step: Cell<Option<(PowerDomain, PowerStep)>>,
+    divergence: Cell<Option<Divergence>>,
+}
+
+impl TraceReplayer {
+    /// Create a replayer positioned at the start of `trace`
+    pub fn new(trace: Trace) -> Self {
+        Self {
+            trace,
+            position: Cell::new(0),
+            step: Cell::new(None),
+            divergence: Cell::new(None),
+        }
+    }
+
+    /// Get the first divergence from the trace, if any
+    pub fn divergence(&self) -> Option<Divergence> {
+        self.divergence.get()
+    }
+
+    /// Check whether the whole trace was replayed without divergence
+    pub fn is_complete(&self) -> bool {
+        self.divergence.get().is_none() && self.position.get() == self.trace.events.len()
+    }
+
+    fn replay(&self, access: Access, offset: usize, value: u32) -> PowerResult<u32> {
+        if self.divergence.get().is_some() {
+            return Err(PowerError::HardwareError);
+        }
+
+        let index = self.position.get();
+        let expected = self.trace.events.get(index).copied();
+        let actual = TraceEvent {
+            access,
+            offset: offset as u32,
+            value,
+            step: self.step.get(),
+        };
+
+        match expected {
+            Some(e)
+                if e.access == access
+                    && e.offset == actual.offset
+                    && e.step == actual.step
+                    && (access == Access::Read || e.value == value) =>
+            {
+                self.position.set(index + 1);
+                Ok(e.value)
+            }
+            _ => {
+                self.divergence.set(Some(Divergence {
+                    index,
+                    expected,
+                    actual,
+                }));
+                Err(PowerError::HardwareError)
+            }
+        }
+    }
+}
+
+impl RegisterAccess for TraceReplayer {
+    fn read_u32(&self, offset: usize) -> PowerResult<u32> {
+        self.replay(Access::Read, offset, 0)
+    }
+
+    fn write_u32(&self, offset: usize, value: u32) -> PowerResult<()> {
+        self.replay(Access::Write, offset, value).map(|_| ())
+    }
+
+    fn sequencer_step(&self, step: Option<(PowerDomain, PowerStep)>) {
+        self.step.set(step);
+    }
+}
@@ -0,0 +1,159 @@
+//! Power transition latency statistics
+//!
+//! When enabled with [`RockchipPM::with_latency_stats`](crate::RockchipPM::with_latency_stats),
+//! the power sequencer timestamps every [`PowerStep`] with the driver's
+//! [`TimeSource`](crate::time::TimeSource) and accumulates the durations per
+//! domain, per step and per whole transition.
+
+use alloc::collections::{BTreeMap, BTreeSet};
+
+use crate::{PowerDomain, PowerStep};
+
+/// Accumulated duration samples (in microseconds)
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Latency {
+    count: u64,
+    total_us: u64,
+    min_us: u64,
+    max_us: u64,
+}
+
+impl Latency {
+    fn new(us: u64) -> Self {
+        Self {
+            count: 1,
+            total_us: us,
+            min_us: us,
+            max_us: us,
+        }
+    }
+
+    fn add(&mut self, us: u64) {
+        self.count += 1;
+        self.total_us = self.total_us.saturating_add(us);
+        self.min_us = self.min_us.min(us);
+        self.max_us = self.max_us.max(us);
+    }
+
+    /// Get the number of samples
+    pub fn count(&self) -> u64 {
+        self.count
+    }
+
+    /// Get the sum of all samples
+    pub fn total_us(&self) -> u64 {
+        self.total_us
+    }
+
+    /// Get the shortest sample
+    pub fn min_us(&self) -> u64 {
+        self.min_us
+    }
+
+    /// Get the longest sample
+    pub fn max_us(&self) -> u64 {
+        self.max_us
+    }
+
+    /// Get the average sample, rounded down
+    pub fn avg_us(&self) -> u64 {
+        self.total_us / self.count
+    }
+}
+
+/// Latency of power transitions per domain and per step
+///
+/// Every run of a step is one sample, so steps that run twice in a sequence
+/// ([`PowerStep::ClockUngate`]) count twice. Steps of a failed transition are
+/// recorded up to and including the failing one, but only successful
+/// transitions count towards [`transition`](Self::transition).
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct LatencyStats {
+    steps: BTreeMap<(PowerDomain, PowerStep), Latency>,
+    transitions: BTreeMap<(PowerDomain, bool), Latency>,
+}
+
+impl LatencyStats {
+    /// Create empty statistics
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the latency of `step` for `domain`, in both directions
+    pub fn step(&self, domain: PowerDomain, step: PowerStep) -> Option<Latency> {
+        self.steps.get(&(domain, step)).copied()
+    }
+
+    /// Get the latency of every step recorded for `domain`
+    pub fn domain_steps(&self, domain: PowerDomain) -> impl Iterator<Item = (PowerStep, Latency)> {
+        self.steps
+            .range((domain, PowerStep::ClockUngate)..=(domain, PowerStep::QosRestore))
+            .map(|(&(_, step), &latency)| (step, latency))
+    }
+
+    /// Get the latency of complete power-on or power-off transitions of `domain`
+    ///
+    /// # Arguments
+    /// * `domain` - Power domain
+    /// * `power_on` - True for power-on transitions, false for power-off
+    pub fn transition(&self, domain: PowerDomain, power_on: bool) -> Option<Latency> {
+        self.transitions.get(&(domain, power_on)).copied()
+    }
+
+    /// Get the domains with recorded samples
+    pub fn domains(&self) -> impl Iterator<Item = PowerDomain> {
+        let domains: BTreeSet<_> = self
+            .steps
+            .keys()
+            .map(|&(domain, _)| domain)
+            .chain(self.transitions.keys().map(|&(domain, _)| domain))
+            .collect();
+        domains.into_iter()
+    }
+
+    /// Drop all samples
+    pub fn clear(&mut self) {
+        self.steps.clear();
+        self.transitions.clear();
+    }
+
+    pub(crate) fn record_step(&mut self, domain: PowerDomain, step: PowerStep, us: u64) {
+        record(&mut self.steps, (domain, step), us);
+    }
+
+    pub(crate) fn record_transition(&mut self, domain: PowerDomain, power_on: bool, us: u64) {
+        record(&mut self.transitions, (domain, power_on), us);
+    }
+}
+
+fn record<K: Ord>(map: &mut BTreeMap<K, Latency>, key: K, us: u64) {
+    map.entry(key)
+        .and_modify(|latency| latency.add(us))
+        .or_insert_with(|| Latency::new(us));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::RK3588;
+
+    #[test]
+    fn test_latency_accumulation() {
+        let mut stats = LatencyStats::new();
+        stats.record_step(RK3588::AV1, PowerStep::IdleRequest, 30);
+        stats.record_step(RK3588::AV1, PowerStep::IdleRequest, 10);
+        stats.record_step(RK3588::AV1, PowerStep::IdleRequest, 20);
+        stats.record_step(RK3588::GPU, PowerStep::PowerWrite, 5);
+
+        let idle = stats.step(RK3588::AV1, PowerStep::IdleRequest).unwrap();
+        assert_eq!(
+            (idle.count(), idle.min_us(), idle.max_us(), idle.avg_us()),
+            (3, 10, 30, 20)
+        );
+        assert_eq!(stats.domain_steps(RK3588::AV1).count(), 1);
+        assert_eq!(
+            stats.domains().collect::<alloc::vec::Vec<_>>(),
+            [RK3588::GPU, RK3588::AV1]
+        );
+    }
+}
@@ -0,0 +1,581 @@
+//! Time sources and timeouts for the power sequencer poll loops
+//!
+//! Every wait in a power transition polls a status register until it reaches
+//! the expected value or a budget in microseconds runs out. The budgets come
+//! from a [`TimeoutPolicy`], which defaults to the 10 ms limits of the Linux
+//! `pm_domains.c` driver. The clock used to measure them is a [`TimeSource`],
+//! which [`RockchipPM`](crate::RockchipPM) accepts through
+//! [`with_time_source`](crate::RockchipPM::with_time_source). An
+//! [`IdleRetryPolicy`] lets a bus idle request whose acknowledgment timed out
+//! be re-issued after a backoff instead of failing the transition.
+//!
+//! The poll loops are futures that yield to the executor after every
+//! unsuccessful poll. The blocking API drives them to completion on the
+//! spot with [`block_on`], so both share one implementation.
+
+use alloc::{boxed::Box, collections::BTreeMap};
+use core::{
+    cell::Cell,
+    pin::{Pin, pin},
+    task::{Context, Poll, Waker},
+};
+
+use crate::{
+    PowerDomain, PowerResult, RkBoard,
+    irq::{IrqNotifier, IrqSlot},
+    variants,
+};
+
+/// Default budget of every poll loop (in microseconds)
+pub const DEFAULT_TIMEOUT_US: u64 = 10_000;
+
+/// Monotonic microsecond clock with an optional delay primitive
+pub trait TimeSource {
+    /// Get the current time in microseconds
+    ///
+    /// Only differences between two readings are used, so the epoch is
+    /// arbitrary.
+    fn now_us(&self) -> u64;
+
+    /// Wait for at least `us` microseconds
+    ///
+    /// The default implementation spins on [`now_us`](Self::now_us).
+    fn delay_us(&self, us: u64) {
+        let start = self.now_us();
+        while self.now_us().wrapping_sub(start) < us {
+            core::hint::spin_loop();
+        }
+    }
+
+    /// Wake `waker` once [`now_us`](Self::now_us) reaches `deadline_us`
+    ///
+    /// Async waits only suspend on the PMU interrupt if the clock can wake
+    /// them at their timeout, so a missing interrupt can't hang them. The
+    /// same task may be armed again for the same deadline every time an
+    /// interrupt resumes it; extra wakeups are harmless.
+    ///
+    /// The default implementation has no timer and returns false, which
+    /// keeps async waits polling.
+    ///
+    /// # Returns
+    /// True if `waker` will be woken at the deadline
+    fn wake_at(&self, deadline_us: u64, waker: &Waker) -> bool {
+        let _ = (deadline_us, waker);
+        false
+    }
+}
+
+impl<T: TimeSource + ?Sized> TimeSource for &T {
+    fn now_us(&self) -> u64 {
+        (**self).now_us()
+    }
+
+    fn delay_us(&self, us: u64) {
+        (**self).delay_us(us)
+    }
+
+    fn wake_at(&self, deadline_us: u64, waker: &Waker) -> bool {
+        (**self).wake_at(deadline_us, waker)
+    }
+}
+
+/// Clock that advances one microsecond every time it is read
+///
+/// Without a real timer, a poll loop reads the clock once per iteration, so
+/// a budget of `n` microseconds allows roughly `n` polls. This is the default
+/// when no hardware or OS clock is available, and makes timeouts
+/// deterministic in tests.
+#[derive(Debug, Default)]
+pub struct PollCounter {
+    ticks: Cell<u64>,
+}
+
+impl PollCounter {
+    /// Create a counter starting at 0
+    pub const fn new() -> Self {
+        Self {
+            ticks: Cell::new(0),
+        }
+    }
+}
+
+impl TimeSource for PollCounter {
+    fn now_us(&self) -> u64 {
+        let now = self.ticks.get();
+        self.ticks.set(now + 1);
+        now
+    }
+}
+
+/// ARM generic timer (`CNTVCT_EL0` scaled by `CNTFRQ_EL0`)
+#[cfg(target_arch = "aarch64")]
+#[derive(Debug, Default, Clone, Copy)]
+pub struct GenericTimer;
+
+#[cfg(target_arch = "aarch64")]
+impl TimeSource for GenericTimer {
+    fn now_us(&self) -> u64 {
+        let count: u64;
+        let freq: u64;
+        unsafe {
+            core::arch::asm!("isb", "mrs {}, cntvct_el0", out(reg) count);
+            core::arch::asm!("mrs {}, cntfrq_el0", out(reg) freq);
+        }
+        if freq == 0 {
+            return count;
+        }
+        (count as u128 * 1_000_000 / freq as u128) as u64
+    }
+}
+
+/// Clock based on [`std::time::Instant`]
+#[cfg(feature = "std")]
+#[derive(Debug, Clone, Copy)]
+pub struct StdClock {
+    start: std::time::Instant,
+}
+
+#[cfg(feature = "std")]
+impl StdClock {
+    /// Create a clock counting from now
+    pub fn new() -> Self {
+        Self {
+            start: std::time::Instant::now(),
+        }
+    }
+}
+
+#[cfg(feature = "std")]
+impl Default for StdClock {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+#[cfg(feature = "std")]
+impl TimeSource for StdClock {
+    fn now_us(&self) -> u64 {
+        self.start.elapsed().as_micros() as u64
+    }
+}
+
+/// Poll loops of a power transition with their own timeout
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub enum TimeoutStep {
+    /// Wait for domain memory to settle after switching its power
+    MemoryStable,
+    /// Wait for the bus idle acknowledgment
+    IdleAck,
+    /// Wait for the bus idle status to follow the request
+    IdleState,
+    /// Wait for memory repair to complete after power on
+    Repair,
+    /// Wait for the power status to match the requested state
+    PowerStable,
+}
+
+/// Timeout budgets for the power sequencer poll loops
+///
+/// A budget is looked up from the most specific setting available:
+///
+/// 1. the domain and step ([`set_domain_step`](Self::set_domain_step))
+/// 2. the domain ([`set_domain`](Self::set_domain))
+/// 3. the step ([`set_step`](Self::set_step))
+/// 4. the global default ([`set_default`](Self::set_default))
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeoutPolicy {
+    default_us: u64,
+    steps: BTreeMap<TimeoutStep, u64>,
+    domains: BTreeMap<PowerDomain, u64>,
+    domain_steps: BTreeMap<(PowerDomain, TimeoutStep), u64>,
+}
+
+impl TimeoutPolicy {
+    /// Create a policy with the same budget for every wait
+    ///
+    /// # Arguments
+    /// * `default_us` - Budget in microseconds
+    pub fn new(default_us: u64) -> Self {
+        Self {
+            default_us,
+            steps: BTreeMap::new(),
+            domains: BTreeMap::new(),
+            domain_steps: BTreeMap::new(),
+        }
+    }
+
+    /// Create the default policy for a chip variant
+    pub fn for_board(board: RkBoard) -> Self {
+        match board {
+            RkBoard::Rk3568 => variants::rk3568::timeout_policy(),
+            RkBoard::Rk3588 => variants::rk3588::timeout_policy(),
+        }
+    }
+
+    /// Set the global default budget
+    pub fn set_default(&mut self, timeout_us: u64) -> &mut Self {
+        self.default_us = timeout_us;
+        self
+    }
+
+    /// Set the budget of `step` for all domains
+    pub fn set_step(&mut self, step: TimeoutStep, timeout_us: u64) -> &mut Self {
+        self.steps.insert(step, timeout_us);
+        self
+    }
+
+    /// Set the budget of every step of `domain`
+    pub fn set_domain(&mut self, domain: PowerDomain, timeout_us: u64) -> &mut Self {
+        self.domains.insert(domain, timeout_us);
+        self
+    }
+
+    /// Set the budget of `step` for `domain`
+    pub fn set_domain_step(
+        &mut self,
+        domain: PowerDomain,
+        step: TimeoutStep,
+        timeout_us: u64,
+    ) -> &mut Self {
+        self.domain_steps.insert((domain, step), timeout_us);
+        self
+    }
+
+    /// Get the budget of `step` for `domain` (in microseconds)
+    pub fn timeout_us(&self, domain: PowerDomain, step: TimeoutStep) -> u64 {
+        self.domain_steps
+            .get(&(domain, step))
+            .or_else(|| self.domains.get(&domain))
+            .or_else(|| self.steps.get(&step))
+            .copied()
+            .unwrap_or(self.default_us)
+    }
+}
+
+impl Default for TimeoutPolicy {
+    fn default() -> Self {
+        Self::new(DEFAULT_TIMEOUT_US)
+    }
+}
+
+/// Retry of a bus idle request whose acknowledgment timed out
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct IdleRetry {
+    /// Number of times the request is re-issued after the first attempt
+    pub retries: u32,
+    /// Delay before the first retry (in microseconds), doubled for every
+    /// further retry
+    pub backoff_us: u64,
+    /// Drive the request bit back to its previous state before re-issuing it
+    pub toggle_request: bool,
+}
+
+impl IdleRetry {
+    /// Fail on the first acknowledgment timeout
+    pub const NONE: Self = Self::new(0, 0);
+
+    /// Re-issue the request up to `retries` times
+    ///
+    /// # Arguments
+    /// * `retries` - Number of retries after the first attempt
+    /// * `backoff_us` - Delay before the first retry, doubled for every
+    ///   further retry
+    pub const fn new(retries: u32, backoff_us: u64) -> Self {
+        Self {
+            retries,
+            backoff_us,
+            toggle_request: false,
+        }
+    }
+
+    /// Toggle the request bit before every retry
+    pub const fn with_toggle(mut self, toggle_request: bool) -> Self {
+        self.toggle_request = toggle_request;
+        self
+    }
+
+    /// Get the delay before retry number `retry` (counting from 1)
+    pub fn delay_before(&self, retry: u32) -> u64 {
+        let factor = 1u64
+            .checked_shl(retry.saturating_sub(1))
+            .unwrap_or(u64::MAX);
+        self.backoff_us.saturating_mul(factor)
+    }
+}
+
+impl Default for IdleRetry {
+    fn default() -> Self {
+        Self::NONE
+    }
+}
+
+/// Bus idle retry settings, with per-domain overrides
+///
+/// By default no domain retries, which matches the Linux driver.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct IdleRetryPolicy {
+    default: IdleRetry,
+    domains: BTreeMap<PowerDomain, IdleRetry>,
+}
+
+impl IdleRetryPolicy {
+    /// Create a policy with the same retry settings for every domain
+    pub const fn new(default: IdleRetry) -> Self {
+        Self {
+            default,
+            domains: BTreeMap::new(),
+        }
+    }
+
+    /// Set the retry settings of domains without an override
+    pub fn set_default(&mut self, retry: IdleRetry) -> &mut Self {
+        self.default = retry;
+        self
+    }
+
+    /// Set the retry settings of `domain`
+    pub fn set_domain(&mut self, domain: PowerDomain, retry: IdleRetry) -> &mut Self {
+        self.domains.insert(domain, retry);
+        self
+    }
+
+    /// Get the retry settings of `domain`
+    pub fn retry(&self, domain: PowerDomain) -> IdleRetry {
+        self.domains.get(&domain).copied().unwrap_or(self.default)
+    }
+}
+
+impl Default for IdleRetryPolicy {
+    fn default() -> Self {
+        Self::new(IdleRetry::NONE)
+    }
+}
+
+/// Clock, budgets and retry settings for the waits of one domain transition
+pub(crate) struct Waiter<'a> {
+    pub time: &'a dyn TimeSource,
+    pub policy: &'a TimeoutPolicy,
+    pub retry: &'a IdleRetryPolicy,
+    /// Wakes suspended waits on PMU interrupts, if interrupts are configured
+    pub irq: Option<&'a IrqNotifier>,
+    pub domain: PowerDomain,
+}
+
+impl Waiter<'_> {
+    /// Poll `done` within the budget of `step`, see [`poll_until`]
+    pub async fn poll(
+        &self,
+        step: TimeoutStep,
+        done: impl FnMut() -> PowerResult<bool>,
+    ) -> PowerResult<bool> {
+        let budget_us = self.policy.timeout_us(self.domain, step);
+        poll_until(self.time, budget_us, self.irq, done).await
+    }
+
+    /// Get the bus idle retry settings of the domain
+    pub fn idle_retry(&self) -> IdleRetry {
+        self.retry.retry(self.domain)
+    }
+
+    /// Wait `us` microseconds, yielding to the executor meanwhile
+    pub async fn delay(&self, us: u64) {
+        let start = self.time.now_us();
+        while self.time.now_us().wrapping_sub(start) < us {
+            Suspend::yield_now().await;
+        }
+    }
+}
+
+/// Get the best time source available on this target
+///
+/// The ARM generic timer on aarch64, the OS clock with the `std` feature, and
+/// [`PollCounter`] otherwise.
+pub(crate) fn default_time_source() -> Box<dyn TimeSource + Send> {
+    #[cfg(target_arch = "aarch64")]
+    {
+        Box::new(GenericTimer)
+    }
+    #[cfg(all(not(target_arch = "aarch64"), feature = "std"))]
+    {
+        Box::new(StdClock::new())
+    }
+    #[cfg(all(not(target_arch = "aarch64"), not(feature = "std")))]
+    {
+        Box::new(PollCounter::new())
+    }
+}
+
+/// Poll `done` until it returns true or `budget_us` microseconds have passed
+///
+/// `done` is always evaluated at least once, and once more after the budget
+/// has run out, so a slow clock read never turns a completed wait into a
+/// timeout. The future yields to the executor between polls; the budget keeps
+/// running while it is suspended.
+///
+/// Without `irq` the task wakes itself right away, so the executor keeps
+/// polling. With `irq` it is woken by the next PMU interrupt, or by `time` at
+/// the end of the budget; a clock that can't [`wake_at`](TimeSource::wake_at)
+/// the deadline keeps the task polling.
+///
+/// # Returns
+/// * `Ok(true)` if `done` returned true within the budget
+/// * `Ok(false)` on timeout
+/// * `Err(PowerError)` if `done` failed
+pub(crate) async fn poll_until(
+    time: &dyn TimeSource,
+    budget_us: u64,
+    irq: Option<&IrqNotifier>,
+    mut done: impl FnMut() -> PowerResult<bool>,
+) -> PowerResult<bool> {
+    let start = time.now_us();
+    loop {
+        let expired = time.now_us().wrapping_sub(start) >= budget_us;
+        if done()? {
+            return Ok(true);
+        }
+        if expired {
+            return Ok(false);
+        }
+        let slot = match irq {
+            Some(irq) => arm_irq(irq, time, start.wrapping_add(budget_us)).await,
+            None => None,
+        };
+        match slot {
+            Some(slot) => {
+                // An interrupt between the check above and the registration
+                // found no waker to wake, so check once more before sleeping
+                if done()? {
+                    return Ok(true);
+                }
+                Suspend::until_woken(slot).await;
+            }
+            None => Suspend::yield_now().await,
+        }
+    }
+}
+
+/// Register the current task for the next PMU interrupt
+///
+/// Completes right away, so the task is still in the same poll when it
+/// suspends on the returned slot and the registered waker is its current one.
+///
+/// # Returns
+/// `None` if every notifier slot is taken, or if nothing would wake the task
+/// at `deadline_us`
+async fn arm_irq<'a>(
+    irq: &'a IrqNotifier,
+    time: &dyn TimeSource,
+    deadline_us: u64,
+) -> Option<IrqSlot<'a>> {
+    core::future::poll_fn(|cx| {
+        let slot = irq
+            .claim()
+            .filter(|_| time.wake_at(deadline_us, cx.waker()));
+        if let Some(slot) = &slot {
+            slot.register(cx.waker());
+        }
+        Poll::Ready(slot)
+    })
+    .await
+}
+
+/// Future that returns `Pending` once
+struct Suspend<'a> {
+    /// Notifier slot holding the waker, or `None` to wake the task right away
+    slot: Option<IrqSlot<'a>>,
+    suspended: bool,
+}
+
+impl<'a> Suspend<'a> {
+    fn yield_now() -> Self {
+        Self {
+            slot: None,
+            suspended: false,
+        }
+    }
+
+    fn until_woken(slot: IrqSlot<'a>) -> Self {
+        Self {
+            slot: Some(slot),
+            suspended: false,
+        }
+    }
+}
+
+impl Future for Suspend<'_> {
+    type Output = ();
+
+    fn poll(mut self: Pin<&mut Self>, cx: &mut Context<'_>) -> Poll<()> {
+        if self.suspended {
+            // Release the notifier slot for other waits
+            self.slot = None;
+            return Poll::Ready(());
+        }
+        self.suspended = true;
+        if self.slot.is_none() {
+            cx.waker().wake_by_ref();
+        }
+        Poll::Pending
+    }
+}
+
+/// Run `future` to completion on the current thread
+///
+/// The sequencer futures only suspend between polls in [`poll_until`], so
+/// busy-polling with a no-op waker is equivalent to the blocking loop, with
+/// or without interrupts.
+pub(crate) fn block_on<F: Future>(future: F) -> F::Output {
+    let mut future = pin!(future);
+    let mut cx = Context::from_waker(Waker::noop());
+    loop {
+        if let Poll::Ready(output) = future.as_mut().poll(&mut cx) {
+            return output;
+        }
+        core::hint::spin_loop();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Clock whose timer setup is preempted by a completing PMU interrupt
+    struct IrqOnArm<'a> {
+        notifier: &'a IrqNotifier,
+        done: &'a Cell<bool>,
+    }
+
+    impl TimeSource for IrqOnArm<'_> {
+        fn now_us(&self) -> u64 {
+            0
+        }
+
+        fn wake_at(&self, _deadline_us: u64, _waker: &Waker) -> bool {
+            self.done.set(true);
+            self.notifier.notify();
+            true
+        }
+    }
+
+    #[test]
+    fn test_irq_before_registration() {
+        let notifier = IrqNotifier::default();
+        let done = Cell::new(false);
+        let time = IrqOnArm {
+            notifier: &notifier,
+            done: &done,
+        };
+
+        // The interrupt fires after the status read but before the waker is
+        // stored, so only the check after registering sees the completion
+        let mut wait = pin!(poll_until(
+            &time,
+            DEFAULT_TIMEOUT_US,
+            Some(&notifier),
+            || { Ok(done.get()) }
+        ));
+        let mut cx = Context::from_waker(Waker::noop());
+        assert_eq!(wait.as_mut().poll(&mut cx), Poll::Ready(Ok(true)));
+        assert_eq!(notifier.waiting(), 0);
+    }
+}
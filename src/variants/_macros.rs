@@ -29,11 +29,11 @@ macro_rules! define_power_domains {
     };
 }
 
-macro_rules! bit {
-    ($n:expr) => {
-        (1 << $n)
+/// Shifted mask of a `register_bitfields!` field, for use in domain tables
+macro_rules! field {
+    ($field:expr) => {
+        (($field.mask << $field.shift) as i32)
     };
-    () => {};
 }
 
 // Make sure RockchipDomainInfo is in scope
@@ -52,6 +52,32 @@ impl RockchipPmuInfo {
             RkBoard::Rk3588 => rk3588::pmu_info(),
         }
     }
+
+    /// Get the offsets of every register the power domain table can access
+    ///
+    /// Offsets may repeat. Registers of features a domain does not use (no
+    /// memory, repair or clock ungate control) are left out.
+    pub fn register_offsets(&self) -> impl Iterator<Item = usize> + '_ {
+        let global = [self.status_offset, self.idle_offset, self.ack_offset];
+        let per_domain = self.domains.values().flat_map(move |d| {
+            [
+                (d.pwr_mask != 0).then_some(self.pwr_offset + d.pwr_offset),
+                (d.req_mask != 0).then_some(self.req_offset + d.req_offset),
+                (d.mem_mask != 0).then_some(self.mem_pwr_offset + d.mem_offset),
+                (d.mem_status_mask != 0).then_some(self.chain_status_offset + d.mem_offset),
+                (d.mem_status_mask != 0).then_some(self.mem_status_offset + d.mem_offset),
+                (d.repair_status_mask != 0).then_some(self.repair_status_offset),
+                (d.repair_mask != 0).then_some(self.repair_status_offset + d.repair_offset),
+                (d.clk_ungate_mask != 0).then_some(self.clk_ungate_offset),
+            ]
+            .into_iter()
+            .flatten()
+        });
+        global
+            .into_iter()
+            .chain(per_domain)
+            .map(|offset| offset as usize)
+    }
 }
 
 /// Domain dependency information
@@ -63,6 +89,31 @@ pub struct DomainDependency {
     pub children: alloc::vec::Vec<PowerDomain>,
 }
 
+/// QoS register block of one bus master port
+///
+/// Each port has its own PRIORITY, MODE, BANDWIDTH, SATURATION and EXTCONTROL
+/// registers in the NoC service area.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct QosPort {
+    /// Port name, following the `qos_*` device tree nodes
+    pub name: &'static str,
+    /// Power domain of the bus master
+    pub domain: PowerDomain,
+    /// Physical address of the register block
+    pub phys_addr: u64,
+}
+
+impl QosPort {
+    /// Describe a QoS port
+    pub const fn new(name: &'static str, domain: PowerDomain, phys_addr: u64) -> Self {
+        Self {
+            name,
+            domain,
+            phys_addr,
+        }
+    }
+}
+
 #[derive(Debug, Clone, Default)]
 pub struct RockchipDomainInfo {
     pub name: &'static str,
@@ -89,11 +140,9 @@ pub struct RockchipDomainInfo {
     pub repair_offset: u32,
     pub repair_mask: i32,
 
-    /// QoS configuration
-    /// Number of QoS ports for this domain
-    pub num_qos: usize,
-    /// QoS port base address offsets (relative to system bus base)
-    pub qos_offsets: &'static [u32],
+    /// QoS ports of the bus masters in this domain, saved at power off and
+    /// restored at power on
+    pub qos_ports: &'static [QosPort],
 
     /// Domain dependency information
     pub dependency: Option<DomainDependency>,
@@ -1,27 +1,36 @@
-use crate::variants::{
-    _macros::domain_m, DomainDependency, DomainMap, PowerDomain, RockchipDomainInfo,
-    RockchipPmuInfo,
+use crate::{
+    registers::rk3568::{
+        BUS_IDLE_ACK, BUS_IDLE_REQ, BUS_IDLE_ST, PMU_BUS_IDLE, PMU_BUS_IDLE_REQ, PMU_PWR_DWN_CON,
+        PMU_PWR_DWN_ST, PWR_DWN_CON, PWR_DWN_ST,
+    },
+    time::TimeoutPolicy,
+    variants::{
+        _macros::domain_m, DomainDependency, DomainMap, PowerDomain, QosPort, RockchipDomainInfo,
+        RockchipPmuInfo,
+    },
 };
 
 // QoS (Quality of Service) base addresses for RK3568
 // These addresses are used for bandwidth and priority control
-const QOS_GPU_BASE: usize = 0xFE128000;
-const QOS_NPU_BASE: usize = 0xFE138000;
-const QOS_VPU_BASE: usize = 0xFE148000;
-const QOS_RKVDEC_BASE: usize = 0xFE158000;
-const QOS_RKVENC_BASE: usize = 0xFE168000;
+const QOS_GPU_BASE: u64 = 0xFE128000;
+const QOS_NPU_BASE: u64 = 0xFE138000;
+const QOS_VPU_BASE: u64 = 0xFE148000;
+const QOS_RKVDEC_BASE: u64 = 0xFE158000;
+const QOS_RKVENC_BASE: u64 = 0xFE168000;
 
-// QoS port offset arrays for each domain
-// Each port has its own set of QoS registers
-static GPU_QOS_OFFSETS: &[usize] = &[QOS_GPU_BASE];
+// QoS ports of the bus masters in each domain
+static GPU_QOS_PORTS: &[QosPort] = &[QosPort::new("gpu", GPU, QOS_GPU_BASE)];
 
-static NPU_QOS_OFFSETS: &[usize] = &[QOS_NPU_BASE];
+static NPU_QOS_PORTS: &[QosPort] = &[QosPort::new("npu", NPU, QOS_NPU_BASE)];
 
-static VPU_QOS_OFFSETS: &[usize] = &[QOS_VPU_BASE, QOS_VPU_BASE + 0x1000];
+static VPU_QOS_PORTS: &[QosPort] = &[
+    QosPort::new("vpu_m0", VPU, QOS_VPU_BASE),
+    QosPort::new("vpu_m1", VPU, QOS_VPU_BASE + 0x1000),
+];
 
-static RKVDEC_QOS_OFFSETS: &[usize] = &[QOS_RKVDEC_BASE];
+static RKVDEC_QOS_PORTS: &[QosPort] = &[QosPort::new("rkvdec", RKVDEC, QOS_RKVDEC_BASE)];
 
-static RKVENC_QOS_OFFSETS: &[usize] = &[QOS_RKVENC_BASE];
+static RKVENC_QOS_PORTS: &[QosPort] = &[QosPort::new("rkvenc", RKVENC, QOS_RKVENC_BASE)];
 
 define_power_domains! {
     /// NPU (Neural Processing Unit) power domain
@@ -55,11 +64,10 @@ fn domain_m_with_qos(
     ack: i32,
     wakeup: bool,
     keepon: bool,
-    qos_offsets: &'static [usize],
+    qos_ports: &'static [QosPort],
 ) -> RockchipDomainInfo {
     let mut info = domain_m(name, pwr, status, req, idle, ack, wakeup, keepon);
-    info.qos_offsets = unsafe { core::mem::transmute::<&[usize], &[u32]>(qos_offsets) };
-    info.num_qos = qos_offsets.len();
+    info.qos_ports = qos_ports;
     info
 }
 
@@ -75,22 +83,21 @@ fn domain_m_with_deps_qos(
     wakeup: bool,
     keepon: bool,
     dependency: Option<DomainDependency>,
-    qos_offsets: &'static [usize],
+    qos_ports: &'static [QosPort],
 ) -> RockchipDomainInfo {
     let mut info = domain_m(name, pwr, status, req, idle, ack, wakeup, keepon);
     info.dependency = dependency;
-    info.qos_offsets = unsafe { core::mem::transmute::<&[usize], &[u32]>(qos_offsets) };
-    info.num_qos = qos_offsets.len();
+    info.qos_ports = qos_ports;
     info
 }
 
 pub fn pmu_info() -> RockchipPmuInfo {
     RockchipPmuInfo {
-        pwr_offset: 0xa0,
-        status_offset: 0x98,
-        req_offset: 0x50,
-        idle_offset: 0x68,
-        ack_offset: 0x60,
+        pwr_offset: PWR_DWN_CON.offset() as u32,
+        status_offset: PWR_DWN_ST.offset() as u32,
+        req_offset: BUS_IDLE_REQ.offset() as u32,
+        idle_offset: BUS_IDLE_ST.offset() as u32,
+        ack_offset: BUS_IDLE_ACK.offset() as u32,
         mem_pwr_offset: 0,
         chain_status_offset: 0,
         mem_status_offset: 0,
@@ -100,45 +107,50 @@ pub fn pmu_info() -> RockchipPmuInfo {
     }
 }
 
+/// Default timeout policy: the 10 ms budgets of the Linux driver
+pub fn timeout_policy() -> TimeoutPolicy {
+    TimeoutPolicy::default()
+}
+
 fn domains() -> DomainMap {
     map! {
         // GPU domain with QoS (1 port)
-        GPU    => domain_m_with_qos("gpu", bit!(0), bit!(0), bit!(1), bit!(1), bit!(1), false, false, GPU_QOS_OFFSETS),
+        GPU    => domain_m_with_qos("gpu", field!(PMU_PWR_DWN_CON::GPU), field!(PMU_PWR_DWN_ST::GPU), field!(PMU_BUS_IDLE_REQ::GPU), field!(PMU_BUS_IDLE::GPU), field!(PMU_BUS_IDLE::GPU), false, false, GPU_QOS_PORTS),
 
         // NPU domain with QoS (1 port)
-        NPU    => domain_m_with_qos("npu", bit!(1), bit!(1), bit!(2), bit!(2), bit!(2), false, false, NPU_QOS_OFFSETS),
+        NPU    => domain_m_with_qos("npu", field!(PMU_PWR_DWN_CON::NPU), field!(PMU_PWR_DWN_ST::NPU), field!(PMU_BUS_IDLE_REQ::NPU), field!(PMU_BUS_IDLE::NPU), field!(PMU_BUS_IDLE::NPU), false, false, NPU_QOS_PORTS),
 
         // VPU domain with QoS and dependencies (2 ports, parent of RKVDEC and RKVENC)
-        VPU    => domain_m_with_deps_qos("vpu", bit!(2), bit!(2), bit!(6), bit!(6), bit!(6), false, false,
+        VPU    => domain_m_with_deps_qos("vpu", field!(PMU_PWR_DWN_CON::VPU), field!(PMU_PWR_DWN_ST::VPU), field!(PMU_BUS_IDLE_REQ::VPU), field!(PMU_BUS_IDLE::VPU), field!(PMU_BUS_IDLE::VPU), false, false,
                     Some(DomainDependency {
                         parent: None,
                         children: alloc::vec![RKVDEC, RKVENC],
-                    }), VPU_QOS_OFFSETS),
+                    }), VPU_QOS_PORTS),
 
         // VI (Video Input) domain - independent
-        VI     => domain_m("vi", bit!(6), bit!(6), bit!(3), bit!(3), bit!(3), false, false),
+        VI     => domain_m("vi", field!(PMU_PWR_DWN_CON::VI), field!(PMU_PWR_DWN_ST::VI), field!(PMU_BUS_IDLE_REQ::VI), field!(PMU_BUS_IDLE::VI), field!(PMU_BUS_IDLE::VI), false, false),
 
         // VO (Video Output) domain - keepon_startup=true
-        VO     => domain_m("vo", bit!(7), bit!(7), bit!(4), bit!(4), bit!(4), false, true),
+        VO     => domain_m("vo", field!(PMU_PWR_DWN_CON::VO), field!(PMU_PWR_DWN_ST::VO), field!(PMU_BUS_IDLE_REQ::VO), field!(PMU_BUS_IDLE::VO), field!(PMU_BUS_IDLE::VO), false, true),
 
         // RGA (Raster Graphics) domain - independent
-        RGA    => domain_m("rga", bit!(5), bit!(5), bit!(5), bit!(5), bit!(5), false, false),
+        RGA    => domain_m("rga", field!(PMU_PWR_DWN_CON::RGA), field!(PMU_PWR_DWN_ST::RGA), field!(PMU_BUS_IDLE_REQ::RGA), field!(PMU_BUS_IDLE::RGA), field!(PMU_BUS_IDLE::RGA), false, false),
 
         // RKVDEC (Video Decoder) with QoS and dependency (child of VPU)
-        RKVDEC => domain_m_with_deps_qos("rkvdec", bit!(4), bit!(4), bit!(8), bit!(8), bit!(8), false, false,
+        RKVDEC => domain_m_with_deps_qos("rkvdec", field!(PMU_PWR_DWN_CON::RKVDEC), field!(PMU_PWR_DWN_ST::RKVDEC), field!(PMU_BUS_IDLE_REQ::RKVDEC), field!(PMU_BUS_IDLE::RKVDEC), field!(PMU_BUS_IDLE::RKVDEC), false, false,
                     Some(DomainDependency {
                         parent: Some(VPU),
                         children: alloc::vec![],
-                    }), RKVDEC_QOS_OFFSETS),
+                    }), RKVDEC_QOS_PORTS),
 
         // RKVENC (Video Encoder) with QoS and dependency (child of VPU)
-        RKVENC => domain_m_with_deps_qos("rkvenc", bit!(3), bit!(3), bit!(7), bit!(7), bit!(7), false, false,
+        RKVENC => domain_m_with_deps_qos("rkvenc", field!(PMU_PWR_DWN_CON::RKVENC), field!(PMU_PWR_DWN_ST::RKVENC), field!(PMU_BUS_IDLE_REQ::RKVENC), field!(PMU_BUS_IDLE::RKVENC), field!(PMU_BUS_IDLE::RKVENC), false, false,
                     Some(DomainDependency {
                         parent: Some(VPU),
                         children: alloc::vec![],
-                    }), RKVENC_QOS_OFFSETS),
+                    }), RKVENC_QOS_PORTS),
 
         // PIPE (Display Pipeline) - independent
-        PIPE   => domain_m("pipe", bit!(8), bit!(8), bit!(11), bit!(11), bit!(11), false, false),
+        PIPE   => domain_m("pipe", field!(PMU_PWR_DWN_CON::PIPE), field!(PMU_PWR_DWN_ST::PIPE), field!(PMU_BUS_IDLE_REQ::PIPE), field!(PMU_BUS_IDLE::PIPE), field!(PMU_BUS_IDLE::PIPE), false, false),
     }
 }
@@ -1,48 +1,115 @@
-use crate::variants::{
-    _macros::domain_m_o_r, DomainDependency, DomainMap, PowerDomain, RockchipDomainInfo,
-    RockchipPmuInfo,
+//! RK3588 power domains and PMU register tables
+
+use crate::{
+    registers::rk3588::{
+        BUS_IDLE_ACK, BUS_IDLE_REQ0, BUS_IDLE_ST, CHAIN_STATUS0, MEM_PWR_GATE_CON0, MEM_STATUS0,
+        PMU_BUS_IDLE, PMU_BUS_IDLE_REQ0, PMU_BUS_IDLE_REQ1, PMU_MEM_STATUS0, PMU_MEM_STATUS1,
+        PMU_PWR_GATE_CON0, PMU_PWR_GATE_CON1, PMU_PWR_GATE_ST, PMU_REPAIR_STATUS, PWR_GATE_CON0,
+        PWR_GATE_ST, REPAIR_STATUS,
+    },
+    time::TimeoutPolicy,
+    variants::{
+        _macros::domain_m_o_r, DomainDependency, DomainMap, PowerDomain, QosPort,
+        RockchipDomainInfo, RockchipPmuInfo,
+    },
 };
 
-// QoS (Quality of Service) base addresses for RK3588
-// These addresses are used for bandwidth and priority control
-const QOS_GPU_BASE: usize = 0xFDF35000;
-const QOS_NPU_BASE: usize = 0xFDF40000;
-const QOS_RKVDEC_BASE: usize = 0xFDF48000;
-const QOS_RKVENC_BASE: usize = 0xFDF50000;
-const QOS_VOP_BASE: usize = 0xFDF60000;
-const QOS_VI_BASE: usize = 0xFDF70000;
-const QOS_VCODEC_BASE: usize = 0xFDF78000;
-
-// QoS port offset arrays for each domain
-// Each port has its own set of QoS registers (PRIORITY, MODE, BANDWIDTH, SATURATION, EXTCONTROL)
-static GPU_QOS_OFFSETS: &[usize] = &[QOS_GPU_BASE, QOS_GPU_BASE + 0x1000];
-
-static NPU_QOS_OFFSETS: &[usize] = &[
-    QOS_NPU_BASE,
-    QOS_NPU_BASE + 0x1000,
-    QOS_NPU_BASE + 0x2000,
-    QOS_NPU_BASE + 0x3000,
+// QoS ports of the bus masters in each domain, as assigned with `pm_qos` in
+// the upstream rk3588-base.dtsi. Addresses are those of the `qos_*` syscon
+// nodes. NPU, VCODEC, PHP, GMAC, PCIE, NVM and AUDIO have no QoS ports.
+// PCIE and GMAC do have QoS blocks, but their power-domain nodes carry no
+// `pm_qos` property upstream, so Linux doesn't save or restore them either.
+static GPU_QOS_PORTS: &[QosPort] = &[
+    QosPort::new("gpu_m0", GPU, 0xFDF35000),
+    QosPort::new("gpu_m1", GPU, 0xFDF35200),
+    QosPort::new("gpu_m2", GPU, 0xFDF35400),
+    QosPort::new("gpu_m3", GPU, 0xFDF35600),
 ];
 
-static RKVDEC_QOS_OFFSETS: &[usize] = &[QOS_RKVDEC_BASE, QOS_RKVDEC_BASE + 0x1000];
+static NPUTOP_QOS_PORTS: &[QosPort] = &[
+    QosPort::new("npu0_mwr", NPUTOP, 0xFDF72000),
+    QosPort::new("npu0_mro", NPUTOP, 0xFDF72200),
+    QosPort::new("mcu_npu", NPUTOP, 0xFDF72400),
+];
+
+static NPU1_QOS_PORTS: &[QosPort] = &[QosPort::new("npu1", NPU1, 0xFDF70000)];
+
+static NPU2_QOS_PORTS: &[QosPort] = &[QosPort::new("npu2", NPU2, 0xFDF71000)];
+
+static VENC0_QOS_PORTS: &[QosPort] = &[
+    QosPort::new("rkvenc0_m0ro", VENC0, 0xFDF60000),
+    QosPort::new("rkvenc0_m1ro", VENC0, 0xFDF60200),
+    QosPort::new("rkvenc0_m2wo", VENC0, 0xFDF60400),
+];
+
+static VENC1_QOS_PORTS: &[QosPort] = &[
+    QosPort::new("rkvenc1_m0ro", VENC1, 0xFDF61000),
+    QosPort::new("rkvenc1_m1ro", VENC1, 0xFDF61200),
+    QosPort::new("rkvenc1_m2wo", VENC1, 0xFDF61400),
+];
+
+static RKVDEC0_QOS_PORTS: &[QosPort] = &[QosPort::new("rkvdec0", RKVDEC0, 0xFDF62000)];
+
+static RKVDEC1_QOS_PORTS: &[QosPort] = &[QosPort::new("rkvdec1", RKVDEC1, 0xFDF63000)];
 
-static RKVENC_QOS_OFFSETS: &[usize] = &[QOS_RKVENC_BASE, QOS_RKVENC_BASE + 0x1000];
+static VDPU_QOS_PORTS: &[QosPort] = &[
+    QosPort::new("iep", VDPU, 0xFDF66000),
+    QosPort::new("jpeg_dec", VDPU, 0xFDF66200),
+    QosPort::new("jpeg_enc0", VDPU, 0xFDF66400),
+    QosPort::new("jpeg_enc1", VDPU, 0xFDF66600),
+    QosPort::new("jpeg_enc2", VDPU, 0xFDF66800),
+    QosPort::new("jpeg_enc3", VDPU, 0xFDF66A00),
+    QosPort::new("rga2_mro", VDPU, 0xFDF66C00),
+    QosPort::new("rga2_mwo", VDPU, 0xFDF66E00),
+    QosPort::new("vdpu", VDPU, 0xFDF67200),
+];
+
+static RGA30_QOS_PORTS: &[QosPort] = &[QosPort::new("rga3_0", RGA30, 0xFDF67000)];
+
+static AV1_QOS_PORTS: &[QosPort] = &[QosPort::new("av1", AV1, 0xFDF64000)];
 
-static VOP_QOS_OFFSETS: &[usize] = &[
-    QOS_VOP_BASE,
-    QOS_VOP_BASE + 0x1000,
-    QOS_VOP_BASE + 0x2000,
-    QOS_VOP_BASE + 0x3000,
+static VI_QOS_PORTS: &[QosPort] = &[
+    QosPort::new("isp0_mro", VI, 0xFDF40400),
+    QosPort::new("isp0_mwo", VI, 0xFDF40500),
+    QosPort::new("vicap_m0", VI, 0xFDF40600),
+    QosPort::new("vicap_m1", VI, 0xFDF40800),
 ];
 
-static VI_QOS_OFFSETS: &[usize] = &[QOS_VI_BASE, QOS_VI_BASE + 0x1000];
+static FEC_QOS_PORTS: &[QosPort] = &[
+    QosPort::new("fisheye0", FEC, 0xFDF40000),
+    QosPort::new("fisheye1", FEC, 0xFDF40200),
+];
 
-static VCODEC_QOS_OFFSETS: &[usize] = &[
-    QOS_VCODEC_BASE,
-    QOS_VCODEC_BASE + 0x1000,
-    QOS_VCODEC_BASE + 0x2000,
+static ISP1_QOS_PORTS: &[QosPort] = &[
+    QosPort::new("isp1_mwo", ISP1, 0xFDF41000),
+    QosPort::new("isp1_mro", ISP1, 0xFDF41100),
 ];
 
+static RGA31_QOS_PORTS: &[QosPort] = &[QosPort::new("rga3_1", RGA31, 0xFDF36000)];
+
+static VOP_QOS_PORTS: &[QosPort] = &[
+    QosPort::new("vop_m0", VOP, 0xFDF82000),
+    QosPort::new("vop_m1", VOP, 0xFDF82200),
+];
+
+static VO0_QOS_PORTS: &[QosPort] = &[QosPort::new("hdcp0", VO0, 0xFDF80000)];
+
+static VO1_QOS_PORTS: &[QosPort] = &[
+    QosPort::new("hdcp1", VO1, 0xFDF81000),
+    QosPort::new("hdmirx", VO1, 0xFDF81200),
+];
+
+static SDIO_QOS_PORTS: &[QosPort] = &[QosPort::new("sdio", SDIO, 0xFDF39000)];
+
+static USB_QOS_PORTS: &[QosPort] = &[
+    QosPort::new("usb3_1", USB, 0xFDF3E000),
+    QosPort::new("usb3_0", USB, 0xFDF3E200),
+    QosPort::new("usb2host_0", USB, 0xFDF3E400),
+    QosPort::new("usb2host_1", USB, 0xFDF3E600),
+];
+
+static SDMMC_QOS_PORTS: &[QosPort] = &[QosPort::new("sdmmc", SDMMC, 0xFDF3D800)];
+
 define_power_domains! {
     // VD_NPU
     /// NPU (Neural Processing Unit) main domain
@@ -113,20 +180,25 @@ define_power_domains! {
 
 pub fn pmu_info() -> RockchipPmuInfo {
     RockchipPmuInfo {
-        pwr_offset: 0x14c,
-        status_offset: 0x180,
-        req_offset: 0x10c,
-        idle_offset: 0x120,
-        ack_offset: 0x118,
-        mem_pwr_offset: 0x1a0,
-        chain_status_offset: 0x1f0,
-        mem_status_offset: 0x1f8,
-        repair_status_offset: 0x290,
+        pwr_offset: PWR_GATE_CON0.offset() as u32,
+        status_offset: PWR_GATE_ST.offset() as u32,
+        req_offset: BUS_IDLE_REQ0.offset() as u32,
+        idle_offset: BUS_IDLE_ST.offset() as u32,
+        ack_offset: BUS_IDLE_ACK.offset() as u32,
+        mem_pwr_offset: MEM_PWR_GATE_CON0.offset() as u32,
+        chain_status_offset: CHAIN_STATUS0.offset() as u32,
+        mem_status_offset: MEM_STATUS0.offset() as u32,
+        repair_status_offset: REPAIR_STATUS.offset() as u32,
         domains: domains(),
         ..Default::default()
     }
 }
 
+/// Default timeout policy: the 10 ms budgets of the Linux driver
+pub fn timeout_policy() -> TimeoutPolicy {
+    TimeoutPolicy::default()
+}
+
 #[allow(clippy::too_many_arguments)]
 fn domain_info(
     name: &'static str,
@@ -208,7 +280,7 @@ fn domain_info_with_deps_qos(
     idle: i32,
     wakeup: bool,
     dependency: Option<DomainDependency>,
-    qos_offsets: &'static [usize],
+    qos_ports: &'static [QosPort],
 ) -> RockchipDomainInfo {
     let mut info = domain_m_o_r(
         name,
@@ -226,9 +298,7 @@ fn domain_info_with_deps_qos(
         false,
     );
     info.dependency = dependency;
-    // Convert usize offsets to u32 (safe on 32-bit and 64-bit systems for memory-mapped addresses)
-    info.qos_offsets = unsafe { core::mem::transmute::<&[usize], &[u32]>(qos_offsets) };
-    info.num_qos = qos_offsets.len();
+    info.qos_ports = qos_ports;
     info
 }
 
@@ -246,7 +316,7 @@ fn domain_info_with_qos(
     req: i32,
     idle: i32,
     wakeup: bool,
-    qos_offsets: &'static [usize],
+    qos_ports: &'static [QosPort],
 ) -> RockchipDomainInfo {
     let mut info = domain_m_o_r(
         name,
@@ -263,126 +333,125 @@ fn domain_info_with_qos(
         wakeup,
         false,
     );
-    info.qos_offsets = unsafe { core::mem::transmute::<&[usize], &[u32]>(qos_offsets) };
-    info.num_qos = qos_offsets.len();
+    info.qos_ports = qos_ports;
     info
 }
 
 fn domains() -> DomainMap {
     map! {
-        // GPU domain with QoS configuration (2 ports)
-        GPU      => domain_info_with_qos("gpu", 0x0, bit!(0), 0, 0x0, 0, bit!(1), 0x0, bit!(0), bit!(0), false, GPU_QOS_OFFSETS),
+        // GPU domain with QoS configuration (4 ports)
+        GPU      => domain_info_with_qos("gpu", 0x0, field!(PMU_PWR_GATE_CON0::GPU), 0, 0x0, 0, field!(PMU_REPAIR_STATUS::GPU), 0x0, field!(PMU_BUS_IDLE_REQ0::GPU), field!(PMU_BUS_IDLE::GPU), false, GPU_QOS_PORTS),
 
         // NPU domains with dependencies and QoS
-        NPU      => domain_info_with_qos("npu", 0x0, bit!(1), bit!(1), 0x0, 0, 0, 0x0, 0, 0, false, NPU_QOS_OFFSETS),
+        NPU      => domain_info("npu", 0x0, field!(PMU_PWR_GATE_CON0::NPU), field!(PMU_PWR_GATE_ST::NPU), 0x0, 0, 0, 0x0, 0, 0, false),
 
-        // VCODEC domain with QoS and dependencies (parent of VENC0/1, RKVDEC0/1)
-        VCODEC   => domain_info_with_deps_qos("vcodec", 0x0, bit!(2), bit!(2), 0x0, 0, 0, 0x0, 0, 0, false,
+        // VCODEC domain with dependencies (parent of VENC0/1, RKVDEC0/1)
+        VCODEC   => domain_info_with_deps("vcodec", 0x0, field!(PMU_PWR_GATE_CON0::VCODEC), field!(PMU_PWR_GATE_ST::VCODEC), 0x0, 0, 0, 0x0, 0, 0, false,
                         Some(DomainDependency {
                             parent: None,
                             children: alloc::vec![VENC0, VENC1, RKVDEC0, RKVDEC1],
-                        }), VCODEC_QOS_OFFSETS),
+                        })),
 
         // NPUTOP has NPU1 and NPU2 as children (children must be powered off first)
-        NPUTOP   => domain_info_with_deps("nputop", 0x0, bit!(3), 0, 0x0, bit!(11), bit!(2), 0x0, bit!(1), bit!(1), false,
+        NPUTOP   => domain_info_with_deps_qos("nputop", 0x0, field!(PMU_PWR_GATE_CON0::NPUTOP), 0, 0x0, field!(PMU_MEM_STATUS0::NPUTOP), field!(PMU_REPAIR_STATUS::NPUTOP), 0x0, field!(PMU_BUS_IDLE_REQ0::NPUTOP), field!(PMU_BUS_IDLE::NPUTOP), false,
                         Some(DomainDependency {
                             parent: None,
                             children: alloc::vec![NPU1, NPU2],
-                        })),
+                        }), NPUTOP_QOS_PORTS),
 
         // NPU1 depends on NPUTOP (parent must be powered on first)
-        NPU1     => domain_info_with_deps("npu1", 0x0, bit!(4), 0, 0x0, bit!(12), bit!(3), 0x0, bit!(2), bit!(2), false,
+        NPU1     => domain_info_with_deps_qos("npu1", 0x0, field!(PMU_PWR_GATE_CON0::NPU1), 0, 0x0, field!(PMU_MEM_STATUS0::NPU1), field!(PMU_REPAIR_STATUS::NPU1), 0x0, field!(PMU_BUS_IDLE_REQ0::NPU1), field!(PMU_BUS_IDLE::NPU1), false,
                         Some(DomainDependency {
                             parent: Some(NPUTOP),
                             children: alloc::vec![],
-                        })),
+                        }), NPU1_QOS_PORTS),
 
         // NPU2 depends on NPUTOP (parent must be powered on first)
-        NPU2     => domain_info_with_deps("npu2", 0x0, bit!(5), 0, 0x0, bit!(13), bit!(4), 0x0, bit!(3), bit!(3), false,
+        NPU2     => domain_info_with_deps_qos("npu2", 0x0, field!(PMU_PWR_GATE_CON0::NPU2), 0, 0x0, field!(PMU_MEM_STATUS0::NPU2), field!(PMU_REPAIR_STATUS::NPU2), 0x0, field!(PMU_BUS_IDLE_REQ0::NPU2), field!(PMU_BUS_IDLE::NPU2), false,
                         Some(DomainDependency {
                             parent: Some(NPUTOP),
                             children: alloc::vec![],
-                        })),
+                        }), NPU2_QOS_PORTS),
 
         // Video encoder domains with dependencies (children of VCODEC)
-        VENC0    => domain_info_with_deps_qos("venc0", 0x0, bit!(6), 0, 0x0, bit!(14), bit!(5), 0x0, bit!(4), bit!(4), false,
+        VENC0    => domain_info_with_deps_qos("venc0", 0x0, field!(PMU_PWR_GATE_CON0::VENC0), 0, 0x0, field!(PMU_MEM_STATUS0::VENC0), field!(PMU_REPAIR_STATUS::VENC0), 0x0, field!(PMU_BUS_IDLE_REQ0::VENC0), field!(PMU_BUS_IDLE::VENC0), false,
                         Some(DomainDependency {
                             parent: Some(VCODEC),
                             children: alloc::vec![],
-                        }), RKVENC_QOS_OFFSETS),
+                        }), VENC0_QOS_PORTS),
 
-        VENC1    => domain_info_with_deps("venc1", 0x0, bit!(7), 0, 0x0, bit!(15), bit!(6), 0x0, bit!(5), bit!(5), false,
+        VENC1    => domain_info_with_deps_qos("venc1", 0x0, field!(PMU_PWR_GATE_CON0::VENC1), 0, 0x0, field!(PMU_MEM_STATUS0::VENC1), field!(PMU_REPAIR_STATUS::VENC1), 0x0, field!(PMU_BUS_IDLE_REQ0::VENC1), field!(PMU_BUS_IDLE::VENC1), false,
                         Some(DomainDependency {
                             parent: Some(VCODEC),
                             children: alloc::vec![],
-                        })),
+                        }), VENC1_QOS_PORTS),
 
         // Video decoder domains with dependencies (children of VCODEC)
-        RKVDEC0  => domain_info_with_deps_qos("rkvdec0", 0x0, bit!(8), 0, 0x0, bit!(16), bit!(7), 0x0, bit!(6), bit!(6), false,
+        RKVDEC0  => domain_info_with_deps_qos("rkvdec0", 0x0, field!(PMU_PWR_GATE_CON0::RKVDEC0), 0, 0x0, field!(PMU_MEM_STATUS0::RKVDEC0), field!(PMU_REPAIR_STATUS::RKVDEC0), 0x0, field!(PMU_BUS_IDLE_REQ0::RKVDEC0), field!(PMU_BUS_IDLE::RKVDEC0), false,
                         Some(DomainDependency {
                             parent: Some(VCODEC),
                             children: alloc::vec![],
-                        }), RKVDEC_QOS_OFFSETS),
+                        }), RKVDEC0_QOS_PORTS),
 
-        RKVDEC1  => domain_info_with_deps("rkvdec1", 0x0, bit!(9), 0, 0x0, bit!(17), bit!(8), 0x0, bit!(7), bit!(7), false,
+        RKVDEC1  => domain_info_with_deps_qos("rkvdec1", 0x0, field!(PMU_PWR_GATE_CON0::RKVDEC1), 0, 0x0, field!(PMU_MEM_STATUS0::RKVDEC1), field!(PMU_REPAIR_STATUS::RKVDEC1), 0x0, field!(PMU_BUS_IDLE_REQ0::RKVDEC1), field!(PMU_BUS_IDLE::RKVDEC1), false,
                         Some(DomainDependency {
                             parent: Some(VCODEC),
                             children: alloc::vec![],
-                        })),
+                        }), RKVDEC1_QOS_PORTS),
 
         // LOGIC domains
-        VDPU     => domain_info("vdpu",    0x0, bit!(10), 0,       0x0, bit!(18), bit!(9),  0x0, bit!(8),  bit!(8),  false),
-        RGA30    => domain_info("rga30",   0x0, bit!(11), 0,       0x0, bit!(19), bit!(10), 0x0, 0,        0,        false),
-        AV1      => domain_info("av1",     0x0, bit!(12), 0,       0x0, bit!(20), bit!(11), 0x0, bit!(9),  bit!(9),  false),
+        VDPU     => domain_info_with_qos("vdpu", 0x0, field!(PMU_PWR_GATE_CON0::VDPU), 0, 0x0, field!(PMU_MEM_STATUS0::VDPU), field!(PMU_REPAIR_STATUS::VDPU), 0x0, field!(PMU_BUS_IDLE_REQ0::VDPU), field!(PMU_BUS_IDLE::VDPU), false, VDPU_QOS_PORTS),
+        RGA30    => domain_info_with_qos("rga30", 0x0, field!(PMU_PWR_GATE_CON0::RGA30), 0, 0x0, field!(PMU_MEM_STATUS0::RGA30), field!(PMU_REPAIR_STATUS::RGA30), 0x0, 0, 0, false, RGA30_QOS_PORTS),
+        AV1      => domain_info_with_qos("av1", 0x0, field!(PMU_PWR_GATE_CON0::AV1), 0, 0x0, field!(PMU_MEM_STATUS0::AV1), field!(PMU_REPAIR_STATUS::AV1), 0x0, field!(PMU_BUS_IDLE_REQ0::AV1), field!(PMU_BUS_IDLE::AV1), false, AV1_QOS_PORTS),
 
         // VI (Video Input) domain with QoS and dependencies (parent of ISP1)
-        VI       => domain_info_with_deps_qos("vi", 0x0, bit!(13), 0, 0x0, bit!(21), bit!(12), 0x0, bit!(10), bit!(10), false,
+        VI       => domain_info_with_deps_qos("vi", 0x0, field!(PMU_PWR_GATE_CON0::VI), 0, 0x0, field!(PMU_MEM_STATUS0::VI), field!(PMU_REPAIR_STATUS::VI), 0x0, field!(PMU_BUS_IDLE_REQ0::VI), field!(PMU_BUS_IDLE::VI), false,
                         Some(DomainDependency {
                             parent: None,
                             children: alloc::vec![ISP1],
-                        }), VI_QOS_OFFSETS),
+                        }), VI_QOS_PORTS),
 
-        FEC      => domain_info("fec",     0x0, bit!(14), 0,       0x0, bit!(22), bit!(13), 0x0, 0,        0,        false),
+        FEC      => domain_info_with_qos("fec", 0x0, field!(PMU_PWR_GATE_CON0::FEC), 0, 0x0, field!(PMU_MEM_STATUS0::FEC), field!(PMU_REPAIR_STATUS::FEC), 0x0, 0, 0, false, FEC_QOS_PORTS),
 
         // ISP1 depends on VI (parent must be powered on first)
-        ISP1     => domain_info_with_deps("isp1", 0x0, bit!(15), 0, 0x0, bit!(23), bit!(14), 0x0, bit!(11), bit!(11), false,
+        ISP1     => domain_info_with_deps_qos("isp1", 0x0, field!(PMU_PWR_GATE_CON0::ISP1), 0, 0x0, field!(PMU_MEM_STATUS0::ISP1), field!(PMU_REPAIR_STATUS::ISP1), 0x0, field!(PMU_BUS_IDLE_REQ0::ISP1), field!(PMU_BUS_IDLE::ISP1), false,
                         Some(DomainDependency {
                             parent: Some(VI),
                             children: alloc::vec![],
-                        })),
+                        }), ISP1_QOS_PORTS),
 
         // More LOGIC domains with pwr_offset 0x4
-        RGA31    => domain_info("rga31",   0x4, bit!(0),  0,       0x0, bit!(24), bit!(15), 0x0, bit!(12), bit!(12), false),
+        RGA31    => domain_info_with_qos("rga31", 0x4, field!(PMU_PWR_GATE_CON1::RGA31), 0, 0x0, field!(PMU_MEM_STATUS0::RGA31), field!(PMU_REPAIR_STATUS::RGA31), 0x0, field!(PMU_BUS_IDLE_REQ0::RGA31), field!(PMU_BUS_IDLE::RGA31), false, RGA31_QOS_PORTS),
 
         // VOP (Video Output Processor) with QoS and dependencies (parent of VO0, VO1)
-        VOP      => domain_info_with_deps_qos("vop", 0x4, bit!(1), 0, 0x0, bit!(25), bit!(16), 0x0, bit!(13) | bit!(14), bit!(13) | bit!(14), false,
+        VOP      => domain_info_with_deps_qos("vop", 0x4, field!(PMU_PWR_GATE_CON1::VOP), 0, 0x0, field!(PMU_MEM_STATUS0::VOP), field!(PMU_REPAIR_STATUS::VOP), 0x0, field!(PMU_BUS_IDLE_REQ0::VOP), field!(PMU_BUS_IDLE::VOP), false,
                         Some(DomainDependency {
                             parent: None,
                             children: alloc::vec![VO0, VO1],
-                        }), VOP_QOS_OFFSETS),
+                        }), VOP_QOS_PORTS),
 
         // VO0 depends on VOP (parent must be powered on first)
-        VO0      => domain_info_with_deps("vo0", 0x4, bit!(2), 0, 0x0, bit!(26), bit!(17), 0x0, bit!(15), bit!(15), false,
+        VO0      => domain_info_with_deps_qos("vo0", 0x4, field!(PMU_PWR_GATE_CON1::VO0), 0, 0x0, field!(PMU_MEM_STATUS0::VO0), field!(PMU_REPAIR_STATUS::VO0), 0x0, field!(PMU_BUS_IDLE_REQ0::VO0), field!(PMU_BUS_IDLE::VO0), false,
                         Some(DomainDependency {
                             parent: Some(VOP),
                             children: alloc::vec![],
-                        })),
+                        }), VO0_QOS_PORTS),
 
         // VO1 depends on VOP (parent must be powered on first)
-        VO1      => domain_info_with_deps("vo1", 0x4, bit!(3), 0, 0x0, bit!(27), bit!(18), 0x4, bit!(0), bit!(16), false,
+        VO1      => domain_info_with_deps_qos("vo1", 0x4, field!(PMU_PWR_GATE_CON1::VO1), 0, 0x0, field!(PMU_MEM_STATUS0::VO1), field!(PMU_REPAIR_STATUS::VO1), 0x4, field!(PMU_BUS_IDLE_REQ1::VO1), field!(PMU_BUS_IDLE::VO1), false,
                         Some(DomainDependency {
                             parent: Some(VOP),
                             children: alloc::vec![],
-                        })),
-
-        AUDIO    => domain_info("audio",   0x4, bit!(4),  0,       0x0, bit!(28), bit!(19), 0x4, bit!(1),  bit!(17), false),
-        PHP      => domain_info("php",     0x4, bit!(5),  0,       0x0, bit!(29), bit!(20), 0x4, bit!(5),  bit!(21), false),
-        GMAC     => domain_info("gmac",    0x4, bit!(6),  0,       0x0, bit!(30), bit!(21), 0x0, 0,        0,        false),
-        PCIE     => domain_info("pcie",    0x4, bit!(7),  0,       0x0, bit!(31), bit!(22), 0x0, 0,        0,        true),
-        NVM      => domain_info("nvm",     0x4, bit!(8),  bit!(24),0x4, 0,        0,        0x4, bit!(2),  bit!(18), false),
-        NVM0     => domain_info("nvm0",    0x4, bit!(9),  0,       0x4, bit!(1),  bit!(23), 0x0, 0,        0,        false),
-        SDIO     => domain_info("sdio",    0x4, bit!(10), 0,       0x4, bit!(2),  bit!(24), 0x4, bit!(3),  bit!(19), false),
-        USB      => domain_info("usb",     0x4, bit!(11), 0,       0x4, bit!(3),  bit!(25), 0x4, bit!(4),  bit!(20), true),
-        SDMMC    => domain_info("sdmmc",   0x4, bit!(13), 0,       0x4, bit!(5),  bit!(26), 0x0, 0,        0,        false),
+                        }), VO1_QOS_PORTS),
+
+        AUDIO    => domain_info("audio", 0x4, field!(PMU_PWR_GATE_CON1::AUDIO), 0, 0x0, field!(PMU_MEM_STATUS0::AUDIO), field!(PMU_REPAIR_STATUS::AUDIO), 0x4, field!(PMU_BUS_IDLE_REQ1::AUDIO), field!(PMU_BUS_IDLE::AUDIO), false),
+        PHP      => domain_info("php", 0x4, field!(PMU_PWR_GATE_CON1::PHP), 0, 0x0, field!(PMU_MEM_STATUS0::PHP), field!(PMU_REPAIR_STATUS::PHP), 0x4, field!(PMU_BUS_IDLE_REQ1::PHP), field!(PMU_BUS_IDLE::PHP), false),
+        GMAC     => domain_info("gmac", 0x4, field!(PMU_PWR_GATE_CON1::GMAC), 0, 0x0, field!(PMU_MEM_STATUS0::GMAC), field!(PMU_REPAIR_STATUS::GMAC), 0x0, 0, 0, false),
+        PCIE     => domain_info("pcie", 0x4, field!(PMU_PWR_GATE_CON1::PCIE), 0, 0x0, field!(PMU_MEM_STATUS0::PCIE), field!(PMU_REPAIR_STATUS::PCIE), 0x0, 0, 0, true),
+        NVM      => domain_info("nvm", 0x4, field!(PMU_PWR_GATE_CON1::NVM), field!(PMU_PWR_GATE_ST::NVM), 0x4, 0, 0, 0x4, field!(PMU_BUS_IDLE_REQ1::NVM), field!(PMU_BUS_IDLE::NVM), false),
+        NVM0     => domain_info("nvm0", 0x4, field!(PMU_PWR_GATE_CON1::NVM0), 0, 0x4, field!(PMU_MEM_STATUS1::NVM0), field!(PMU_REPAIR_STATUS::NVM0), 0x0, 0, 0, false),
+        SDIO     => domain_info_with_qos("sdio", 0x4, field!(PMU_PWR_GATE_CON1::SDIO), 0, 0x4, field!(PMU_MEM_STATUS1::SDIO), field!(PMU_REPAIR_STATUS::SDIO), 0x4, field!(PMU_BUS_IDLE_REQ1::SDIO), field!(PMU_BUS_IDLE::SDIO), false, SDIO_QOS_PORTS),
+        USB      => domain_info_with_qos("usb", 0x4, field!(PMU_PWR_GATE_CON1::USB), 0, 0x4, field!(PMU_MEM_STATUS1::USB), field!(PMU_REPAIR_STATUS::USB), 0x4, field!(PMU_BUS_IDLE_REQ1::USB), field!(PMU_BUS_IDLE::USB), true, USB_QOS_PORTS),
+        SDMMC    => domain_info_with_qos("sdmmc", 0x4, field!(PMU_PWR_GATE_CON1::SDMMC), 0, 0x4, field!(PMU_MEM_STATUS1::SDMMC), field!(PMU_REPAIR_STATUS::SDMMC), 0x0, 0, 0, false, SDMMC_QOS_PORTS),
     }
 }
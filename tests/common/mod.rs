@@ -0,0 +1,336 @@
+//! Fixtures shared by the host-side tests
+//!
+//! PMU register offsets and bits come from [`rockchip_pm::registers`].
+
+use core::{
+    cell::Cell,
+    pin::pin,
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll, Waker},
+};
+use std::{
+    sync::{Arc, Mutex},
+    task::Wake,
+    thread::{self, ThreadId},
+};
+
+use rockchip_pm::{
+    PowerDomain, QOS_PORT_SIZE, QosWindow, RK3588, RegisterAccess, RkBoard, RockchipPM,
+    irq::IrqLayout,
+    registers::rk3588::{
+        self, PMU_BUS_IDLE, PMU_BUS_IDLE_REQ0, PMU_PWR_GATE_CON0, PMU_REPAIR_STATUS,
+    },
+    sim::{Access, Fault, FaultInjector, Rk3568Pmu, Rk3588Pmu, SimIrq, Trace, TraceRecorder},
+    time::{IdleRetry, PollCounter, TimeSource, TimeoutStep},
+};
+
+// ========================================
+// PMU models
+// ========================================
+
+pub fn rk3588_pm() -> RockchipPM<Rk3588Pmu> {
+    RockchipPM::with_regs(Rk3588Pmu::new(), RkBoard::Rk3588)
+}
+
+pub fn rk3568_pm() -> RockchipPM<Rk3568Pmu> {
+    RockchipPM::with_regs(Rk3568Pmu::new(), RkBoard::Rk3568)
+}
+
+pub fn faulty_rk3588_pm() -> RockchipPM<FaultInjector<Rk3588Pmu>> {
+    RockchipPM::with_regs(FaultInjector::new(Rk3588Pmu::new()), RkBoard::Rk3588)
+}
+
+/// AV1 power bit in `PWR_GATE_CON0`
+pub const AV1_PWR: u32 = PMU_PWR_GATE_CON0::AV1::SET.value;
+/// AV1 idle bit in `BUS_IDLE_REQ0`, `BUS_IDLE_ACK` and `BUS_IDLE_ST`
+pub const AV1_IDLE: u32 = PMU_BUS_IDLE::AV1::SET.value;
+/// AV1 power-on bit in `REPAIR_STATUS`
+pub const AV1_REPAIR: u32 = PMU_REPAIR_STATUS::AV1::SET.value;
+
+const _: () = assert!(PMU_BUS_IDLE_REQ0::AV1::SET.value == AV1_IDLE);
+
+/// AV1 with a 100 µs acknowledgment budget and an acknowledgment that is
+/// missing for the first 150 polls
+pub fn av1_ack_hiccup(retry: IdleRetry) -> RockchipPM<TraceRecorder<FaultInjector<Rk3588Pmu>>> {
+    let regs = TraceRecorder::new(FaultInjector::new(Rk3588Pmu::new()));
+    regs.inner().inject(Fault::StuckFor {
+        offset: rk3588::BUS_IDLE_ACK.offset(),
+        mask: AV1_IDLE,
+        value: 0,
+        reads: 150,
+    });
+
+    let mut pm = RockchipPM::with_regs(regs, RkBoard::Rk3588).with_time_source(PollCounter::new());
+    pm.timeouts_mut().set_step(TimeoutStep::IdleAck, 100);
+    pm.idle_retry_mut().set_domain(RK3588::AV1, retry);
+    pm
+}
+
+/// Values written to `BUS_IDLE_REQ0`, in order
+pub fn idle_request_writes<R: RegisterAccess>(pm: &RockchipPM<TraceRecorder<R>>) -> Vec<u32> {
+    pm.regs()
+        .trace()
+        .events()
+        .iter()
+        .filter(|e| e.access == Access::Write && e.offset == rk3588::BUS_IDLE_REQ0.offset() as u32)
+        .map(|e| e.value)
+        .collect()
+}
+
+// ========================================
+// Time and async
+// ========================================
+
+/// Clock that advances a fixed step every time it is read
+pub struct StepClock {
+    now: Cell<u64>,
+    step_us: u64,
+}
+
+impl StepClock {
+    pub fn new(step_us: u64) -> Self {
+        Self {
+            now: Cell::new(0),
+            step_us,
+        }
+    }
+}
+
+impl TimeSource for StepClock {
+    fn now_us(&self) -> u64 {
+        let now = self.now.get();
+        self.now.set(now + self.step_us);
+        now
+    }
+}
+
+/// Clock advancing one microsecond per read, with a timer fired by hand
+///
+/// Clones share the same clock, so a test keeps one after handing another
+/// to the driver.
+#[derive(Clone, Default)]
+pub struct AlarmClock(Arc<Mutex<Alarms>>);
+
+#[derive(Default)]
+struct Alarms {
+    now: u64,
+    armed: Vec<(u64, Waker)>,
+}
+
+impl AlarmClock {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Get the number of wakeups armed and not fired yet
+    pub fn armed(&self) -> usize {
+        self.0.lock().unwrap().armed.len()
+    }
+
+    /// Jump forward to the earliest armed deadline and fire it
+    ///
+    /// Returns the deadline, or `None` if nothing is armed.
+    pub fn fire_next(&self) -> Option<u64> {
+        let mut alarms = self.0.lock().unwrap();
+        let deadline = alarms.armed.iter().map(|(deadline, _)| *deadline).min()?;
+        alarms.now = alarms.now.max(deadline);
+        let (due, armed) = alarms.armed.drain(..).partition(|(at, _)| *at <= deadline);
+        alarms.armed = armed;
+        drop(alarms);
+        for (_, waker) in due {
+            waker.wake();
+        }
+        Some(deadline)
+    }
+}
+
+impl TimeSource for AlarmClock {
+    fn now_us(&self) -> u64 {
+        let mut alarms = self.0.lock().unwrap();
+        alarms.now += 1;
+        alarms.now - 1
+    }
+
+    fn wake_at(&self, deadline_us: u64, waker: &Waker) -> bool {
+        self.0
+            .lock()
+            .unwrap()
+            .armed
+            .push((deadline_us, waker.clone()));
+        true
+    }
+}
+
+/// Poll `a` and `b` in turn until both complete
+///
+/// Returns each output with the number of polls it took.
+pub fn join<A: Future, B: Future>(a: A, b: B) -> ((A::Output, usize), (B::Output, usize)) {
+    let (mut a, mut b) = (pin!(a), pin!(b));
+    let (mut out_a, mut out_b) = (None, None);
+    let mut cx = Context::from_waker(Waker::noop());
+    let mut polls = 0;
+    while out_a.is_none() || out_b.is_none() {
+        polls += 1;
+        if out_a.is_none()
+            && let Poll::Ready(out) = a.as_mut().poll(&mut cx)
+        {
+            out_a = Some((out, polls));
+        }
+        if out_b.is_none()
+            && let Poll::Ready(out) = b.as_mut().poll(&mut cx)
+        {
+            out_b = Some((out, polls));
+        }
+    }
+    (out_a.unwrap(), out_b.unwrap())
+}
+
+// ========================================
+// PMU interrupts
+// ========================================
+
+/// Interrupt wiring of the RK3588 model, in an unused part of its window
+///
+/// Test scaffolding only: the RK3588 interrupt registers and sources are not
+/// modeled.
+pub const SIM_IRQ: SimIrq = SimIrq {
+    layout: IrqLayout {
+        enable_offset: 0x3f0,
+        masked_when_set: true,
+        status_offset: 0x3f4,
+    },
+    pwr_gate: IRQ_PWR_GATE,
+    bus_idle: IRQ_BUS_IDLE,
+};
+/// Source the model latches on power switches
+pub const IRQ_PWR_GATE: u32 = 1 << 0;
+/// Source the model latches on idle request changes
+pub const IRQ_BUS_IDLE: u32 = 1 << 1;
+/// Source the model never latches
+pub const IRQ_UNUSED: u32 = 1 << 2;
+
+pub fn irq_rk3588_pmu() -> Rk3588Pmu {
+    Rk3588Pmu::new().with_irq(SIM_IRQ)
+}
+
+/// Waker counting how often it was woken
+pub struct CountingWaker(pub AtomicUsize);
+
+impl Wake for CountingWaker {
+    fn wake(self: Arc<Self>) {
+        self.0.fetch_add(1, Ordering::Relaxed);
+    }
+}
+
+// ========================================
+// QoS ports
+// ========================================
+
+/// Physical address of the first GPU port, the lowest RK3588 QoS port
+pub const QOS_GPU_PHYS: u64 = 0xfdf3_5000;
+/// Offset of the second GPU port
+pub const QOS_GPU_M1: usize = 0x200;
+/// Size of the QoS address space covering all GPU ports
+pub const QOS_GPU_SIZE: usize = 0x600 + QOS_PORT_SIZE;
+/// Size of the QoS address space from the GPU ports up to the last port
+pub const QOS_SPAN: usize = 0x5b000;
+/// PRIORITY and MODE registers within a port
+pub const QOS_PRIORITY: usize = 0x08;
+pub const QOS_MODE: usize = 0x0c;
+
+/// Host memory standing in for the mapped QoS ports, starting at the GPU
+pub struct QosMemory {
+    words: Vec<u32>,
+}
+
+impl QosMemory {
+    /// Cover `size` bytes of QoS address space starting at the GPU ports
+    pub fn new(size: usize) -> Self {
+        Self {
+            words: vec![0; size / 4],
+        }
+    }
+
+    pub fn base(&mut self) -> NonNull<u8> {
+        NonNull::new(self.words.as_mut_ptr().cast()).unwrap()
+    }
+
+    pub fn window(&mut self) -> QosWindow {
+        QosWindow::new(self.base(), QOS_GPU_PHYS, self.words.len() * 4)
+    }
+
+    pub fn read(&mut self, offset: usize) -> u32 {
+        unsafe { self.base().add(offset).cast::<u32>().read_volatile() }
+    }
+
+    pub fn write(&mut self, offset: usize, value: u32) {
+        unsafe { self.base().add(offset).cast::<u32>().write_volatile(value) }
+    }
+
+    /// Registers lose their value while the domain is powered off
+    pub fn reset(&mut self) {
+        for offset in (0..self.words.len() * 4).step_by(4) {
+            self.write(offset, 0);
+        }
+    }
+}
+
+/// Offset of a QoS port from the GPU ports
+pub fn qos_offset(pm: &RockchipPM<Rk3588Pmu>, domain: PowerDomain, port: usize) -> usize {
+    (pm.qos_ports(domain).unwrap()[port].phys_addr - QOS_GPU_PHYS) as usize
+}
+
+// ========================================
+// Logging
+// ========================================
+
+/// Logger keeping the warnings of every test thread
+struct WarningLog(Mutex<Vec<(ThreadId, String)>>);
+
+impl log::Log for WarningLog {
+    fn enabled(&self, metadata: &log::Metadata) -> bool {
+        metadata.level() <= log::Level::Warn
+    }
+
+    fn log(&self, record: &log::Record) {
+        if self.enabled(record.metadata()) {
+            let entry = (thread::current().id(), record.args().to_string());
+            self.0.lock().unwrap().push(entry);
+        }
+    }
+
+    fn flush(&self) {}
+}
+
+static WARNINGS: WarningLog = WarningLog(Mutex::new(Vec::new()));
+
+/// Get the warnings logged by the calling test so far
+pub fn warnings() -> Vec<String> {
+    let _ = log::set_logger(&WARNINGS);
+    log::set_max_level(log::LevelFilter::Warn);
+    let id = thread::current().id();
+    let entries = WARNINGS.0.lock().unwrap();
+    entries
+        .iter()
+        .filter(|(thread, _)| *thread == id)
+        .map(|(_, message)| message.clone())
+        .collect()
+}
+
+// ========================================
+// Traces
+// ========================================
+
+/// Golden trace of an AV1 power-off/power-on cycle on the RK3588 model
+///
+/// Regenerate with `UPDATE_GOLDEN=1 cargo test --target <host> --test sim`
+/// after an intentional change to the register sequence.
+pub const AV1_CYCLE_GOLDEN: &str = "tests/golden/rk3588_av1_cycle.trace";
+
+pub fn record_av1_cycle() -> Trace {
+    let mut pm = RockchipPM::with_regs(TraceRecorder::new(Rk3588Pmu::new()), RkBoard::Rk3588);
+    pm.power_domain_off(RK3588::AV1).unwrap();
+    pm.power_domain_on(RK3588::AV1).unwrap();
+    pm.regs().take_trace()
+}
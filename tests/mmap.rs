@@ -0,0 +1,115 @@
+//! Host tests for the mmap register backend, using a register image file
+
+use std::{fs, os::unix::fs::FileExt, path::PathBuf};
+
+use rockchip_pm::{
+    MmapRegion, PowerError, QosSettings, RK3588, RegisterAccess, RkBoard, RockchipPM,
+    registers::rk3588::{self, PMU_PWR_GATE_CON0, PMU_REPAIR_STATUS},
+};
+
+/// File offset of the PMU window, deliberately not page aligned
+const WINDOW_OFFSET: usize = 0x1010;
+const WINDOW_SIZE: usize = 0x400;
+const REPAIR_STATUS: usize = rk3588::REPAIR_STATUS.offset();
+const PWR_GATE_CON0: usize = rk3588::PWR_GATE_CON0.offset();
+/// File offset of the QoS window, standing in for the GPU ports
+const QOS_OFFSET: usize = 0x2000;
+/// Physical address of the first GPU port
+const QOS_GPU_PHYS: u64 = 0xfdf3_5000;
+/// Second GPU port and its PRIORITY register
+const QOS_GPU_M1: usize = 0x200;
+const QOS_PRIORITY: usize = 0x08;
+
+/// Register image file, removed when dropped
+struct Image(PathBuf);
+
+impl Image {
+    fn new(name: &str) -> Self {
+        let path =
+            std::env::temp_dir().join(format!("rockchip-pm-{}-{name}.img", std::process::id()));
+        let mut image = vec![0u8; 0x3000];
+        // AV1 powered on, everything else reports powered off
+        image[WINDOW_OFFSET + REPAIR_STATUS..][..4]
+            .copy_from_slice(&PMU_REPAIR_STATUS::AV1::SET.value.to_le_bytes());
+        fs::write(&path, image).unwrap();
+        Self(path)
+    }
+
+    fn read_u32(&self, offset: usize) -> u32 {
+        let mut value = [0; 4];
+        fs::File::open(&self.0)
+            .unwrap()
+            .read_exact_at(&mut value, offset as u64)
+            .unwrap();
+        u32::from_le_bytes(value)
+    }
+}
+
+impl Drop for Image {
+    fn drop(&mut self) {
+        let _ = fs::remove_file(&self.0);
+    }
+}
+
+#[test]
+fn test_mmap_register_image() {
+    let image = Image::new("image");
+    let region = MmapRegion::open(&image.0, WINDOW_OFFSET as u64, WINDOW_SIZE).unwrap();
+    assert_eq!(region.len(), WINDOW_SIZE);
+
+    let pm = RockchipPM::from_mmap(region, RkBoard::Rk3588).unwrap();
+    assert_eq!(pm.is_domain_on(&RK3588::AV1), Ok(true));
+    assert_eq!(pm.is_domain_on(&RK3588::GPU), Ok(false));
+
+    // Writes go straight to the backing file
+    pm.regs()
+        .write_hiword(PWR_GATE_CON0, PMU_PWR_GATE_CON0::AV1::SET.value, u32::MAX)
+        .unwrap();
+    assert_eq!(image.read_u32(WINDOW_OFFSET + PWR_GATE_CON0), 0x1000_1000);
+
+    assert_eq!(
+        pm.regs().read_u32(WINDOW_SIZE),
+        Err(PowerError::RegisterOutOfRange)
+    );
+}
+
+#[test]
+fn test_mmap_window_too_small() {
+    let image = Image::new("small");
+    let region = MmapRegion::open(&image.0, WINDOW_OFFSET as u64, REPAIR_STATUS).unwrap();
+
+    assert!(matches!(
+        RockchipPM::from_mmap(region, RkBoard::Rk3588),
+        Err(PowerError::RegisterOutOfRange)
+    ));
+}
+
+#[test]
+fn test_mmap_qos_window() {
+    let image = Image::new("qos");
+    let region = MmapRegion::open(&image.0, WINDOW_OFFSET as u64, WINDOW_SIZE).unwrap();
+    let qos = MmapRegion::open(&image.0, QOS_OFFSET as u64, 0x1000).unwrap();
+    let mut pm = RockchipPM::from_mmap(region, RkBoard::Rk3588)
+        .unwrap()
+        .with_qos_mapper(qos.into_qos_window(QOS_GPU_PHYS));
+
+    // Report the GPU powered on as well
+    let status = pm.regs().read_u32(REPAIR_STATUS).unwrap();
+    pm.regs()
+        .write_u32(REPAIR_STATUS, status | PMU_REPAIR_STATUS::GPU::SET.value)
+        .unwrap();
+
+    let settings = QosSettings {
+        read_priority: 3,
+        write_priority: 3,
+        ..pm.qos_settings(RK3588::GPU, 1).unwrap()
+    };
+    pm.set_qos_settings(RK3588::GPU, 1, &settings).unwrap();
+
+    // The driver owns the QoS mapping, so writes land in the file
+    assert_eq!(
+        image.read_u32(QOS_OFFSET + QOS_GPU_M1 + QOS_PRIORITY),
+        0x0303
+    );
+    assert_eq!(pm.qos_settings(RK3588::GPU, 1), Ok(settings));
+}
@@ -0,0 +1,1787 @@
+//! Host-side tests running the power sequences against the simulated PMUs
+
+mod common;
+
+use core::{
+    pin::pin,
+    ptr::NonNull,
+    sync::atomic::{AtomicUsize, Ordering},
+    task::{Context, Poll, Waker},
+};
+use std::sync::Arc;
+
+use common::*;
+use rockchip_pm::{
+    PmuRegs, PowerDomain, PowerError, PowerStep, QOS_PORT_SIZE, QosMapper, QosMode, QosProfile,
+    QosSettings, RK3568, RK3588, RegisterAccess, RkBoard, RockchipPM,
+    irq::{IrqHandle, IrqLayout},
+    registers::{
+        rk3568::{self, PMU_BUS_IDLE as PMU_RK3568_BUS_IDLE, PMU_PWR_DWN_ST},
+        rk3588::{
+            self, PMU_BUS_IDLE, PMU_BUS_IDLE_REQ1, PMU_PWR_GATE_CON0, PMU_PWR_GATE_CON1,
+            PMU_PWR_GATE_ST, PMU_REPAIR_STATUS,
+        },
+    },
+    sim::{
+        Access, Fault, FaultInjector, Rk3568Pmu, Rk3588Pmu, SimIrq, Trace, TraceRecorder,
+        TraceReplayer,
+    },
+    time::{IdleRetry, PollCounter, TimeSource, TimeoutPolicy, TimeoutStep},
+};
+
+// ========================================
+// RK3588 PMU model
+// ========================================
+
+#[test]
+fn test_rk3588_sim_reset_state() {
+    let pm = rk3588_pm();
+
+    for domain in [RK3588::GPU, RK3588::NPUTOP, RK3588::AV1, RK3588::VO1] {
+        assert!(pm.regs().is_domain_powered(domain));
+        assert!(!pm.regs().is_domain_idle(domain));
+        assert_eq!(pm.is_domain_on(&domain), Ok(true));
+        assert_eq!(pm.is_domain_idle(&domain), Ok(false));
+    }
+}
+
+#[test]
+fn test_rk3588_sim_hiword_write_enable() {
+    let pmu = Rk3588Pmu::new();
+
+    let con0 = rk3588::PWR_GATE_CON0.offset();
+    let gpu = PMU_PWR_GATE_CON0::GPU::SET.value;
+
+    // Without write-enable bits the write is ignored
+    pmu.write_u32(con0, gpu).unwrap();
+    assert!(pmu.is_domain_powered(RK3588::GPU));
+
+    // With write-enable only the enabled bits change
+    pmu.write_u32(con0, gpu << 16 | 0xffff).unwrap();
+    assert!(!pmu.is_domain_powered(RK3588::GPU));
+    assert!(pmu.is_domain_powered(RK3588::NPUTOP));
+    assert_eq!(pmu.read_u32(con0).unwrap(), gpu);
+
+    // Status registers are read-only
+    let repair = rk3588::REPAIR_STATUS.offset();
+    pmu.write_u32(repair, 0xffff_ffff).unwrap();
+    assert_eq!(
+        pmu.read_u32(repair).unwrap() & PMU_REPAIR_STATUS::GPU::SET.value,
+        0
+    );
+}
+
+#[test]
+fn test_rk3588_power_off_on_cycle() {
+    let mut pm = rk3588_pm();
+
+    pm.power_domain_off(RK3588::AV1).unwrap();
+    assert!(!pm.regs().is_domain_powered(RK3588::AV1));
+    assert!(pm.regs().is_domain_idle(RK3588::AV1));
+    assert_eq!(pm.is_domain_on(&RK3588::AV1), Ok(false));
+
+    pm.power_domain_on(RK3588::AV1).unwrap();
+    assert!(pm.regs().is_domain_powered(RK3588::AV1));
+    assert!(!pm.regs().is_domain_idle(RK3588::AV1));
+    assert_eq!(pm.is_domain_on(&RK3588::AV1), Ok(true));
+}
+
+#[test]
+fn test_rk3588_second_register_bank() {
+    let mut pm = rk3588_pm();
+
+    // VO1 is gated through PWR_GATE_CON1 and BUS_IDLE_REQ1
+    pm.power_domain_off(RK3588::VO1).unwrap();
+    let vo1_pwr = PMU_PWR_GATE_CON1::VO1::SET.value;
+    let vo1_req = PMU_BUS_IDLE_REQ1::VO1::SET.value;
+    let vo1_idle = PMU_BUS_IDLE::VO1::SET.value;
+    let regs = pm.regs();
+    assert_eq!(regs.peek(rk3588::PWR_GATE_CON1.offset()) & vo1_pwr, vo1_pwr);
+    assert_eq!(regs.peek(rk3588::BUS_IDLE_REQ1.offset()) & vo1_req, vo1_req);
+    assert_eq!(regs.peek(rk3588::BUS_IDLE_ST.offset()) & vo1_idle, vo1_idle);
+    assert!(pm.regs().is_domain_powered(RK3588::VOP));
+
+    pm.power_domain_on(RK3588::VO1).unwrap();
+    assert_eq!(pm.is_domain_on(&RK3588::VO1), Ok(true));
+}
+
+#[test]
+fn test_rk3588_status_register_domains() {
+    let mut pm = rk3588_pm();
+
+    // NVM reports its power state through PWR_GATE_ST instead of repair status
+    pm.power_domain_off(RK3588::NVM).unwrap();
+    assert_eq!(pm.is_domain_on(&RK3588::NVM), Ok(false));
+    let nvm = PMU_PWR_GATE_ST::NVM::SET.value;
+    assert_eq!(pm.regs().peek(rk3588::PWR_GATE_ST.offset()) & nvm, nvm);
+
+    pm.power_domain_on(RK3588::NVM).unwrap();
+    assert_eq!(pm.is_domain_on(&RK3588::NVM), Ok(true));
+}
+
+#[test]
+fn test_rk3588_npu_hierarchy_with_deps() {
+    let mut pm = rk3588_pm();
+
+    assert_eq!(
+        pm.power_domain_on_with_deps(RK3588::NPU1),
+        Err(PowerError::DependencyNotMet)
+    );
+
+    pm.power_domain_on_with_deps(RK3588::NPUTOP).unwrap();
+    pm.power_domain_on_with_deps(RK3588::NPU1).unwrap();
+    pm.power_domain_on_with_deps(RK3588::NPU2).unwrap();
+
+    assert_eq!(
+        pm.power_domain_off_with_deps(RK3588::NPUTOP),
+        Err(PowerError::DependencyNotMet)
+    );
+
+    pm.power_domain_off_with_deps(RK3588::NPU2).unwrap();
+    pm.power_domain_off_with_deps(RK3588::NPU1).unwrap();
+    pm.power_domain_off_with_deps(RK3588::NPUTOP).unwrap();
+
+    for domain in [RK3588::NPUTOP, RK3588::NPU1, RK3588::NPU2] {
+        assert!(!pm.regs().is_domain_powered(domain));
+        assert!(pm.regs().is_domain_idle(domain));
+    }
+    assert!(pm.get_active_domains().is_empty());
+}
+
+#[test]
+fn test_rk3588_typed_registers() {
+    use rockchip_pm::registers::rk3588::*;
+
+    let mut pm = rk3588_pm();
+    pm.power_domain_off(RK3588::AV1).unwrap();
+    pm.power_domain_off(RK3588::VO1).unwrap();
+
+    let regs = pm.regs();
+    assert!(
+        PWR_GATE_CON0
+            .get(regs)
+            .unwrap()
+            .is_set(PMU_PWR_GATE_CON0::AV1)
+    );
+    assert!(
+        !PWR_GATE_CON0
+            .get(regs)
+            .unwrap()
+            .is_set(PMU_PWR_GATE_CON0::VDPU)
+    );
+    assert!(
+        PWR_GATE_CON1
+            .get(regs)
+            .unwrap()
+            .is_set(PMU_PWR_GATE_CON1::VO1)
+    );
+    assert!(
+        BUS_IDLE_REQ0
+            .get(regs)
+            .unwrap()
+            .is_set(PMU_BUS_IDLE_REQ0::AV1)
+    );
+    assert!(
+        BUS_IDLE_REQ1
+            .get(regs)
+            .unwrap()
+            .is_set(PMU_BUS_IDLE_REQ1::VO1)
+    );
+
+    let ack = BUS_IDLE_ACK.get(regs).unwrap();
+    let idle = BUS_IDLE_ST.get(regs).unwrap();
+    for field in [PMU_BUS_IDLE::AV1, PMU_BUS_IDLE::VO1] {
+        assert!(ack.is_set(field));
+        assert!(idle.is_set(field));
+    }
+    assert!(!idle.is_set(PMU_BUS_IDLE::VOP));
+
+    let repair = REPAIR_STATUS.get(regs).unwrap();
+    assert!(!repair.is_set(PMU_REPAIR_STATUS::AV1));
+    assert!(!repair.is_set(PMU_REPAIR_STATUS::VO1));
+    assert!(repair.is_set(PMU_REPAIR_STATUS::GPU));
+
+    let chain = CHAIN_STATUS0.get(regs).unwrap();
+    assert!(!chain.is_set(PMU_MEM_STATUS0::AV1));
+    assert!(chain.is_set(PMU_MEM_STATUS0::VDPU));
+
+    // Typed writes go through the same hi-word write-enable path
+    PWR_GATE_CON0
+        .set(
+            regs,
+            PMU_PWR_GATE_CON0::AV1::CLEAR + PMU_PWR_GATE_CON0::WRITE_ENABLE.val(AV1_PWR),
+        )
+        .unwrap();
+    assert!(regs.is_domain_powered(RK3588::AV1));
+}
+
+#[test]
+fn test_rk3588_qos_port_table() {
+    let pm = rk3588_pm();
+    // Domains with `pm_qos` in the upstream rk3588-base.dtsi and their port count
+    let qos_domains = [
+        (RK3588::GPU, 4),
+        (RK3588::NPUTOP, 3),
+        (RK3588::NPU1, 1),
+        (RK3588::NPU2, 1),
+        (RK3588::VENC0, 3),
+        (RK3588::VENC1, 3),
+        (RK3588::RKVDEC0, 1),
+        (RK3588::RKVDEC1, 1),
+        (RK3588::VDPU, 9),
+        (RK3588::RGA30, 1),
+        (RK3588::AV1, 1),
+        (RK3588::VI, 4),
+        (RK3588::FEC, 2),
+        (RK3588::ISP1, 2),
+        (RK3588::RGA31, 1),
+        (RK3588::VOP, 2),
+        (RK3588::VO0, 1),
+        (RK3588::VO1, 2),
+        (RK3588::SDIO, 1),
+        (RK3588::USB, 4),
+        (RK3588::SDMMC, 1),
+    ];
+
+    let mut addrs = Vec::new();
+    for (domain, count) in qos_domains {
+        let ports = pm.qos_ports(domain).unwrap();
+        assert_eq!(ports.len(), count, "{domain:?}");
+        for port in ports {
+            assert_eq!(port.domain, domain, "{}", port.name);
+            assert_eq!(port.phys_addr & 0xff, 0, "{}", port.name);
+            addrs.push(port.phys_addr);
+        }
+    }
+
+    // Full 64-bit addresses, one non-overlapping register block per port
+    assert_eq!(pm.qos_ports(RK3588::GPU).unwrap()[0].phys_addr, 0xfdf3_5000);
+    addrs.sort();
+    assert_eq!(addrs.len(), 48);
+    for pair in addrs.windows(2) {
+        assert!(pair[1] - pair[0] >= QOS_PORT_SIZE as u64, "{:#x}", pair[1]);
+    }
+
+    for domain in [
+        RK3588::NPU,
+        RK3588::VCODEC,
+        RK3588::PHP,
+        RK3588::GMAC,
+        RK3588::PCIE,
+        RK3588::NVM,
+        RK3588::AUDIO,
+    ] {
+        assert!(pm.qos_ports(domain).unwrap().is_empty(), "{domain:?}");
+    }
+    assert_eq!(
+        pm.qos_ports(PowerDomain::new(usize::MAX)),
+        Err(PowerError::DomainNotFound)
+    );
+}
+
+// ========================================
+// RK3568 PMU model
+// ========================================
+
+#[test]
+fn test_rk3568_sim_register_layout() {
+    let pmu = Rk3568Pmu::new();
+
+    use rockchip_pm::registers::rk3568::*;
+
+    // VI: power bit 6 in PMU_PWR_DWN_CON, idle bit 3 in PMU_BUS_IDLE_REQ
+    let vi_pwr = PMU_PWR_DWN_CON::VI::SET.value;
+    let vi_idle = PMU_BUS_IDLE_REQ::VI::SET.value;
+    pmu.write_u32(PWR_DWN_CON.offset(), vi_pwr << 16 | vi_pwr)
+        .unwrap();
+    pmu.write_u32(BUS_IDLE_REQ.offset(), vi_idle << 16 | vi_idle)
+        .unwrap();
+
+    assert!(!pmu.is_domain_powered(RK3568::VI));
+    assert!(pmu.is_domain_idle(RK3568::VI));
+    assert_eq!(pmu.read_u32(PWR_DWN_ST.offset()).unwrap(), vi_pwr);
+    assert_eq!(pmu.read_u32(BUS_IDLE_ST.offset()).unwrap(), vi_idle);
+    assert_eq!(pmu.read_u32(BUS_IDLE_ACK.offset()).unwrap(), vi_idle);
+
+    // Writes without write-enable bits are ignored
+    pmu.write_u32(PWR_DWN_CON.offset(), 0).unwrap();
+    assert!(!pmu.is_domain_powered(RK3568::VI));
+}
+
+#[test]
+fn test_rk3568_power_off_on_cycle() {
+    let mut pm = rk3568_pm();
+
+    for domain in [RK3568::VI, RK3568::VO, RK3568::RGA, RK3568::PIPE] {
+        pm.power_domain_off(domain).unwrap();
+        assert!(!pm.regs().is_domain_powered(domain));
+        assert!(pm.regs().is_domain_idle(domain));
+        assert_eq!(pm.is_domain_on(&domain), Ok(false));
+        assert_eq!(pm.is_domain_idle(&domain), Ok(true));
+
+        pm.power_domain_on(domain).unwrap();
+        assert!(pm.regs().is_domain_powered(domain));
+        assert!(!pm.regs().is_domain_idle(domain));
+        assert_eq!(pm.is_domain_on(&domain), Ok(true));
+    }
+}
+
+#[test]
+fn test_rk3568_domains_are_independent() {
+    let mut pm = rk3568_pm();
+
+    pm.power_domain_off(RK3568::PIPE).unwrap();
+
+    for domain in [RK3568::VI, RK3568::VO, RK3568::RGA, RK3568::GPU] {
+        assert!(pm.regs().is_domain_powered(domain));
+        assert!(!pm.regs().is_domain_idle(domain));
+    }
+    assert_eq!(
+        pm.regs().peek(rk3568::PWR_DWN_ST.offset()),
+        PMU_PWR_DWN_ST::PIPE::SET.value
+    );
+    assert_eq!(
+        pm.regs().peek(rk3568::BUS_IDLE_ST.offset()),
+        PMU_RK3568_BUS_IDLE::PIPE::SET.value
+    );
+}
+
+#[test]
+fn test_rk3568_vpu_dependency() {
+    let mut pm = rk3568_pm();
+
+    assert_eq!(
+        pm.power_domain_on_with_deps(RK3568::RKVDEC),
+        Err(PowerError::DependencyNotMet)
+    );
+    assert_eq!(
+        pm.power_domain_on_with_deps(RK3568::RKVENC),
+        Err(PowerError::DependencyNotMet)
+    );
+    assert!(pm.get_active_domains().is_empty());
+}
+
+#[test]
+fn test_rk3568_typed_registers() {
+    use rockchip_pm::registers::rk3568::*;
+
+    let mut pm = rk3568_pm();
+    pm.power_domain_off(RK3568::PIPE).unwrap();
+
+    let regs = pm.regs();
+    let con = PWR_DWN_CON.get(regs).unwrap();
+    assert!(con.is_set(PMU_PWR_DWN_CON::PIPE));
+    assert!(!con.is_set(PMU_PWR_DWN_CON::VO));
+    assert!(PWR_DWN_ST.get(regs).unwrap().is_set(PMU_PWR_DWN_ST::PIPE));
+    assert!(
+        BUS_IDLE_REQ
+            .get(regs)
+            .unwrap()
+            .is_set(PMU_BUS_IDLE_REQ::PIPE)
+    );
+    assert!(BUS_IDLE_ACK.get(regs).unwrap().is_set(PMU_BUS_IDLE::PIPE));
+    assert!(BUS_IDLE_ST.get(regs).unwrap().is_set(PMU_BUS_IDLE::PIPE));
+    assert!(!BUS_IDLE_ST.get(regs).unwrap().is_set(PMU_BUS_IDLE::RGA));
+}
+
+#[test]
+fn test_rk3568_qos_port_table() {
+    let pm = rk3568_pm();
+    let vpu = pm.qos_ports(RK3568::VPU).unwrap();
+    assert_eq!(vpu.len(), 2);
+    assert!(vpu.iter().all(|port| port.domain == RK3568::VPU));
+    assert_eq!(pm.qos_ports(RK3568::GPU).unwrap()[0].phys_addr, 0xfe12_8000);
+}
+
+// ========================================
+// Hi-word writes
+// ========================================
+
+#[test]
+fn test_hiword_write() {
+    let pmu = Rk3588Pmu::new();
+
+    pmu.write_hiword(rk3588::PWR_GATE_CON0.offset(), AV1_PWR, u32::MAX)
+        .unwrap();
+    assert!(!pmu.is_domain_powered(RK3588::AV1));
+    assert!(pmu.is_domain_powered(RK3588::GPU));
+
+    // Masks reaching into the write-enable half are rejected
+    assert_eq!(
+        pmu.write_hiword(rk3588::PWR_GATE_CON0.offset(), 1 << 16, 0),
+        Err(PowerError::InvalidWriteMask)
+    );
+    assert!(!pmu.is_domain_powered(RK3588::AV1));
+}
+
+#[test]
+fn test_control_registers_never_read() {
+    // Power and idle requests are single hi-word writes, never read-modify-write
+    let trace = record_av1_cycle();
+    assert!(trace.events().iter().all(|e| e.access == Access::Write
+        || (e.offset != rk3588::PWR_GATE_CON0.offset() as u32
+            && e.offset != rk3588::BUS_IDLE_REQ0.offset() as u32)));
+}
+
+// ========================================
+// Memory-mapped register window
+// ========================================
+
+#[test]
+fn test_pmu_regs_window_bounds() {
+    let mut window = vec![0u32; 0x400 / 4];
+    let base = NonNull::new(window.as_mut_ptr().cast::<u8>()).unwrap();
+    let regs = PmuRegs::new(base, 0x400);
+
+    regs.write_u32(0x3fc, 0x1234).unwrap();
+    assert_eq!(regs.read_u32(0x3fc), Ok(0x1234));
+
+    for offset in [0x400, 0x3fe, 0x3fd, usize::MAX - 3] {
+        assert_eq!(regs.read_u32(offset), Err(PowerError::RegisterOutOfRange));
+        assert_eq!(
+            regs.write_u32(offset, 0),
+            Err(PowerError::RegisterOutOfRange)
+        );
+    }
+    assert_eq!(regs.read_u32(0x3fc), Ok(0x1234));
+
+    // The typed register accessor is checked as well
+    assert!(regs.pwr_con0().is_ok());
+    for size in [0, 2] {
+        assert!(matches!(
+            PmuRegs::new(base, size).pwr_con0(),
+            Err(PowerError::RegisterOutOfRange)
+        ));
+    }
+}
+
+#[test]
+fn test_constructor_checks_table_offsets() {
+    let mut window = vec![0u32; 0x400 / 4];
+    let base = NonNull::new(window.as_mut_ptr().cast::<u8>()).unwrap();
+
+    // REPAIR_STATUS is the highest RK3588 register
+    let repair = rk3588::REPAIR_STATUS.offset();
+    assert!(RockchipPM::new(base, repair + 4, RkBoard::Rk3588).is_ok());
+    assert!(matches!(
+        RockchipPM::new(base, repair, RkBoard::Rk3588),
+        Err(PowerError::RegisterOutOfRange)
+    ));
+
+    // PMU_PWR_DWN_CON is the highest RK3568 register
+    let con = rk3568::PWR_DWN_CON.offset();
+    let pm = RockchipPM::new(base, con + 4, RkBoard::Rk3568).unwrap();
+    assert_eq!(pm.is_domain_on(&RK3568::GPU), Ok(true));
+    assert!(matches!(
+        RockchipPM::new(base, con, RkBoard::Rk3568),
+        Err(PowerError::RegisterOutOfRange)
+    ));
+}
+
+// ========================================
+// Fault injection
+// ========================================
+
+#[test]
+fn test_fault_idle_ack_timeout() {
+    let mut pm = faulty_rk3588_pm();
+    pm.regs().inject(Fault::Stuck {
+        offset: rk3588::BUS_IDLE_ACK.offset(),
+        mask: AV1_IDLE,
+        value: 0,
+    });
+
+    assert_eq!(
+        pm.power_domain_off(RK3588::AV1),
+        Err(PowerError::IdleAckTimeout)
+    );
+
+    // The idle request is latched but power was never removed
+    let pmu = pm.regs().inner();
+    assert_eq!(
+        pmu.peek(rk3588::BUS_IDLE_REQ0.offset()) & AV1_IDLE,
+        AV1_IDLE
+    );
+    assert_eq!(pmu.peek(rk3588::PWR_GATE_CON0.offset()) & AV1_PWR, 0);
+    assert!(pmu.is_domain_powered(RK3588::AV1));
+}
+
+#[test]
+fn test_fault_idle_request_timeout() {
+    let mut pm = faulty_rk3588_pm();
+    pm.regs().inject(Fault::Stuck {
+        offset: rk3588::BUS_IDLE_ST.offset(),
+        mask: AV1_IDLE,
+        value: 0,
+    });
+
+    assert_eq!(
+        pm.power_domain_off(RK3588::AV1),
+        Err(PowerError::IdleRequestTimeout)
+    );
+    assert!(pm.regs().inner().is_domain_powered(RK3588::AV1));
+}
+
+#[test]
+fn test_fault_power_stable_timeout() {
+    let mut pm = faulty_rk3588_pm();
+    pm.regs().inject(Fault::Stuck {
+        offset: rk3588::REPAIR_STATUS.offset(),
+        mask: AV1_REPAIR,
+        value: AV1_REPAIR,
+    });
+
+    assert_eq!(pm.power_domain_off(RK3588::AV1), Err(PowerError::Timeout));
+
+    // The power-down request went out; only the status never followed
+    let pmu = pm.regs().inner();
+    assert_eq!(pmu.peek(rk3588::PWR_GATE_CON0.offset()) & AV1_PWR, AV1_PWR);
+    assert!(pmu.is_domain_idle(RK3588::AV1));
+}
+
+#[test]
+fn test_fault_delayed_ack() {
+    let mut pm = faulty_rk3588_pm();
+
+    // A slow acknowledgment within the poll budget is tolerated
+    pm.regs().inject(Fault::Delayed {
+        offset: rk3588::BUS_IDLE_ACK.offset(),
+        mask: AV1_IDLE,
+        polls: 100,
+    });
+    pm.power_domain_off(RK3588::AV1).unwrap();
+    pm.power_domain_on(RK3588::AV1).unwrap();
+
+    // One that never arrives within the budget is not
+    pm.regs().clear();
+    pm.regs().inject(Fault::Delayed {
+        offset: rk3588::BUS_IDLE_ACK.offset(),
+        mask: AV1_IDLE,
+        polls: u32::MAX,
+    });
+    assert_eq!(
+        pm.power_domain_off(RK3588::AV1),
+        Err(PowerError::IdleAckTimeout)
+    );
+}
+
+#[test]
+fn test_fault_status_glitch() {
+    let mut pm = faulty_rk3588_pm();
+    pm.power_domain_off(RK3588::AV1).unwrap();
+
+    // The flipped bit is seen exactly once
+    pm.regs().inject(Fault::Flip {
+        offset: rk3588::REPAIR_STATUS.offset(),
+        mask: AV1_REPAIR,
+        nth: 0,
+    });
+    assert_eq!(pm.is_domain_on(&RK3588::AV1), Ok(true));
+    assert_eq!(pm.is_domain_on(&RK3588::AV1), Ok(false));
+
+    // A spurious "powered off" reading does not fail the power-on wait; the
+    // first read is the check whether the domain is already on
+    pm.regs().clear();
+    pm.regs().inject(Fault::Flip {
+        offset: rk3588::REPAIR_STATUS.offset(),
+        mask: AV1_REPAIR,
+        nth: 1,
+    });
+    pm.power_domain_on(RK3588::AV1).unwrap();
+    assert!(pm.regs().inner().is_domain_powered(RK3588::AV1));
+}
+
+#[test]
+fn test_fault_bus_error() {
+    let mut pm = faulty_rk3588_pm();
+    pm.regs().inject(Fault::BusError {
+        offset: rk3588::PWR_GATE_CON0.offset(),
+    });
+
+    assert_eq!(
+        pm.power_domain_off(RK3588::AV1),
+        Err(PowerError::HardwareError)
+    );
+    assert!(pm.regs().inner().is_domain_powered(RK3588::AV1));
+}
+
+#[test]
+fn test_idle_retry_recovers_ack_hiccup() {
+    let mut pm = av1_ack_hiccup(IdleRetry::NONE);
+    assert_eq!(
+        pm.power_domain_off(RK3588::AV1),
+        Err(PowerError::IdleAckTimeout)
+    );
+
+    let mut pm = av1_ack_hiccup(IdleRetry::new(2, 10));
+    pm.power_domain_off(RK3588::AV1).unwrap();
+    assert!(!pm.regs().inner().inner().is_domain_powered(RK3588::AV1));
+
+    // The request is simply re-issued
+    let set = AV1_IDLE << 16 | AV1_IDLE;
+    assert_eq!(idle_request_writes(&pm), [set, set]);
+}
+
+#[test]
+fn test_idle_retry_toggles_request() {
+    let mut pm = av1_ack_hiccup(IdleRetry::new(1, 10).with_toggle(true));
+    pm.power_domain_off(RK3588::AV1).unwrap();
+
+    let set = AV1_IDLE << 16 | AV1_IDLE;
+    let clear = AV1_IDLE << 16;
+    assert_eq!(idle_request_writes(&pm), [set, clear, set]);
+}
+
+#[test]
+fn test_idle_retry_gives_up() {
+    let mut pm = av1_ack_hiccup(IdleRetry::new(3, 10));
+    pm.regs().inner().inject(Fault::Stuck {
+        offset: rk3588::BUS_IDLE_ACK.offset(),
+        mask: AV1_IDLE,
+        value: 0,
+    });
+    assert_eq!(
+        pm.power_domain_off(RK3588::AV1),
+        Err(PowerError::IdleAckTimeout)
+    );
+    assert_eq!(idle_request_writes(&pm).len(), 4);
+    assert!(pm.regs().inner().inner().is_domain_powered(RK3588::AV1));
+
+    // Backoff doubles with every retry
+    let retry = IdleRetry::new(3, 10);
+    assert_eq!([1, 2, 3].map(|n| retry.delay_before(n)), [10, 20, 40]);
+}
+
+// ========================================
+// Waiting for external state changes
+// ========================================
+
+#[test]
+fn test_wait_domain_state() {
+    let pm = faulty_rk3588_pm().with_time_source(PollCounter::new());
+
+    // Firmware finishes powering up AV1 after a few polls
+    pm.regs().inject(Fault::StuckFor {
+        offset: rk3588::REPAIR_STATUS.offset(),
+        mask: AV1_REPAIR,
+        value: 0,
+        reads: 5,
+    });
+    assert_eq!(pm.is_domain_on(&RK3588::AV1), Ok(false));
+    pm.wait_domain_state(RK3588::AV1, true, 100).unwrap();
+
+    pm.regs().inject(Fault::Stuck {
+        offset: rk3588::REPAIR_STATUS.offset(),
+        mask: AV1_REPAIR,
+        value: 0,
+    });
+    assert_eq!(
+        pm.wait_domain_state(RK3588::AV1, true, 100),
+        Err(PowerError::Timeout)
+    );
+    assert_eq!(
+        pm.wait_domain_state(PowerDomain::new(usize::MAX), true, 100),
+        Err(PowerError::DomainNotFound)
+    );
+}
+
+#[test]
+fn test_wait_domain_idle() {
+    let mut pm = faulty_rk3588_pm().with_time_source(PollCounter::new());
+    pm.wait_domain_idle(RK3588::AV1, false, 100).unwrap();
+
+    pm.power_domain_off(RK3588::AV1).unwrap();
+    pm.wait_domain_idle(RK3588::AV1, true, 100).unwrap();
+    assert_eq!(
+        pm.wait_domain_idle(RK3588::AV1, false, 100),
+        Err(PowerError::Timeout)
+    );
+}
+
+// ========================================
+// Time sources
+// ========================================
+
+#[test]
+fn test_timeout_budget_is_wall_clock() {
+    let regs = TraceRecorder::new(FaultInjector::new(Rk3588Pmu::new()));
+    regs.inner().inject(Fault::Stuck {
+        offset: rk3588::BUS_IDLE_ACK.offset(),
+        mask: AV1_IDLE,
+        value: 0,
+    });
+
+    // At 1 ms per clock read, the 10 ms acknowledgment budget allows 10 polls
+    let mut pm =
+        RockchipPM::with_regs(regs, RkBoard::Rk3588).with_time_source(StepClock::new(1000));
+    assert_eq!(
+        pm.power_domain_off(RK3588::AV1),
+        Err(PowerError::IdleAckTimeout)
+    );
+
+    let polls = pm
+        .regs()
+        .trace()
+        .events()
+        .iter()
+        .filter(|e| e.offset == rk3588::BUS_IDLE_ACK.offset() as u32)
+        .count();
+    assert_eq!(polls, 10);
+}
+
+#[test]
+fn test_poll_counter_time_source() {
+    let mut pm = faulty_rk3588_pm().with_time_source(PollCounter::new());
+    pm.regs().inject(Fault::Delayed {
+        offset: rk3588::BUS_IDLE_ACK.offset(),
+        mask: AV1_IDLE,
+        polls: 100,
+    });
+
+    pm.power_domain_off(RK3588::AV1).unwrap();
+    assert!(!pm.regs().inner().is_domain_powered(RK3588::AV1));
+}
+
+#[test]
+fn test_domain_step_timeout_override() {
+    let regs = TraceRecorder::new(FaultInjector::new(Rk3588Pmu::new()));
+    regs.inner().inject(Fault::Stuck {
+        offset: rk3588::BUS_IDLE_ACK.offset(),
+        mask: AV1_IDLE,
+        value: 0,
+    });
+
+    let mut pm =
+        RockchipPM::with_regs(regs, RkBoard::Rk3588).with_time_source(StepClock::new(1000));
+    pm.timeouts_mut()
+        .set_step(TimeoutStep::IdleAck, 5000)
+        .set_domain_step(RK3588::AV1, TimeoutStep::IdleAck, 3000);
+    assert_eq!(
+        pm.power_domain_off(RK3588::AV1),
+        Err(PowerError::IdleAckTimeout)
+    );
+
+    let polls = pm
+        .regs()
+        .trace()
+        .events()
+        .iter()
+        .filter(|e| e.offset == rk3588::BUS_IDLE_ACK.offset() as u32)
+        .count();
+    assert_eq!(polls, 3);
+}
+
+#[test]
+fn test_timeout_policy_lookup() {
+    let mut policy = TimeoutPolicy::new(1000);
+    policy
+        .set_step(TimeoutStep::Repair, 2000)
+        .set_domain(RK3588::GPU, 3000)
+        .set_domain_step(RK3588::GPU, TimeoutStep::PowerStable, 4000);
+
+    assert_eq!(policy.timeout_us(RK3588::AV1, TimeoutStep::IdleAck), 1000);
+    assert_eq!(policy.timeout_us(RK3588::AV1, TimeoutStep::Repair), 2000);
+    assert_eq!(policy.timeout_us(RK3588::GPU, TimeoutStep::Repair), 3000);
+    assert_eq!(
+        policy.timeout_us(RK3588::GPU, TimeoutStep::PowerStable),
+        4000
+    );
+
+    // Every wait gets the 10 ms budget of the Linux driver by default
+    let rk3588 = TimeoutPolicy::for_board(RkBoard::Rk3588);
+    assert_eq!(
+        rk3588.timeout_us(RK3588::NPUTOP, TimeoutStep::PowerStable),
+        10_000
+    );
+    assert_eq!(rk3588.timeout_us(RK3588::AV1, TimeoutStep::Repair), 10_000);
+    assert_eq!(rk3588_pm().timeouts(), &rk3588);
+}
+
+#[test]
+fn test_latency_stats_per_step() {
+    let mut pm = faulty_rk3588_pm()
+        .with_time_source(StepClock::new(1))
+        .with_latency_stats();
+    pm.regs().inject(Fault::Delayed {
+        offset: rk3588::BUS_IDLE_ACK.offset(),
+        mask: AV1_IDLE,
+        polls: 20,
+    });
+
+    pm.power_domain_off(RK3588::AV1).unwrap();
+    pm.power_domain_on(RK3588::AV1).unwrap();
+
+    let stats = pm.latency_stats().unwrap();
+    let idle = stats.step(RK3588::AV1, PowerStep::IdleRequest).unwrap();
+    let write = stats.step(RK3588::AV1, PowerStep::PowerWrite).unwrap();
+    assert_eq!(idle.count(), 2);
+    assert!(idle.max_us() >= 20);
+    assert!(idle.max_us() > write.max_us());
+    assert!(idle.min_us() <= idle.avg_us() && idle.avg_us() <= idle.max_us());
+
+    // The steps add up to the whole transition
+    let off = stats.transition(RK3588::AV1, false).unwrap();
+    let on = stats.transition(RK3588::AV1, true).unwrap();
+    let steps: u64 = stats
+        .domain_steps(RK3588::AV1)
+        .map(|(_, latency)| latency.total_us())
+        .sum();
+    assert_eq!(off.count(), 1);
+    assert_eq!(steps, off.total_us() + on.total_us());
+    assert_eq!(stats.domains().collect::<Vec<_>>(), [RK3588::AV1]);
+
+    // Failed transitions only contribute their steps
+    pm.clear_latency_stats();
+    pm.regs().inject(Fault::Stuck {
+        offset: rk3588::BUS_IDLE_ACK.offset(),
+        mask: AV1_IDLE,
+        value: 0,
+    });
+    assert!(pm.power_domain_off(RK3588::AV1).is_err());
+    let stats = pm.latency_stats().unwrap();
+    assert_eq!(stats.transition(RK3588::AV1, false), None);
+    assert!(
+        stats
+            .step(RK3588::AV1, PowerStep::IdleRequest)
+            .unwrap()
+            .max_us()
+            >= 10_000
+    );
+
+    pm.set_latency_stats(false);
+    assert_eq!(pm.latency_stats(), None);
+}
+
+// ========================================
+// Async transitions
+// ========================================
+
+#[test]
+fn test_async_power_off_yields_while_waiting() {
+    let pm = faulty_rk3588_pm().with_time_source(PollCounter::new());
+    pm.regs().inject(Fault::Delayed {
+        offset: rk3588::BUS_IDLE_ACK.offset(),
+        mask: AV1_IDLE,
+        polls: 20,
+    });
+
+    let mut off = pin!(pm.power_domain_off_async(RK3588::AV1));
+    let mut cx = Context::from_waker(Waker::noop());
+    assert_eq!(off.as_mut().poll(&mut cx), Poll::Pending);
+    // Suspended in the acknowledgment wait: idle requested, power untouched
+    assert!(pm.regs().inner().is_domain_powered(RK3588::AV1));
+
+    let mut polls = 1;
+    while off.as_mut().poll(&mut cx).is_pending() {
+        polls += 1;
+    }
+    assert!(polls >= 20);
+    assert!(!pm.regs().inner().is_domain_powered(RK3588::AV1));
+}
+
+#[test]
+fn test_async_concurrent_domains() {
+    let pm = faulty_rk3588_pm().with_time_source(PollCounter::new());
+    pm.regs().inject(Fault::Delayed {
+        offset: rk3588::BUS_IDLE_ACK.offset(),
+        mask: AV1_IDLE,
+        polls: 50,
+    });
+
+    // VO1 completes while AV1 is still waiting for its acknowledgment
+    let ((av1, av1_polls), (vo1, vo1_polls)) = join(
+        pm.power_domain_off_async(RK3588::AV1),
+        pm.power_domain_off_async(RK3588::VO1),
+    );
+    assert_eq!(av1, Ok(()));
+    assert_eq!(vo1, Ok(()));
+    assert!(vo1_polls < av1_polls);
+    assert_eq!(pm.is_domain_on(&RK3588::AV1), Ok(false));
+    assert_eq!(pm.is_domain_on(&RK3588::VO1), Ok(false));
+
+    let ((av1, _), (vo1, _)) = join(
+        pm.power_domain_on_async(RK3588::AV1),
+        pm.power_domain_on_async(RK3588::VO1),
+    );
+    assert_eq!((av1, vo1), (Ok(()), Ok(())));
+    assert_eq!(pm.is_domain_on(&RK3588::AV1), Ok(true));
+    assert_eq!(pm.is_domain_on(&RK3588::VO1), Ok(true));
+}
+
+#[test]
+fn test_async_timeout() {
+    let pm = faulty_rk3588_pm().with_time_source(PollCounter::new());
+    pm.regs().inject(Fault::Stuck {
+        offset: rk3588::BUS_IDLE_ACK.offset(),
+        mask: AV1_IDLE,
+        value: 0,
+    });
+
+    let ((av1, _), (vo1, _)) = join(
+        pm.power_domain_off_async(RK3588::AV1),
+        pm.power_domain_off_async(RK3588::VO1),
+    );
+    assert_eq!(av1, Err(PowerError::IdleAckTimeout));
+    assert_eq!(vo1, Ok(()));
+}
+
+// ========================================
+// Step-wise transitions
+// ========================================
+
+#[test]
+fn test_transition_pending_step() {
+    let pm = faulty_rk3588_pm().with_time_source(PollCounter::new());
+    pm.regs().inject(Fault::Delayed {
+        offset: rk3588::BUS_IDLE_ACK.offset(),
+        mask: AV1_IDLE,
+        polls: 5,
+    });
+
+    let mut off = pm.start_power_off(RK3588::AV1).unwrap();
+    assert_eq!(off.pending_step(), None);
+    assert!(pm.regs().inner().is_domain_powered(RK3588::AV1));
+
+    assert_eq!(off.poll(), Poll::Pending);
+    assert_eq!(off.pending_step(), Some(PowerStep::IdleRequest));
+    assert!(pm.regs().inner().is_domain_powered(RK3588::AV1));
+
+    while off.poll().is_pending() {}
+    assert!(off.is_done());
+    assert_eq!(off.result(), Some(Ok(())));
+    assert_eq!(off.pending_step(), None);
+    assert!(!pm.regs().inner().is_domain_powered(RK3588::AV1));
+
+    // A finished transition keeps reporting its result
+    assert_eq!(off.poll(), Poll::Ready(Ok(())));
+}
+
+#[test]
+fn test_transition_stuck_step_is_visible() {
+    let pm = faulty_rk3588_pm().with_time_source(PollCounter::new());
+    assert_eq!(
+        pm.start_power_off(RK3588::AV1).unwrap().poll(),
+        Poll::Ready(Ok(()))
+    );
+    pm.regs().inject(Fault::Stuck {
+        offset: rk3588::REPAIR_STATUS.offset(),
+        mask: AV1_REPAIR,
+        value: 0,
+    });
+
+    let mut on = pm.start_power_on(RK3588::AV1).unwrap();
+    assert!(on.is_power_on());
+    for _ in 0..10 {
+        assert_eq!(on.poll(), Poll::Pending);
+    }
+    assert_eq!(on.pending_step(), Some(PowerStep::PowerStable));
+
+    // Once the status follows, the next poll finishes the sequence
+    pm.regs().clear();
+    assert_eq!(on.poll(), Poll::Ready(Ok(())));
+    assert_eq!(pm.is_domain_on(&RK3588::AV1), Ok(true));
+}
+
+#[test]
+fn test_transition_interleaving() {
+    let pm = faulty_rk3588_pm().with_time_source(PollCounter::new());
+    pm.regs().inject(Fault::Stuck {
+        offset: rk3588::BUS_IDLE_ACK.offset(),
+        mask: AV1_IDLE,
+        value: 0,
+    });
+
+    let mut av1 = pm.start_power_off(RK3588::AV1).unwrap();
+    let mut vo1 = pm.start_power_off(RK3588::VO1).unwrap();
+    while !(av1.is_done() && vo1.is_done()) {
+        let _ = av1.poll();
+        let _ = vo1.poll();
+    }
+    assert_eq!(av1.result(), Some(Err(PowerError::IdleAckTimeout)));
+    assert_eq!(vo1.result(), Some(Ok(())));
+    assert_eq!(av1.domain(), RK3588::AV1);
+
+    assert!(matches!(
+        pm.start_power_on(PowerDomain::new(usize::MAX)),
+        Err(PowerError::DomainNotFound)
+    ));
+}
+
+// ========================================
+// PMU interrupts
+// ========================================
+
+#[test]
+fn test_irq_enable_mask_ack() {
+    let regs = TraceRecorder::new(irq_rk3588_pmu());
+    let mut pm = RockchipPM::with_regs(&regs, RkBoard::Rk3588);
+    assert_eq!(pm.enable_irq(1), Err(PowerError::IrqNotConfigured));
+    assert_eq!(pm.irq_handle().err(), Some(PowerError::IrqNotConfigured));
+
+    pm = pm.with_irq(SIM_IRQ.layout);
+    let irq = pm.irq_handle().unwrap();
+    pm.enable_irq(IRQ_PWR_GATE | IRQ_BUS_IDLE).unwrap();
+    pm.mask_irq(IRQ_BUS_IDLE).unwrap();
+    assert_eq!(pm.mask_irq(1 << 16), Err(PowerError::InvalidWriteMask));
+
+    // Powering AV1 off latches both events; only the enabled one interrupts
+    pm.power_domain_off(RK3588::AV1).unwrap();
+    assert_eq!(pm.pending_irq(), Ok(IRQ_PWR_GATE | IRQ_BUS_IDLE));
+    assert!(regs.inner().is_irq_asserted());
+    assert_eq!(irq.handle_irq(), Ok(IRQ_PWR_GATE | IRQ_BUS_IDLE));
+    assert_eq!(pm.pending_irq(), Ok(0));
+    assert!(!regs.inner().is_irq_asserted());
+
+    // Only the bus idle request of a power on is masked
+    pm.power_domain_on(RK3588::AV1).unwrap();
+    irq.ack_irq(IRQ_PWR_GATE).unwrap();
+    assert_eq!(irq.pending_irq(), Ok(IRQ_BUS_IDLE));
+    assert!(!regs.inner().is_irq_asserted());
+
+    let irq_offsets = [SIM_IRQ.layout.enable_offset, SIM_IRQ.layout.status_offset];
+    let writes: Vec<_> = regs
+        .trace()
+        .events()
+        .iter()
+        .filter(|e| e.access == Access::Write && irq_offsets.contains(&(e.offset as usize)))
+        .map(|e| (e.offset as usize, e.value))
+        .collect();
+    assert_eq!(
+        writes,
+        [
+            (SIM_IRQ.layout.enable_offset, 0x0003_0000),
+            (SIM_IRQ.layout.enable_offset, 0x0002_0002),
+            (SIM_IRQ.layout.status_offset, 0b11),
+            (SIM_IRQ.layout.status_offset, 0b01),
+        ]
+    );
+
+    // Enable registers with the opposite polarity
+    let layout = IrqLayout {
+        masked_when_set: false,
+        ..SIM_IRQ.layout
+    };
+    let pmu = Rk3588Pmu::new().with_irq(SimIrq { layout, ..SIM_IRQ });
+    let mut pm = RockchipPM::with_regs(pmu, RkBoard::Rk3588).with_irq(layout);
+    pm.enable_irq(IRQ_PWR_GATE | IRQ_UNUSED).unwrap();
+    assert_eq!(
+        pm.regs().peek(layout.enable_offset),
+        IRQ_PWR_GATE | IRQ_UNUSED
+    );
+    pm.mask_irq(IRQ_PWR_GATE).unwrap();
+    assert_eq!(pm.regs().peek(layout.enable_offset), IRQ_UNUSED);
+    pm.power_domain_off(RK3588::AV1).unwrap();
+    assert!(!pm.regs().is_irq_asserted());
+}
+
+#[test]
+fn test_irq_wakes_suspended_transition() {
+    let regs = FaultInjector::new(irq_rk3588_pmu());
+    let pm = RockchipPM::with_regs(&regs, RkBoard::Rk3588)
+        .with_time_source(AlarmClock::new())
+        .with_irq(SIM_IRQ.layout);
+    let irq = pm.irq_handle().unwrap();
+    irq.enable_irq(IRQ_PWR_GATE | IRQ_BUS_IDLE).unwrap();
+    // The acknowledgment is missing from the reads before and after the wait
+    // registers for the interrupt
+    regs.inject(Fault::Delayed {
+        offset: rk3588::BUS_IDLE_ACK.offset(),
+        mask: AV1_IDLE,
+        polls: 2,
+    });
+
+    let wakes = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let waker = Waker::from(wakes.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut off = pin!(pm.power_domain_off_async(RK3588::AV1));
+
+    // The wait suspends without asking to be polled again
+    assert_eq!(off.as_mut().poll(&mut cx), Poll::Pending);
+    assert_eq!(wakes.0.load(Ordering::Relaxed), 0);
+    assert_eq!(irq.waiting_transitions(), 1);
+
+    // The completed idle request interrupts, and the handler resumes the wait
+    assert!(regs.inner().is_irq_asserted());
+    assert_eq!(irq.handle_irq(), Ok(IRQ_BUS_IDLE));
+    assert_eq!(wakes.0.load(Ordering::Relaxed), 1);
+    assert_eq!(irq.waiting_transitions(), 0);
+    assert_eq!(off.as_mut().poll(&mut cx), Poll::Ready(Ok(())));
+    assert_eq!(pm.is_domain_on(&RK3588::AV1), Ok(false));
+
+    // Switching the power off raised the next event
+    assert!(regs.inner().is_irq_asserted());
+    assert_eq!(irq.handle_irq(), Ok(IRQ_PWR_GATE));
+}
+
+#[test]
+fn test_irq_handle_on_another_thread() {
+    let mut window = vec![0u32; 0x400 / 4];
+    let base = NonNull::new(window.as_mut_ptr().cast::<u8>()).unwrap();
+    let pm = RockchipPM::new(base, 0x400, RkBoard::Rk3588)
+        .unwrap()
+        .with_irq(SIM_IRQ.layout);
+    let irq: IrqHandle = pm.irq_handle().unwrap();
+
+    // The handler shares nothing with the driver but the MMIO window
+    pm.regs()
+        .write_u32(SIM_IRQ.layout.status_offset, IRQ_UNUSED)
+        .unwrap();
+    let pending = std::thread::scope(|s| s.spawn(|| irq.handle_irq()).join().unwrap());
+    assert_eq!(pending, Ok(IRQ_UNUSED));
+    assert_eq!(pm.waiting_transitions(), 0);
+}
+
+#[test]
+fn test_irq_wait_times_out_without_interrupt() {
+    let clock = AlarmClock::new();
+    let mut pm = faulty_rk3588_pm()
+        .with_time_source(clock.clone())
+        .with_irq(SIM_IRQ.layout);
+    pm.timeouts_mut().set_step(TimeoutStep::IdleAck, 500);
+    pm.regs().inject(Fault::Stuck {
+        offset: rk3588::BUS_IDLE_ACK.offset(),
+        mask: AV1_IDLE,
+        value: 0,
+    });
+
+    let wakes = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let waker = Waker::from(wakes.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut off = pin!(pm.power_domain_off_async(RK3588::AV1));
+
+    // The wait suspends with a wakeup armed at the end of its budget
+    assert_eq!(off.as_mut().poll(&mut cx), Poll::Pending);
+    assert_eq!(pm.waiting_transitions(), 1);
+    assert_eq!(clock.armed(), 1);
+
+    // No interrupt arrives: the deadline wakes the task and it times out
+    let deadline = clock.fire_next().unwrap();
+    assert_eq!(wakes.0.load(Ordering::Relaxed), 1);
+    assert_eq!(
+        off.as_mut().poll(&mut cx),
+        Poll::Ready(Err(PowerError::IdleAckTimeout))
+    );
+    assert!(clock.now_us() >= deadline);
+    assert_eq!(pm.waiting_transitions(), 0);
+}
+
+#[test]
+fn test_irq_wait_without_timer_keeps_polling() {
+    let pm = faulty_rk3588_pm()
+        .with_time_source(PollCounter::new())
+        .with_irq(SIM_IRQ.layout);
+    pm.regs().inject(Fault::StuckFor {
+        offset: rk3588::BUS_IDLE_ACK.offset(),
+        mask: AV1_IDLE,
+        value: 0,
+        reads: 3,
+    });
+
+    let wakes = Arc::new(CountingWaker(AtomicUsize::new(0)));
+    let waker = Waker::from(wakes.clone());
+    let mut cx = Context::from_waker(&waker);
+    let mut off = pin!(pm.power_domain_off_async(RK3588::AV1));
+
+    // Nothing could wake the wait at its timeout, so it doesn't suspend
+    assert_eq!(off.as_mut().poll(&mut cx), Poll::Pending);
+    assert_eq!(wakes.0.load(Ordering::Relaxed), 1);
+    assert_eq!(pm.waiting_transitions(), 0);
+    while off.as_mut().poll(&mut cx).is_pending() {}
+    assert_eq!(pm.is_domain_on(&RK3588::AV1), Ok(false));
+}
+
+#[test]
+fn test_irq_blocking_transitions_still_poll() {
+    let mut pm = faulty_rk3588_pm().with_irq(SIM_IRQ.layout);
+    pm.regs().inject(Fault::Delayed {
+        offset: rk3588::BUS_IDLE_ACK.offset(),
+        mask: AV1_IDLE,
+        polls: 10,
+    });
+    pm.power_domain_off(RK3588::AV1).unwrap();
+    assert_eq!(pm.waiting_transitions(), 0);
+}
+
+// ========================================
+// QoS save and restore
+// ========================================
+
+#[test]
+fn test_qos_restored_after_power_cycle() {
+    let mut qos = QosMemory::new(QOS_GPU_SIZE);
+    let mut pm = rk3588_pm().with_qos_window(qos.base(), QOS_GPU_PHYS, QOS_GPU_SIZE);
+    assert_eq!(pm.qos_ports(RK3588::GPU).unwrap().len(), 4);
+
+    qos.write(QOS_PRIORITY, 0x0202);
+    qos.write(QOS_GPU_M1 + QOS_MODE, 0x1);
+
+    pm.power_domain_off(RK3588::GPU).unwrap();
+    assert!(pm.has_qos_state(RK3588::GPU));
+    qos.reset();
+
+    pm.power_domain_on(RK3588::GPU).unwrap();
+    assert_eq!(qos.read(QOS_PRIORITY), 0x0202);
+    assert_eq!(qos.read(QOS_GPU_M1 + QOS_MODE), 0x1);
+
+    // Every power off saves the registers again, overriding the old state
+    qos.reset();
+    pm.power_domain_off(RK3588::GPU).unwrap();
+    qos.write(QOS_PRIORITY, 0x0303);
+    pm.power_domain_on(RK3588::GPU).unwrap();
+    assert_eq!(qos.read(QOS_PRIORITY), 0);
+
+    // Without saved state the registers keep their reset values
+    pm.power_domain_off(RK3588::GPU).unwrap();
+    pm.clear_qos_state(RK3588::GPU);
+    qos.write(QOS_PRIORITY, 0x0303);
+    pm.power_domain_on(RK3588::GPU).unwrap();
+    assert_eq!(qos.read(QOS_PRIORITY), 0x0303);
+}
+
+#[test]
+fn test_qos_repeated_transitions() {
+    let mut qos = QosMemory::new(QOS_GPU_SIZE);
+    let mut pm = rk3588_pm().with_qos_window(qos.base(), QOS_GPU_PHYS, QOS_GPU_SIZE);
+    qos.write(QOS_PRIORITY, 0x0202);
+
+    // Powering off an off domain neither reads its QoS registers nor replaces
+    // the state saved at the real power off
+    pm.power_domain_off(RK3588::GPU).unwrap();
+    qos.reset();
+    pm.power_domain_off(RK3588::GPU).unwrap();
+    pm.power_domain_on(RK3588::GPU).unwrap();
+    assert_eq!(qos.read(QOS_PRIORITY), 0x0202);
+
+    // Powering on an on domain doesn't restore over live settings
+    qos.write(QOS_PRIORITY, 0x0303);
+    pm.power_domain_on(RK3588::GPU).unwrap();
+    assert_eq!(qos.read(QOS_PRIORITY), 0x0303);
+}
+
+#[test]
+fn test_qos_mapper_callback() {
+    let mut qos = QosMemory::new(QOS_GPU_SIZE);
+    let window = qos.window();
+
+    // Any closure translating physical addresses works as a mapper
+    let mut pm = rk3588_pm().with_qos_mapper(move |phys_addr, size| window.map(phys_addr, size));
+    qos.write(QOS_PRIORITY, 0x0101);
+    pm.power_domain_off(RK3588::GPU).unwrap();
+    qos.reset();
+    pm.power_domain_on(RK3588::GPU).unwrap();
+    assert_eq!(qos.read(QOS_PRIORITY), 0x0101);
+}
+
+#[test]
+fn test_qos_ports_mapped_once() {
+    let mut qos = QosMemory::new(QOS_GPU_SIZE);
+    let window = qos.window();
+    let calls = Arc::new(AtomicUsize::new(0));
+    let counter = calls.clone();
+
+    // A mapper creating a new mapping per call must not leak one per access
+    let mut pm = rk3588_pm().with_qos_mapper(move |phys_addr, size| {
+        counter.fetch_add(1, Ordering::Relaxed);
+        window.map(phys_addr, size)
+    });
+    for _ in 0..3 {
+        pm.power_domain_off(RK3588::GPU).unwrap();
+        pm.power_domain_on(RK3588::GPU).unwrap();
+    }
+    let settings = pm.qos_settings(RK3588::GPU, 0).unwrap();
+    pm.set_qos_settings(RK3588::GPU, 0, &settings).unwrap();
+    pm.import_qos_state(&pm.export_qos_state().unwrap())
+        .unwrap();
+    assert_eq!(calls.load(Ordering::Relaxed), 4);
+}
+
+#[test]
+fn test_qos_without_mapping() {
+    // Without a mapper QoS is left alone, which is reported once
+    assert!(warnings().is_empty());
+    let mut pm = rk3588_pm();
+    pm.power_domain_off(RK3588::GPU).unwrap();
+    assert!(!pm.has_qos_state(RK3588::GPU));
+    pm.power_domain_on(RK3588::GPU).unwrap();
+    pm.power_domain_off(RK3588::GPU).unwrap();
+    pm.power_domain_off(RK3588::AV1).unwrap();
+    let logged = warnings();
+    assert_eq!(logged.len(), 1);
+    assert!(
+        logged[0].starts_with("gpu: QoS of 4 port(s) not saved"),
+        "{logged:?}"
+    );
+    pm.power_domain_on(RK3588::GPU).unwrap();
+
+    // Domains without QoS ports have nothing to report
+    let mut pm = rk3588_pm();
+    pm.power_domain_off(RK3588::NVM).unwrap();
+    assert_eq!(warnings().len(), 1);
+
+    // A port outside the window fails the power off before anything is written
+    let mut qos = QosMemory::new(QOS_PORT_SIZE);
+    let mut pm = rk3588_pm().with_qos_window(qos.base(), QOS_GPU_PHYS, QOS_PORT_SIZE);
+    assert_eq!(pm.power_domain_off(RK3588::GPU), Err(PowerError::QoSError));
+    assert!(pm.regs().is_domain_powered(RK3588::GPU));
+    assert!(!pm.has_qos_state(RK3588::GPU));
+
+    let window = qos.window();
+    assert!(window.map(QOS_GPU_PHYS, QOS_PORT_SIZE).is_some());
+    assert!(window.map(QOS_GPU_PHYS + 4, QOS_PORT_SIZE).is_none());
+    assert!(window.map(QOS_GPU_PHYS - 4, 4).is_none());
+}
+
+#[test]
+fn test_qos_settings_tuning() {
+    let mut qos = QosMemory::new(QOS_GPU_SIZE);
+    let mut pm = rk3588_pm().with_qos_window(qos.base(), QOS_GPU_PHYS, QOS_GPU_SIZE);
+
+    qos.write(QOS_PRIORITY, 0x0101);
+    assert_eq!(
+        pm.qos_settings(RK3588::GPU, 0).unwrap(),
+        QosSettings {
+            read_priority: 1,
+            write_priority: 1,
+            ..Default::default()
+        }
+    );
+
+    // Raise the priority of the first port while the GPU is powered
+    let high = QosSettings {
+        read_priority: 3,
+        write_priority: 3,
+        ..Default::default()
+    };
+    pm.set_qos_settings(RK3588::GPU, 0, &high).unwrap();
+    assert_eq!(qos.read(QOS_PRIORITY), 0x0303);
+
+    // Tuned values are saved at power off and can still be changed
+    pm.power_domain_off(RK3588::GPU).unwrap();
+    qos.reset();
+    assert_eq!(pm.qos_settings(RK3588::GPU, 0).unwrap(), high);
+    let limited = QosSettings {
+        mode: QosMode::Limiter,
+        bandwidth: 0x100,
+        saturation: 0x40,
+        ..Default::default()
+    };
+    pm.set_qos_settings(RK3588::GPU, 1, &limited).unwrap();
+    assert_eq!(qos.read(QOS_GPU_M1 + QOS_MODE), 0);
+
+    pm.power_domain_on(RK3588::GPU).unwrap();
+    assert_eq!(qos.read(QOS_PRIORITY), 0x0303);
+    assert_eq!(pm.qos_settings(RK3588::GPU, 1).unwrap(), limited);
+}
+
+#[test]
+fn test_qos_settings_keep_reserved_bits() {
+    let mut qos = QosMemory::new(QOS_GPU_SIZE);
+    let mut pm = rk3588_pm().with_qos_window(qos.base(), QOS_GPU_PHYS, QOS_GPU_SIZE);
+
+    qos.write(QOS_PRIORITY, 0x8000_0302);
+    qos.write(QOS_MODE, 0xc000_0001);
+    let mut settings = pm.qos_settings(RK3588::GPU, 0).unwrap();
+    assert_eq!((settings.read_priority, settings.write_priority), (3, 2));
+
+    settings.read_priority = 1;
+    settings.mode = QosMode::Bypass;
+    pm.set_qos_settings(RK3588::GPU, 0, &settings).unwrap();
+    assert_eq!(qos.read(QOS_PRIORITY), 0x8000_0102);
+    assert_eq!(qos.read(QOS_MODE), 0xc000_0002);
+
+    // The saved copy of a powered off domain is merged the same way
+    pm.power_domain_off(RK3588::GPU).unwrap();
+    qos.reset();
+    settings.write_priority = 0;
+    pm.set_qos_settings(RK3588::GPU, 0, &settings).unwrap();
+    pm.power_domain_on(RK3588::GPU).unwrap();
+    assert_eq!(qos.read(QOS_PRIORITY), 0x8000_0100);
+    assert_eq!(qos.read(QOS_MODE), 0xc000_0002);
+}
+
+#[test]
+fn test_qos_settings_errors() {
+    let mut qos = QosMemory::new(QOS_GPU_SIZE);
+    let mut pm = rk3588_pm().with_qos_window(qos.base(), QOS_GPU_PHYS, QOS_GPU_SIZE);
+
+    assert_eq!(
+        pm.qos_settings(RK3588::GPU, 4),
+        Err(PowerError::InvalidQoSConfig)
+    );
+    let invalid = QosSettings {
+        write_priority: 4,
+        ..Default::default()
+    };
+    assert_eq!(
+        pm.set_qos_settings(RK3588::GPU, 0, &invalid),
+        Err(PowerError::InvalidQoSConfig)
+    );
+    assert_eq!(qos.read(QOS_PRIORITY), 0);
+
+    // Registers of a domain powered off without saved state are gone
+    pm.power_domain_off(RK3588::GPU).unwrap();
+    pm.clear_qos_state(RK3588::GPU);
+    assert_eq!(pm.qos_settings(RK3588::GPU, 0), Err(PowerError::QoSError));
+    assert_eq!(
+        pm.set_qos_settings(RK3588::GPU, 0, &QosSettings::default()),
+        Err(PowerError::QoSError)
+    );
+
+    // Without a mapper the ports can't be reached at all
+    let pm = rk3588_pm();
+    assert_eq!(pm.qos_settings(RK3588::GPU, 0), Err(PowerError::QoSError));
+}
+
+#[test]
+fn test_qos_profiles() {
+    let mut qos = QosMemory::new(QOS_SPAN);
+    let mut pm = rk3588_pm().with_qos_window(qos.base(), QOS_GPU_PHYS, QOS_SPAN);
+    let gpu0 = qos_offset(&pm, RK3588::GPU, 0);
+    let gpu1 = qos_offset(&pm, RK3588::GPU, 1);
+    let vop0 = qos_offset(&pm, RK3588::VOP, 0);
+    qos.write(gpu0 + QOS_PRIORITY, 0x0101);
+
+    let high = QosSettings {
+        read_priority: 3,
+        write_priority: 3,
+        ..Default::default()
+    };
+    let display_first = QosProfile::new("display-first")
+        .with(RK3588::VOP, 0, high)
+        .with(RK3588::GPU, 0, QosSettings::default());
+    pm.apply_qos_profile(&display_first).unwrap();
+    assert_eq!(pm.active_qos_profile(), Some("display-first"));
+    assert_eq!(qos.read(vop0 + QOS_PRIORITY), 0x0303);
+    assert_eq!(qos.read(gpu0 + QOS_PRIORITY), 0);
+
+    // Switching profiles puts ports the new one doesn't cover back first
+    let gpu_limited = QosProfile::new("gpu-limited").with(
+        RK3588::GPU,
+        1,
+        QosSettings {
+            mode: QosMode::Limiter,
+            bandwidth: 0x80,
+            ..Default::default()
+        },
+    );
+    pm.apply_qos_profile(&gpu_limited).unwrap();
+    assert_eq!(pm.active_qos_profile(), Some("gpu-limited"));
+    assert_eq!(qos.read(vop0 + QOS_PRIORITY), 0);
+    assert_eq!(qos.read(gpu0 + QOS_PRIORITY), 0x0101);
+    assert_eq!(qos.read(gpu1 + QOS_MODE), 1);
+
+    pm.revert_qos_profile().unwrap();
+    assert_eq!(pm.active_qos_profile(), None);
+    assert_eq!(qos.read(gpu1 + QOS_MODE), 0);
+    assert_eq!(qos.read(gpu0 + QOS_PRIORITY), 0x0101);
+
+    // Reverting without a profile does nothing
+    pm.revert_qos_profile().unwrap();
+}
+
+#[test]
+fn test_qos_profile_atomic() {
+    let mut qos = QosMemory::new(QOS_SPAN);
+    let mut pm = rk3588_pm().with_qos_window(qos.base(), QOS_GPU_PHYS, QOS_SPAN);
+    let gpu0 = qos_offset(&pm, RK3588::GPU, 0);
+    qos.write(gpu0 + QOS_PRIORITY, 0x0101);
+
+    let high = QosSettings {
+        read_priority: 3,
+        write_priority: 3,
+        ..Default::default()
+    };
+    let display_first = QosProfile::new("display-first")
+        .with(RK3588::GPU, 0, high)
+        .with(RK3588::VOP, 0, high);
+
+    // A powered off domain rejects the whole profile
+    pm.power_domain_off(RK3588::VOP).unwrap();
+    assert_eq!(
+        pm.apply_qos_profile(&display_first),
+        Err(PowerError::DomainNotPowered)
+    );
+    assert_eq!(qos.read(gpu0 + QOS_PRIORITY), 0x0101);
+    assert_eq!(pm.active_qos_profile(), None);
+
+    // So do invalid ports and settings
+    pm.power_domain_on(RK3588::VOP).unwrap();
+    let bad_port = display_first.clone().with(RK3588::GPU, 9, high);
+    assert_eq!(
+        pm.apply_qos_profile(&bad_port),
+        Err(PowerError::InvalidQoSConfig)
+    );
+    let bad_setting = display_first.clone().with(
+        RK3588::VOP,
+        1,
+        QosSettings {
+            saturation: 0x400,
+            ..Default::default()
+        },
+    );
+    assert_eq!(
+        pm.apply_qos_profile(&bad_setting),
+        Err(PowerError::InvalidQoSConfig)
+    );
+    assert_eq!(qos.read(gpu0 + QOS_PRIORITY), 0x0101);
+
+    // An applied profile survives a power cycle, and reverting it while one
+    // of its domains is off updates that domain's saved state
+    pm.apply_qos_profile(&display_first).unwrap();
+    pm.power_domain_off(RK3588::GPU).unwrap();
+    qos.reset();
+    pm.power_domain_on(RK3588::GPU).unwrap();
+    assert_eq!(qos.read(gpu0 + QOS_PRIORITY), 0x0303);
+    pm.power_domain_off(RK3588::GPU).unwrap();
+    qos.reset();
+    pm.revert_qos_profile().unwrap();
+    assert_eq!(pm.active_qos_profile(), None);
+    assert_eq!(qos.read(gpu0 + QOS_PRIORITY), 0);
+    assert_eq!(pm.qos_settings(RK3588::GPU, 0).unwrap().read_priority, 1);
+    pm.power_domain_on(RK3588::GPU).unwrap();
+    assert_eq!(qos.read(gpu0 + QOS_PRIORITY), 0x0101);
+
+    // A domain that is off without saved state keeps the profile applied
+    let vop0 = qos_offset(&pm, RK3588::VOP, 0);
+    pm.apply_qos_profile(&display_first).unwrap();
+    pm.power_domain_off(RK3588::GPU).unwrap();
+    pm.clear_qos_state(RK3588::GPU);
+    assert_eq!(pm.revert_qos_profile(), Err(PowerError::QoSError));
+    assert_eq!(pm.active_qos_profile(), Some("display-first"));
+    assert_eq!(qos.read(vop0 + QOS_PRIORITY), 0x0303);
+}
+
+#[test]
+fn test_qos_profile_switch_with_domain_off() {
+    let mut qos = QosMemory::new(QOS_SPAN);
+    let mut pm = rk3588_pm().with_qos_window(qos.base(), QOS_GPU_PHYS, QOS_SPAN);
+    let gpu0 = qos_offset(&pm, RK3588::GPU, 0);
+    let vi0 = qos_offset(&pm, RK3588::VI, 0);
+    qos.write(vi0 + QOS_PRIORITY, 0x0101);
+
+    let high = QosSettings {
+        read_priority: 3,
+        write_priority: 3,
+        ..Default::default()
+    };
+    let camera = QosProfile::new("camera")
+        .with(RK3588::GPU, 0, high)
+        .with(RK3588::VI, 0, high);
+    let gpu_only = QosProfile::new("gpu").with(RK3588::GPU, 0, high);
+
+    // VI, only covered by the old profile, goes back to its old settings in
+    // its saved state
+    pm.apply_qos_profile(&camera).unwrap();
+    pm.power_domain_off(RK3588::VI).unwrap();
+    qos.write(vi0 + QOS_PRIORITY, 0);
+    pm.apply_qos_profile(&gpu_only).unwrap();
+    assert_eq!(pm.active_qos_profile(), Some("gpu"));
+    assert_eq!(qos.read(gpu0 + QOS_PRIORITY), 0x0303);
+    assert_eq!(qos.read(vi0 + QOS_PRIORITY), 0);
+    pm.power_domain_on(RK3588::VI).unwrap();
+    assert_eq!(qos.read(vi0 + QOS_PRIORITY), 0x0101);
+
+    // Domains named by the new profile still have to be powered
+    pm.power_domain_off(RK3588::VI).unwrap();
+    assert_eq!(
+        pm.apply_qos_profile(&camera),
+        Err(PowerError::DomainNotPowered)
+    );
+    assert_eq!(pm.active_qos_profile(), Some("gpu"));
+}
+
+#[test]
+fn test_qos_snapshot_export_import() {
+    let mut qos = QosMemory::new(QOS_SPAN);
+    let mut pm = rk3588_pm().with_qos_window(qos.base(), QOS_GPU_PHYS, QOS_SPAN);
+    let gpu1 = qos_offset(&pm, RK3588::GPU, 1);
+    let vop0 = qos_offset(&pm, RK3588::VOP, 0);
+
+    qos.write(gpu1 + QOS_PRIORITY, 0x0202);
+    qos.write(vop0 + QOS_MODE, 0x3);
+    pm.power_domain_off(RK3588::GPU).unwrap();
+    pm.power_domain_off(RK3588::VOP).unwrap();
+    let snapshot = pm.export_qos_state().unwrap();
+    qos.reset();
+
+    // A fresh driver, as after a warm reboot, takes over the state and
+    // restores it at the next power on
+    let mut pm = rk3588_pm().with_qos_window(qos.base(), QOS_GPU_PHYS, QOS_SPAN);
+    pm.power_domain_off(RK3588::GPU).unwrap();
+    pm.power_domain_off(RK3588::VOP).unwrap();
+    assert_eq!(pm.import_qos_state(&snapshot), Ok(2));
+    assert_eq!(pm.export_qos_state(), Ok(snapshot));
+
+    pm.power_domain_on(RK3588::GPU).unwrap();
+    pm.power_domain_on(RK3588::VOP).unwrap();
+    assert_eq!(qos.read(gpu1 + QOS_PRIORITY), 0x0202);
+    assert_eq!(qos.read(vop0 + QOS_MODE), 0x3);
+
+    // An empty snapshot imports nothing
+    let empty = rk3588_pm().export_qos_state().unwrap();
+    assert_eq!(pm.import_qos_state(&empty), Ok(0));
+}
+
+#[test]
+fn test_qos_snapshot_import_powered() {
+    let mut qos = QosMemory::new(QOS_GPU_SIZE);
+    let mut pm = rk3588_pm().with_qos_window(qos.base(), QOS_GPU_PHYS, QOS_GPU_SIZE);
+    qos.write(QOS_GPU_M1 + QOS_PRIORITY, 0x0303);
+    pm.power_domain_off(RK3588::GPU).unwrap();
+    let snapshot = pm.export_qos_state().unwrap();
+    qos.reset();
+
+    // A powered domain is programmed right away
+    let mut pm = rk3588_pm().with_qos_window(qos.base(), QOS_GPU_PHYS, QOS_GPU_SIZE);
+    qos.write(QOS_GPU_M1 + QOS_PRIORITY, 0x0101);
+    assert_eq!(pm.import_qos_state(&snapshot), Ok(1));
+    assert_eq!(qos.read(QOS_GPU_M1 + QOS_PRIORITY), 0x0303);
+
+    // And keeps the imported settings across a power cycle
+    pm.power_domain_off(RK3588::GPU).unwrap();
+    qos.reset();
+    pm.power_domain_on(RK3588::GPU).unwrap();
+    assert_eq!(qos.read(QOS_GPU_M1 + QOS_PRIORITY), 0x0303);
+}
+
+#[test]
+fn test_qos_snapshot_rejected() {
+    let mut qos = QosMemory::new(QOS_SPAN);
+    let mut pm = rk3588_pm().with_qos_window(qos.base(), QOS_GPU_PHYS, QOS_SPAN);
+    pm.power_domain_off(RK3588::GPU).unwrap();
+    let snapshot = pm.export_qos_state().unwrap();
+
+    let mut pm = rk3588_pm().with_qos_window(qos.base(), QOS_GPU_PHYS, QOS_SPAN);
+    let mut corrupted = snapshot.clone();
+    corrupted[16] ^= 0x80;
+    assert_eq!(
+        pm.import_qos_state(&corrupted),
+        Err(PowerError::InvalidQoSSnapshot)
+    );
+    assert_eq!(
+        pm.import_qos_state(&snapshot[..snapshot.len() - 4]),
+        Err(PowerError::InvalidQoSSnapshot)
+    );
+    assert!(!pm.has_qos_state(RK3588::GPU));
+
+    // Snapshots only fit the chip they were taken on
+    let mut rk3568 = rk3568_pm().with_qos_window(qos.base(), QOS_GPU_PHYS, QOS_SPAN);
+    assert_eq!(
+        rk3568.import_qos_state(&snapshot),
+        Err(PowerError::InvalidQoSSnapshot)
+    );
+    let gpu_ports = pm.qos_ports(RK3588::GPU).unwrap().len();
+    assert_eq!(
+        rk3568.export_qos_state().unwrap().len(),
+        snapshot.len() - 8 - gpu_ports * 5 * 4
+    );
+
+    // Importing needs the ports mapped
+    let mut pm = rk3588_pm();
+    assert_eq!(pm.import_qos_state(&snapshot), Err(PowerError::QoSError));
+}
+
+// ========================================
+// Trace recording and replay
+// ========================================
+
+#[test]
+fn test_trace_records_steps() {
+    let trace = record_av1_cycle();
+    let events = trace.events();
+
+    // The power-off checks the current state, then writes the idle request
+    // and the power gate
+    assert_eq!(events[0].access, Access::Read);
+    assert_eq!(events[0].offset, rk3588::REPAIR_STATUS.offset() as u32);
+    assert_eq!(events[0].step, None);
+    assert_eq!(events[1].access, Access::Write);
+    assert_eq!(events[1].offset, rk3588::BUS_IDLE_REQ0.offset() as u32);
+    assert_eq!(events[1].value, AV1_IDLE | (AV1_IDLE << 16));
+    assert_eq!(events[1].step, Some((RK3588::AV1, PowerStep::IdleRequest)));
+
+    let power_write = events
+        .iter()
+        .find(|e| e.step == Some((RK3588::AV1, PowerStep::PowerWrite)))
+        .unwrap();
+    assert_eq!(power_write.access, Access::Write);
+    assert_eq!(power_write.offset, rk3588::PWR_GATE_CON0.offset() as u32);
+    assert_eq!(power_write.value, AV1_PWR | (AV1_PWR << 16));
+
+    // Queries outside a sequence carry no step
+    let pm = RockchipPM::with_regs(TraceRecorder::new(Rk3588Pmu::new()), RkBoard::Rk3588);
+    pm.is_domain_on(&RK3588::AV1).unwrap();
+    assert_eq!(pm.regs().trace().events()[0].step, None);
+}
+
+#[test]
+fn test_trace_skips_current_state() {
+    let mut pm = RockchipPM::with_regs(TraceRecorder::new(Rk3588Pmu::new()), RkBoard::Rk3588);
+
+    // A transition to the state the domain is already in only reads its status
+    pm.power_domain_on(RK3588::AV1).unwrap();
+    let trace = pm.regs().take_trace();
+    assert_eq!(trace.events().len(), 1);
+    assert_eq!(trace.events()[0].access, Access::Read);
+
+    pm.power_domain_off(RK3588::AV1).unwrap();
+    pm.regs().take_trace();
+    pm.power_domain_off(RK3588::AV1).unwrap();
+    let trace = pm.regs().take_trace();
+    assert_eq!(trace.events().len(), 1);
+    assert_eq!(trace.events()[0].access, Access::Read);
+}
+
+#[test]
+fn test_trace_serialization_round_trip() {
+    let trace = record_av1_cycle();
+    let bytes = trace.to_bytes();
+
+    assert_eq!(Trace::from_bytes(&bytes), Some(trace));
+    assert_eq!(Trace::from_bytes(&bytes[..bytes.len() - 1]), None);
+    assert_eq!(Trace::from_bytes(b"nope"), None);
+}
+
+#[test]
+fn test_trace_matches_golden() {
+    let bytes = record_av1_cycle().to_bytes();
+    if std::env::var_os("UPDATE_GOLDEN").is_some() {
+        std::fs::write(AV1_CYCLE_GOLDEN, &bytes).unwrap();
+    }
+
+    let golden = Trace::from_bytes(&std::fs::read(AV1_CYCLE_GOLDEN).unwrap()).unwrap();
+    let actual = Trace::from_bytes(&bytes).unwrap();
+    assert_eq!(golden.first_divergence(&actual), None);
+}
+
+#[test]
+fn test_trace_replay() {
+    let golden = Trace::from_bytes(&std::fs::read(AV1_CYCLE_GOLDEN).unwrap()).unwrap();
+
+    let mut pm = RockchipPM::with_regs(TraceReplayer::new(golden), RkBoard::Rk3588);
+    pm.power_domain_off(RK3588::AV1).unwrap();
+    pm.power_domain_on(RK3588::AV1).unwrap();
+    assert!(pm.regs().is_complete());
+    assert_eq!(pm.regs().divergence(), None);
+}
+
+#[test]
+fn test_trace_replay_divergence() {
+    let golden = Trace::from_bytes(&std::fs::read(AV1_CYCLE_GOLDEN).unwrap()).unwrap();
+
+    // Powering a different domain diverges on the very first write, right
+    // after the state check
+    let mut pm = RockchipPM::with_regs(TraceReplayer::new(golden), RkBoard::Rk3588);
+    assert_eq!(
+        pm.power_domain_off(RK3588::VDPU),
+        Err(PowerError::HardwareError)
+    );
+
+    let divergence = pm.regs().divergence().unwrap();
+    assert_eq!(divergence.index, 1);
+    assert_eq!(
+        divergence.actual.step,
+        Some((RK3588::VDPU, PowerStep::IdleRequest))
+    );
+    assert!(!pm.regs().is_complete());
+}
@@ -1,14 +1,26 @@
-#![no_std]
-#![no_main]
-#![feature(used_with_arg)]
+//! Board tests, run on RK3588 hardware (or QEMU) through bare-test.
+//!
+//! On a host target this file builds to an empty binary; host-side coverage
+//! lives in `tests/sim.rs`.
 
+#![cfg_attr(target_os = "none", no_std, no_main)]
+#![cfg_attr(target_os = "none", feature(used_with_arg))]
+
+#[cfg(target_os = "none")]
 extern crate alloc;
+#[cfg(target_os = "none")]
 extern crate bare_test;
+#[cfg(target_os = "none")]
 #[macro_use]
 extern crate log;
 
+#[cfg(target_os = "none")]
 use rockchip_pm::*;
 
+#[cfg(not(target_os = "none"))]
+fn main() {}
+
+#[cfg(target_os = "none")]
 #[bare_test::tests]
 mod tests {
 
@@ -83,9 +95,9 @@ mod tests {
 
     #[test]
     fn test_qos_state_management() {
-        let reg = get_syscon_addr();
+        let (reg, size) = get_syscon_addr();
         let board = RkBoard::Rk3588;
-        let mut pm = RockchipPM::new(reg, board);
+        let mut pm = RockchipPM::new(reg, size, board).unwrap();
 
         // Initially no QoS state
         assert!(
@@ -106,9 +118,9 @@ mod tests {
 
     #[test]
     fn test_parent_child_dependency_power_on_order() {
-        let reg = get_syscon_addr();
+        let (reg, size) = get_syscon_addr();
         let board = RkBoard::Rk3588;
-        let mut pm = RockchipPM::new(reg, board);
+        let mut pm = RockchipPM::new(reg, size, board).unwrap();
 
         // Try to power on child (NPU1) without parent (NPUTOP) - should fail
         let result = pm.power_domain_on_with_deps(RK3588::NPU1);
@@ -135,9 +147,9 @@ mod tests {
 
     #[test]
     fn test_parent_child_dependency_power_off_order() {
-        let reg = get_syscon_addr();
+        let (reg, size) = get_syscon_addr();
         let board = RkBoard::Rk3588;
-        let mut pm = RockchipPM::new(reg, board);
+        let mut pm = RockchipPM::new(reg, size, board).unwrap();
 
         // Power on parent and child
         pm.power_domain_on_with_deps(RK3588::VCODEC).unwrap();
@@ -172,9 +184,9 @@ mod tests {
 
     #[test]
     fn test_multi_level_dependency() {
-        let reg = get_syscon_addr();
+        let (reg, size) = get_syscon_addr();
         let board = RkBoard::Rk3588;
-        let mut pm = RockchipPM::new(reg, board);
+        let mut pm = RockchipPM::new(reg, size, board).unwrap();
 
         // Test VOP → VO0/VO1 hierarchy
         // Power on in correct order: VOP → VO0
@@ -203,9 +215,9 @@ mod tests {
 
     #[test]
     fn test_complex_vcodec_hierarchy() {
-        let reg = get_syscon_addr();
+        let (reg, size) = get_syscon_addr();
         let board = RkBoard::Rk3588;
-        let mut pm = RockchipPM::new(reg, board);
+        let mut pm = RockchipPM::new(reg, size, board).unwrap();
 
         // VCODEC has 4 children: VENC0, VENC1, RKVDEC0, RKVDEC1
         pm.power_domain_on_with_deps(RK3588::VCODEC).unwrap();
@@ -243,10 +255,10 @@ mod tests {
 
     #[test]
     fn test_pm() {
-        let reg = get_syscon_addr();
+        let (reg, size) = get_syscon_addr();
         let board = RkBoard::Rk3588;
 
-        let mut pm = RockchipPM::new(reg, board);
+        let mut pm = RockchipPM::new(reg, size, board).unwrap();
 
         let npu = get_npu_info();
 
@@ -311,7 +323,7 @@ mod tests {
         }
     }
 
-    fn get_syscon_addr() -> NonNull<u8> {
+    fn get_syscon_addr() -> (NonNull<u8>, usize) {
         let PlatformInfoKind::DeviceTree(fdt) = &global_val().platform_info;
         let fdt = fdt.get();
 
@@ -329,7 +341,7 @@ mod tests {
         let start = start & !(page_size() - 1);
         let end = (end + page_size() - 1) & !(page_size() - 1);
         info!("Aligned Syscon address range: 0x{:x} - 0x{:x}", start, end);
-        iomap(start.into(), end - start)
+        (iomap(start.into(), end - start), end - start)
     }
 
     // ========================================
@@ -338,9 +350,9 @@ mod tests {
 
     #[test]
     fn test_independent_domains() {
-        let reg = get_syscon_addr();
+        let (reg, size) = get_syscon_addr();
         let board = RkBoard::Rk3588;
-        let mut pm = RockchipPM::new(reg, board);
+        let mut pm = RockchipPM::new(reg, size, board).unwrap();
 
         // Test independent domains (no dependencies)
         // GPU, RGA30, AV1, FEC, RGA31, etc. have no parent/child relationships
@@ -359,9 +371,9 @@ mod tests {
 
     #[test]
     fn test_get_active_domains_tracking() {
-        let reg = get_syscon_addr();
+        let (reg, size) = get_syscon_addr();
         let board = RkBoard::Rk3588;
-        let mut pm = RockchipPM::new(reg, board);
+        let mut pm = RockchipPM::new(reg, size, board).unwrap();
 
         // Start with no active domains
         assert_eq!(
@@ -405,9 +417,9 @@ mod tests {
 
     #[test]
     fn test_power_domain_without_deps_api() {
-        let reg = get_syscon_addr();
+        let (reg, size) = get_syscon_addr();
         let board = RkBoard::Rk3588;
-        let mut pm = RockchipPM::new(reg, board);
+        let mut pm = RockchipPM::new(reg, size, board).unwrap();
 
         // Test the non-dependency API (power_domain_on/off)
         // These should work but won't enforce dependencies
@@ -423,12 +435,21 @@ mod tests {
 
     #[test]
     fn test_qos_configured_domains() {
-        let reg = get_syscon_addr();
+        let (reg, size) = get_syscon_addr();
         let board = RkBoard::Rk3588;
-        let mut pm = RockchipPM::new(reg, board);
+        // QoS ports are physical addresses; the driver maps each one once, on
+        // first access, and keeps the mapping
+        let mut pm = RockchipPM::new(reg, size, board).unwrap().with_qos_mapper(
+            |phys_addr: u64, size: usize| {
+                let start = phys_addr as usize & !(page_size() - 1);
+                let offset = phys_addr as usize - start;
+                let base = iomap(start.into(), (offset + size).next_multiple_of(page_size()));
+                Some(unsafe { base.add(offset) })
+            },
+        );
 
         // Test domains that have QoS configuration
-        // GPU, NPU, VCODEC, VENC0, RKVDEC0, VOP, VI have QoS ports
+        // GPU, NPUTOP, VENC0/1, RKVDEC0/1, VDPU, VOP, VI and others have QoS ports
 
         pm.power_domain_on_with_deps(RK3588::GPU).unwrap();
         info!("✓ GPU (with QoS) powered on");
@@ -441,13 +462,30 @@ mod tests {
 
         pm.power_domain_off_with_deps(RK3588::GPU).unwrap();
         info!("✓ GPU (with QoS) powered off");
+
+        // The QoS registers were saved at power off and are kept for the next power on
+        assert!(
+            pm.has_qos_state(RK3588::GPU),
+            "QoS state should be saved at power off"
+        );
+
+        pm.power_domain_on_with_deps(RK3588::GPU).unwrap();
+        info!("✓ GPU QoS restored at power on");
+        assert!(
+            pm.has_qos_state(RK3588::GPU),
+            "QoS state should be kept after restore"
+        );
+
+        pm.clear_qos_state(RK3588::GPU);
+        assert!(!pm.has_qos_state(RK3588::GPU));
+        pm.power_domain_off_with_deps(RK3588::GPU).unwrap();
     }
 
     #[test]
     fn test_error_handling_invalid_domain() {
-        let reg = get_syscon_addr();
+        let (reg, size) = get_syscon_addr();
         let board = RkBoard::Rk3588;
-        let mut pm = RockchipPM::new(reg, board);
+        let mut pm = RockchipPM::new(reg, size, board).unwrap();
 
         // Test with non-existent domain ID
         let invalid_domain = PowerDomain::new(9999);
@@ -463,9 +501,9 @@ mod tests {
 
     #[test]
     fn test_vi_isp1_dependency() {
-        let reg = get_syscon_addr();
+        let (reg, size) = get_syscon_addr();
         let board = RkBoard::Rk3588;
-        let mut pm = RockchipPM::new(reg, board);
+        let mut pm = RockchipPM::new(reg, size, board).unwrap();
 
         // Test VI (Video Input) → ISP1 (Image Signal Processor) dependency
 
@@ -489,9 +527,9 @@ mod tests {
 
     #[test]
     fn test_qos_state_clear_methods() {
-        let reg = get_syscon_addr();
+        let (reg, size) = get_syscon_addr();
         let board = RkBoard::Rk3588;
-        let mut pm = RockchipPM::new(reg, board);
+        let mut pm = RockchipPM::new(reg, size, board).unwrap();
 
         // Test QoS state management methods
 